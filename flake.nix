@@ -47,7 +47,13 @@
           craneLib = (crane.mkLib pkgs).overrideToolchain rust-bin;
 
           commonArgs = {
-            src = craneLib.cleanCargoSource ./.;
+            # the bundled license texts and the test fixtures aren't rust sources
+            src = lib.cleanSourceWith {
+              src = ./.;
+              filter =
+                path: type:
+                lib.hasSuffix ".txt" path || lib.hasSuffix ".json" path || craneLib.filterCargoSources path type;
+            };
             strictDeps = true;
 
             buildInputs = with pkgs; [ ];
@@ -98,6 +104,14 @@
               inherit cargoArtifacts;
               pname = (builtins.fromTOML (builtins.readFile ./Cargo.toml)).package.name;
               version = "unstable-${self.shortRev or "dirty"}";
+
+              nativeBuildInputs = [ pkgs.installShellFiles ];
+              postInstall = ''
+                # nit writes its log below $HOME/.cache
+                HOME=$TMPDIR $out/bin/nit man --out man
+                installManPage man/*.1
+                installShellCompletion --cmd nit --zsh <(HOME=$TMPDIR $out/bin/nit completions zsh --dynamic)
+              '';
             }
           );
         };
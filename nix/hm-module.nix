@@ -5,6 +5,200 @@ with lib;
 let
   cfg = config.programs.nit;
   configFormat = pkgs.formats.toml { };
+
+  hooksType = types.submodule {
+    options = {
+      pre_init = mkOption {
+        type = types.listOf types.str;
+        default = [ ];
+        description = "Commands run before nix flake init. Init is aborted if one fails";
+      };
+      post_init = mkOption {
+        type = types.listOf types.str;
+        default = [ ];
+        description = "Commands run after nix flake init";
+      };
+    };
+  };
+
+  matcherKind = types.enum [ "nucleo" "substring" ];
+
+  templateType = types.submodule {
+    options = {
+      name = mkOption {
+        type = types.nullOr types.str;
+        default = null;
+        description = "Optional name for the template collection";
+      };
+      uri = mkOption {
+        type = types.str;
+        description = "Flake URI for templates (e.g., github:NixOS/templates)";
+      };
+      type = mkOption {
+        type = types.enum [ "flake" "devenv" "cookiecutter" "cargo-generate" ];
+        default = "flake";
+        description = "Kind of the source. devenv templates are tagged with `devenv`, cookiecutter and cargo-generate sources take a git URL and are initialized with the respective tool";
+      };
+      tags = mkOption {
+        type = types.listOf types.str;
+        default = [ ];
+        description = "Tags attached to every template of the collection";
+      };
+      netrc = mkOption {
+        type = types.nullOr types.str;
+        default = null;
+        description = "netrc file passed to nix as --netrc-file, for flakes behind HTTP authentication";
+      };
+      ssh = mkOption {
+        type = types.nullOr (types.either types.bool types.str);
+        default = null;
+        description = "true to use git over SSH non-interactively, or the path of the key to use";
+      };
+      env = mkOption {
+        type = types.attrsOf types.str;
+        default = { };
+        description = "Environment variables set for the commands fetching this flake";
+      };
+      pure_eval = mkOption {
+        type = types.bool;
+        default = true;
+        description = "Set to false to pass --impure to nix for this flake";
+      };
+      boost = mkOption {
+        type = types.float;
+        default = 0.0;
+        description = "Added to the frecency bonus of every use, which is 15, so that the templates climb faster than others. Negative values sink them until a query is typed";
+      };
+      boosts = mkOption {
+        type = types.attrsOf types.float;
+        default = { };
+        description = "Per-template boost, overriding boost. Negative values sink the template while the query is empty";
+      };
+      demote = mkOption {
+        type = types.bool;
+        default = false;
+        description = "Sink every template of the collection to the bottom until a query is typed";
+      };
+      demotes = mkOption {
+        type = types.listOf types.str;
+        default = [ ];
+        example = [ "default" ];
+        description = "Templates to sink to the bottom until a query is typed";
+      };
+      follow_registry = mkOption {
+        type = types.bool;
+        default = false;
+        description = "Resolve uri through the flake registry, e.g. templates to github:NixOS/templates, and show the templates under the resolved url";
+      };
+      deprecations = mkOption {
+        type = types.bool;
+        default = false;
+        description = "Read deprecated and replacedBy of the templates of the flake, which costs one more nix eval per fetch";
+      };
+      override = mkOption {
+        type = types.attrsOf (types.submodule {
+          options = {
+            description = mkOption {
+              type = types.nullOr types.str;
+              default = null;
+              description = "Instead of the description of the flake";
+            };
+            tags = mkOption {
+              type = types.nullOr (types.listOf types.str);
+              default = null;
+              description = "Instead of every tag of the template";
+            };
+            icon = mkOption {
+              type = types.nullOr types.str;
+              default = null;
+              description = "Drawn in front of the entry, except with --plain";
+            };
+            display_name = mkOption {
+              type = types.nullOr types.str;
+              default = null;
+              description = "Shown instead of uri#name";
+            };
+            deprecated = mkOption {
+              type = types.bool;
+              default = false;
+              description = "Mark the template and sink it to the bottom until a query is typed";
+            };
+            replaced_by = mkOption {
+              type = types.nullOr types.str;
+              default = null;
+              example = "github:me/templates#rust";
+              description = "The uri#name offered instead when the template is selected. Deprecates it too";
+            };
+          };
+        });
+        default = { };
+        example = { "github:NixOS/templates#rust".description = "Rust with a devshell"; };
+        description = "Per uri#name or template name, replacing what the flake says about the template";
+      };
+      templates = mkOption {
+        type = types.nullOr (types.listOf types.str);
+        default = null;
+        description = "List of specific templates to include. If omitted, imports all templates";
+      };
+      execludes = mkOption {
+        type = types.nullOr (types.listOf types.str);
+        default = null;
+        description = "List of templates to exclude";
+      };
+      rename = mkOption {
+        type = types.listOf (types.submodule {
+          options = {
+            file = mkOption {
+              type = types.str;
+              description = "File to patch, relative to the initialized directory";
+            };
+            from = mkOption {
+              type = types.str;
+              description = "Placeholder to replace with the project name";
+            };
+          };
+        });
+        default = [ ];
+        description = "Rules for patching the project name into the generated files";
+      };
+      replace = mkOption {
+        type = types.listOf (types.submodule {
+          options = {
+            from = mkOption {
+              type = types.str;
+              description = "String to replace in the generated files";
+            };
+            to = mkOption {
+              type = types.str;
+              description = "Replacement. May contain {dir_name}, {user} and {date}";
+            };
+          };
+        });
+        default = [ ];
+        description = "String replacements applied to the generated files after init";
+      };
+      gitignore = mkOption {
+        type = types.listOf types.str;
+        default = [ ];
+        description = "Patterns added to the global gitignore for this collection";
+      };
+      license = mkOption {
+        type = types.nullOr types.str;
+        default = null;
+        description = "License for this collection, instead of the global one";
+      };
+      readme = mkOption {
+        type = types.nullOr types.bool;
+        default = null;
+        description = "Whether to write a README.md for this collection, instead of the global readme";
+      };
+      hooks = mkOption {
+        type = hooksType;
+        default = { };
+        description = "Hooks for this collection, run after the global ones. Shell commands run with NIT_TEMPLATE_URI, NIT_TEMPLATE_NAME and NIT_TARGET_DIR set";
+      };
+    };
+  };
 in
 {
   options.programs.nit = {
@@ -15,32 +209,289 @@ in
     settings = mkOption {
       type = types.submodule {
         options = {
-          template = mkOption {
-            description = "List of template sources";
+          cache = {
+            ttl = mkOption {
+              type = types.nullOr (types.either types.ints.unsigned types.str);
+              default = null;
+              example = "7d";
+              description = "How long the cache stays fresh (seconds, or a number with s/m/h/d). Stale caches are refreshed in the background";
+            };
+            store = mkOption {
+              type = types.enum [ "json" "sqlite" ];
+              default = "json";
+              description = "Where the cache is kept. sqlite loads large catalogs in pages and needs nit built with the sqlite-cache feature";
+            };
+          };
+          ui = {
+            marker = mkOption {
+              type = types.str;
+              default = ">";
+              description = "Drawn in front of the highlighted entry";
+            };
+            filler = mkOption {
+              type = types.str;
+              default = " ";
+              description = "Drawn in front of the other entries";
+            };
+            prompt = mkOption {
+              type = types.str;
+              default = "";
+              example = "❯ ";
+              description = "Drawn in front of the query";
+            };
+            gui = mkOption {
+              type = types.nullOr (types.listOf types.str);
+              default = null;
+              example = [ "rofi" "-dmenu" "-i" "-p" "nit" ];
+              description = "The dmenu-compatible picker of --ui gui. The first of fuzzel, wofi, rofi, bemenu and dmenu in PATH if null";
+            };
+          };
+          performance = {
+            fetch_concurrency = mkOption {
+              type = types.ints.positive;
+              default = 4;
+              description = "How many sources are fetched at once when building the cache. Lower it if GitHub rate limits the fetches";
+            };
+            rate_limit_retries = mkOption {
+              type = types.ints.unsigned;
+              default = 3;
+              description = "How often a rate limited source is retried, waiting twice as long each time starting at a second, before the cache is built without it";
+            };
+          };
+          policy = {
+            allow = mkOption {
+              type = types.listOf types.str;
+              default = [ ];
+              example = [ "github:myorg/*" ];
+              description = "If not empty, nit runs nix only against the flake uris matching one of these. * matches anything";
+            };
+            deny = mkOption {
+              type = types.listOf types.str;
+              default = [ ];
+              example = [ "git+http://*" ];
+              description = "Flake uris nit never runs nix against, even if allowed";
+            };
+          };
+          sources.archive = mkOption {
             type = types.listOf (types.submodule {
               options = {
+                url = mkOption {
+                  type = types.str;
+                  example = "https://artifacts.example.com/templates-1.2.tar.gz";
+                  description = "A tarball of a flake with templates, also checked against the policy";
+                };
+                sha256 = mkOption {
+                  type = types.str;
+                  description = "Of the tarball, checked before it is unpacked into the cache directory";
+                };
                 name = mkOption {
                   type = types.nullOr types.str;
                   default = null;
-                  description = "Optional name for the template collection";
+                };
+                tags = mkOption {
+                  type = types.listOf types.str;
+                  default = [ ];
+                };
+              };
+            });
+            default = [ ];
+            description = "Template flakes downloaded as tarballs, for networks without access to GitHub. Collected as path: flakes";
+          };
+          catalog = mkOption {
+            type = types.listOf (types.either types.str (types.submodule {
+              options = {
+                url = mkOption {
+                  type = types.str;
+                };
+                sha256 = mkOption {
+                  type = types.nullOr types.str;
+                  default = null;
+                  description = "Pins the catalog to this content instead of following it";
+                };
+              };
+            }));
+            default = [ ];
+            example = [ "https://example.com/team-templates.toml" ];
+            description = "TOML files of [[template]] entries, e.g. published by a platform team. Fetched once and again on --re-cache, also checked against the policy. Their entries may only set uri, name, tags, templates, execludes, override, boost(s) and demote(s)";
+          };
+          into = mkOption {
+            type = types.nullOr types.str;
+            default = null;
+            description = "Where templates are initialized without --into, relative to the current directory. Usually set in a .nit/config.toml of a project instead";
+          };
+          git_add = mkOption {
+            type = types.bool;
+            default = false;
+            description = "Run git add on the files a template generated when inside a git repository";
+          };
+          hide_defaults = mkOption {
+            type = types.bool;
+            default = false;
+            description = "Hide the default template of every flake";
+          };
+          gitignore = mkOption {
+            type = types.listOf types.str;
+            default = [ ];
+            example = [ "result" ".direnv/" ];
+            description = "Patterns appended to the .gitignore of the project after init, unless already there";
+          };
+          license = mkOption {
+            type = types.nullOr types.str;
+            default = null;
+            example = "MIT";
+            description = "SPDX identifier of the license written to LICENSE after init, when the template ships none";
+          };
+          author = mkOption {
+            type = types.nullOr types.str;
+            default = null;
+            description = "Copyright holder in the license. git config user.name if null";
+          };
+          readme = mkOption {
+            type = types.bool;
+            default = false;
+            description = "Write a README.md from the description and welcomeText of the template after init, when it ships none";
+          };
+          log_format = mkOption {
+            type = types.enum [ "text" "json" ];
+            default = "text";
+            description = "Format of the log file. json writes one object per line";
+          };
+          resume = mkOption {
+            type = types.bool;
+            default = false;
+            description = "Start with the query the TUI was last closed with, like --resume";
+          };
+          confirm = mkOption {
+            type = types.bool;
+            default = true;
+            description = "Show the uri, revision and target directory of the selected template and ask before initializing it";
+          };
+          language = mkOption {
+            type = types.enum [ "auto" "en" "ja" ];
+            default = "auto";
+            description = "Language of the messages, prompts and hints. auto follows LC_ALL, LC_MESSAGES and LANG";
+          };
+          accessible = mkOption {
+            type = types.bool;
+            default = false;
+            description = "No colors, borders or glyphs, and errors as plain lines, for screen readers and braille terminals, like --plain";
+          };
+          nix_command = mkOption {
+            type = types.nullOr (types.listOf types.str);
+            default = null;
+            example = [ "wsl.exe" "--exec" "sh" "-lc" "exec nix \"$@\"" "nix" ];
+            description = "The program and leading arguments nix is run as. nix, or on Windows without one the nix in WSL, if null";
+          };
+          env = mkOption {
+            type = types.attrsOf types.str;
+            default = { };
+            example = {
+              NIX_SSHOPTS = "-o ProxyJump=bastion";
+              https_proxy = "http://proxy:3128";
+            };
+            description = "Environment variables set for every process nit runs, nix and the hooks included. \${VAR} is replaced with the variable nit was started with";
+          };
+          usage_counts = mkOption {
+            type = types.bool;
+            default = false;
+            description = "Append how many times a template was used to its entry, like (×12)";
+          };
+          notifications = mkOption {
+            type = types.bool;
+            default = false;
+            description = "Send a desktop notification with notify-send when an init or a rebuild of the cache that took over 5 seconds ends";
+          };
+          sort = mkOption {
+            type = types.enum [ "frecency" "recent" ];
+            default = "frecency";
+            description = "How the templates are ordered. recent orders by the last use alone, most recent first. Alt-s switches in the TUI";
+          };
+          matcher = mkOption {
+            type = types.either matcherKind (types.submodule {
+              options = {
+                kind = mkOption {
+                  type = matcherKind;
+                  default = "nucleo";
+                  description = "How the query is matched";
+                };
+                name = mkOption {
+                  type = types.float;
+                  default = 2.0;
+                  description = "How much a match in the name of the template counts";
                 };
                 uri = mkOption {
+                  type = types.float;
+                  default = 1.0;
+                  description = "How much a match in the uri of the flake counts";
+                };
+                description = mkOption {
+                  type = types.float;
+                  default = 0.5;
+                  description = "How much a match in the description counts. The description alone doesn't make a match";
+                };
+              };
+            });
+            default = "nucleo";
+            description = "How the query is matched. nucleo is fuzzy with the syntax of fzf, substring needs every word as is. A set also takes the weights of the name, uri and description";
+          };
+          keymap = mkOption {
+            type = types.enum [ "default" "vim" "emacs" ];
+            default = "default";
+            description = "Key bindings of the TUI. vim starts in normal mode with j/k, gg/G and Ctrl-d/Ctrl-u, / types a query. emacs adds C-n/C-p, C-v/M-v, M-</M-> and C-g";
+          };
+          keys = mkOption {
+            type = types.attrsOf types.str;
+            default = { };
+            example = { "ctrl-n" = "down"; };
+            description = "Bindings on top of keymap, from keys like ctrl-n, alt-v or f5 to actions like down, page-up or exit-without-select";
+          };
+          hooks = mkOption {
+            type = hooksType;
+            default = { };
+            description = "Shell commands run with NIT_TEMPLATE_URI, NIT_TEMPLATE_NAME and NIT_TARGET_DIR set";
+          };
+          bundle = mkOption {
+            type = types.listOf (types.submodule {
+              options = {
+                name = mkOption {
+                  type = types.str;
+                  description = "Shown as bundle#<name>";
+                };
+                description = mkOption {
                   type = types.str;
-                  description = "Flake URI for templates (e.g., github:NixOS/templates)";
+                  default = "";
+                  description = "The templates joined with + if empty";
                 };
                 templates = mkOption {
-                  type = types.nullOr (types.listOf types.str);
-                  default = null;
-                  description = "List of specific templates to include. If omitted, imports all templates";
+                  type = types.listOf types.str;
+                  example = [ "github:NixOS/templates#rust" "github:me/templates#just" ];
+                  description = "uri#name of the templates, initialized in this order into the same directory";
                 };
-                execludes = mkOption {
-                  type = types.nullOr (types.listOf types.str);
-                  default = null;
-                  description = "List of templates to exclude";
+                hooks = mkOption {
+                  type = hooksType;
+                  default = { };
+                  description = "Run once, before the first template and after the last";
                 };
               };
             });
             default = [ ];
+            description = "Templates initialized one after another from a single entry. Files a later template replaces are reported";
+          };
+          profile = mkOption {
+            type = types.attrsOf (types.submodule {
+              options.template = mkOption {
+                type = types.listOf templateType;
+                default = [ ];
+                description = "List of template sources of the profile";
+              };
+            });
+            default = { };
+            description = "Separate template sets with their own cache, selected with --profile";
+          };
+          template = mkOption {
+            description = "List of template sources";
+            type = types.listOf templateType;
+            default = [ ];
             example = literalExpression ''
               [
                 {
@@ -52,7 +503,7 @@ in
           };
         };
       };
-      default = { template = [ ]; };
+      default = { };
       description = "Configuration for nit";
     };
   };
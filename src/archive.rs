@@ -0,0 +1,170 @@
+use std::path::{Path, PathBuf};
+
+use futures::{StreamExt as _, TryStreamExt as _};
+use ltrait::color_eyre::{
+    Result,
+    eyre::{ContextCompat, bail, ensure},
+};
+
+use crate::cancel;
+use crate::config::{ArchiveSource, SourceType, TemplateConfig};
+use crate::download::{curl, sha256};
+use crate::locale::tr;
+
+/// Where `archive` is unpacked. Named after the checksum, so it's downloaded only once and a new
+/// tarball never mixes with the files of the old one
+pub fn dir(archive: &ArchiveSource) -> Result<PathBuf> {
+    Ok(crate::platform::cache_dir()
+        .wrap_err("Cache directory does'nt exit.")?
+        .join("nix-nit")
+        .join("archives")
+        .join(archive.sha256.to_ascii_lowercase()))
+}
+
+/// The `[[template]]` entry the unpacked `archive` is collected as
+pub fn template_config(archive: &ArchiveSource) -> Result<TemplateConfig> {
+    Ok(TemplateConfig {
+        name: archive.name.clone(),
+        uri: format!("path:{}", dir(archive)?.display()),
+        source_type: SourceType::Flake,
+        follow_registry: false,
+        deprecations: false,
+        tags: archive.tags.clone(),
+        templates: None,
+        execludes: None,
+        rename: vec![],
+        replace: vec![],
+        hooks: Default::default(),
+        gitignore: vec![],
+        license: None,
+        readme: None,
+        netrc: None,
+        ssh: None,
+        env: Default::default(),
+        pure_eval: true,
+        boost: 0.,
+        boosts: Default::default(),
+        demote: false,
+        demotes: vec![],
+        overrides: Default::default(),
+    })
+}
+
+/// Download, verify and unpack the archives that aren't unpacked yet, `concurrency` at a time
+pub async fn fetch_all(archives: &[ArchiveSource], concurrency: usize) -> Result<()> {
+    futures::stream::iter(archives.iter().cloned())
+        .map(|archive| async move { tokio::task::spawn_blocking(move || fetch(&archive)).await? })
+        .buffer_unordered(concurrency.max(1))
+        .try_collect()
+        .await
+}
+
+fn fetch(archive: &ArchiveSource) -> Result<()> {
+    let dir = dir(archive)?;
+    if dir.exists() {
+        return Ok(());
+    }
+
+    let partial = dir.with_extension("partial");
+    if partial.exists() {
+        std::fs::remove_dir_all(&partial)?;
+    }
+    std::fs::create_dir_all(&partial)?;
+    cancel::writing(&partial);
+
+    let res = download(archive, &partial).and_then(|root| Ok(std::fs::rename(root, &dir)?));
+    let _ = std::fs::remove_dir_all(&partial);
+    cancel::done_writing(&partial);
+
+    res
+}
+
+/// Into `work`, returning the directory of the flake in it
+fn download(archive: &ArchiveSource, work: &Path) -> Result<PathBuf> {
+    tracing::info!("downloading {}", archive.url);
+    let tarball = work.join("archive");
+    curl(&[&archive.url, "-o", &tarball.to_string_lossy()])?;
+
+    let actual = sha256(&tarball)?;
+    ensure!(
+        actual.eq_ignore_ascii_case(&archive.sha256),
+        tr(
+            "checksum-mismatch",
+            &[
+                ("url", &archive.url),
+                ("expected", &archive.sha256),
+                ("actual", &actual)
+            ]
+        )
+    );
+
+    unpack(&tarball, &work.join("root"))
+}
+
+/// Extract `tarball` into `into`. Tarballs usually have everything in one top-level directory,
+/// which is the flake then
+fn unpack(tarball: &Path, into: &Path) -> Result<PathBuf> {
+    std::fs::create_dir_all(into)?;
+    let output = crate::platform::command("tar")
+        .arg("-xf")
+        .arg(tarball)
+        .arg("-C")
+        .arg(into)
+        .output()?;
+    ensure!(
+        output.status.success(),
+        "failed to unpack {}, err: {}",
+        tarball.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    if into.join("flake.nix").exists() {
+        return Ok(into.to_path_buf());
+    }
+    let entries: Vec<_> = std::fs::read_dir(into)?.collect::<Result<_, _>>()?;
+    match &entries[..] {
+        [entry] if entry.path().join("flake.nix").exists() => Ok(entry.path()),
+        _ => bail!("{} has no flake.nix", tarball.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+
+    #[test]
+    fn unpack_finds_the_flake() {
+        let dir = std::env::temp_dir().join(format!("nit-archive-test-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("src/templates-1.0")).unwrap();
+        std::fs::write(dir.join("src/templates-1.0/flake.nix"), "{}").unwrap();
+        let tarball = dir.join("templates.tar.gz");
+        let status = Command::new("tar")
+            .arg("-czf")
+            .arg(&tarball)
+            .arg("-C")
+            .arg(dir.join("src"))
+            .arg("templates-1.0")
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let root = unpack(&tarball, &dir.join("out")).unwrap();
+        assert_eq!(root, dir.join("out/templates-1.0"));
+
+        std::fs::write(dir.join("empty"), "").unwrap();
+        let empty = dir.join("empty.tar");
+        Command::new("tar")
+            .arg("-cf")
+            .arg(&empty)
+            .arg("-C")
+            .arg(&dir)
+            .arg("empty")
+            .status()
+            .unwrap();
+        assert!(unpack(&empty, &dir.join("out-empty")).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,177 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use ltrait::color_eyre::Result;
+
+use crate::Template;
+use crate::cache::{self, Cache, CacheStore as _};
+use crate::config::Config;
+use crate::matcher::{Matcher, MatcherConfig};
+
+/// Templates per synthetic flake, about what a large template repository has
+const PER_FLAKE: usize = 100;
+
+/// `nit bench`: the median of `iterations` runs of the paths every launch goes through, over
+/// synthetic catalogs of each of `sizes` templates, then the startup with the real cache
+pub async fn run(sizes: &[usize], iterations: u32, profile: Option<&str>) -> Result<()> {
+    let work = std::env::temp_dir().join(format!("nit-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&work)?;
+    crate::cancel::writing(&work);
+
+    println!(
+        "{:<24} {:>9} {:>12} {:>12}",
+        "", "templates", "median", "per template"
+    );
+    let res = bench_sizes(sizes, iterations, &work).await;
+    let _ = std::fs::remove_dir_all(&work);
+    crate::cancel::done_writing(&work);
+    res?;
+
+    startup(profile).await
+}
+
+async fn bench_sizes(sizes: &[usize], iterations: u32, work: &std::path::Path) -> Result<()> {
+    let matcher = Matcher::new(MatcherConfig::default());
+
+    for &n in sizes {
+        let shows = flake_shows(n);
+        report(
+            "flatten flake show",
+            n,
+            time(iterations, || {
+                shows
+                    .iter()
+                    .map(|(uri, json)| cache::parse_flake_show(json, uri).map(|t| t.len()))
+                    .sum::<Result<usize>>()
+            }),
+        );
+
+        let templates = shows
+            .iter()
+            .map(|(uri, json)| cache::parse_flake_show(json, uri))
+            .collect::<Result<Vec<_>>>()?
+            .concat();
+
+        let store = Cache::at(work.join(format!("cache-{n}.jsonl")));
+        store.write(&templates, 0).await?;
+        let mut times = Vec::with_capacity(iterations as usize);
+        for _ in 0..iterations.max(1) {
+            let start = Instant::now();
+            black_box(store.read().await?.1.count());
+            times.push(start.elapsed());
+        }
+        report("deserialize cache", n, median(times));
+
+        // the match strings are built once per template, so on a fresh copy every time
+        let mut times = Vec::with_capacity(iterations as usize);
+        for _ in 0..iterations.max(1) {
+            let fresh = templates.clone();
+            let start = Instant::now();
+            black_box(fresh.iter().map(|t| t.match_string().len()).sum::<usize>());
+            times.push(start.elapsed());
+        }
+        report("build match strings", n, median(times));
+
+        let haystacks: Vec<_> = templates.iter().map(Template::match_string).collect();
+        report(
+            "score `rust`",
+            n,
+            time(iterations, || {
+                haystacks
+                    .iter()
+                    .map(|h| matcher.score(h, "rust"))
+                    .filter(|score| *score > 0)
+                    .count()
+            }),
+        );
+    }
+
+    Ok(())
+}
+
+/// Loading the config and the cache of `profile` as a launch does, before anything is drawn.
+/// Skipped before there is a cache, which would take fetching everything
+async fn startup(profile: Option<&str>) -> Result<()> {
+    let config = Config::load_optional()?;
+    if !cache::store(profile, config.as_ref())?.exists() {
+        println!("startup: skipped, there is no cache yet");
+        return Ok(());
+    }
+
+    let start = Instant::now();
+    let loaded =
+        cache::load_cache(false, false, profile, cache::Defaults::Show, None, false).await?;
+    let n = loaded.templates.count();
+    report("startup", n, start.elapsed());
+    // a stale cache started a refresh nobody waits for
+    crate::cancel::background();
+
+    Ok(())
+}
+
+/// `nix flake show --json` of `n` templates, spread over flakes of [`PER_FLAKE`]
+fn flake_shows(n: usize) -> Vec<(String, Vec<u8>)> {
+    (0..n.div_ceil(PER_FLAKE))
+        .map(|flake| {
+            let templates: serde_json::Map<_, _> = (flake * PER_FLAKE
+                ..n.min((flake + 1) * PER_FLAKE))
+                .map(|i| {
+                    (
+                        format!("template-{i}"),
+                        serde_json::json!({
+                            "description": format!("synthetic template {i} for rust, go and nix"),
+                        }),
+                    )
+                })
+                .collect();
+
+            (
+                format!("github:bench/flake-{flake}"),
+                serde_json::to_vec(&serde_json::json!({ "templates": templates })).unwrap(),
+            )
+        })
+        .collect()
+}
+
+/// The median of `iterations` runs of `f`
+fn time<T>(iterations: u32, mut f: impl FnMut() -> T) -> Duration {
+    median(
+        (0..iterations.max(1))
+            .map(|_| {
+                let start = Instant::now();
+                black_box(f());
+                start.elapsed()
+            })
+            .collect(),
+    )
+}
+
+fn median(mut times: Vec<Duration>) -> Duration {
+    times.sort();
+    times[times.len() / 2]
+}
+
+fn report(name: &str, n: usize, median: Duration) {
+    println!(
+        "{name:<24} {n:>9} {:>12} {:>12}",
+        format!("{median:.2?}"),
+        format!("{:.2?}", median / n.max(1) as u32),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn synthetic_catalogs() {
+        let shows = flake_shows(250);
+        assert_eq!(shows.len(), 3);
+
+        let templates: usize = shows
+            .iter()
+            .map(|(uri, json)| cache::parse_flake_show(json, uri).unwrap().len())
+            .sum();
+        assert_eq!(templates, 250);
+    }
+}
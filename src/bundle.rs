@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+use std::io::IsTerminal as _;
+use std::path::{Path, PathBuf};
+
+use ltrait::color_eyre::{Result, eyre::ContextCompat as _};
+
+use crate::config::{BundleConfig, Config, SourceType, fnv1a};
+use crate::init::{self, InitOptions};
+use crate::locale::tr;
+use crate::nix::NixBackend;
+use crate::notify;
+use crate::preview;
+use crate::{FlakeInfo, Template, prompt};
+
+/// The entry of `bundle` in the TUI, as `bundle#<name>`
+pub fn template(bundle: &BundleConfig) -> Template {
+    Template {
+        name: bundle.name.clone(),
+        flake_info: FlakeInfo {
+            uri: "bundle".into(),
+            source_type: SourceType::Bundle,
+            ..Default::default()
+        },
+        description: if bundle.description.is_empty() {
+            bundle.templates.join(" + ")
+        } else {
+            bundle.description.clone()
+        },
+        tags: SourceType::Bundle
+            .tags()
+            .iter()
+            .map(|t| t.to_string())
+            .collect(),
+        boost: 0.,
+        demoted: false,
+        icon: None,
+        display_name: None,
+        deprecated: false,
+        replaced_by: None,
+        nar_hash: None,
+        keys: Default::default(),
+    }
+}
+
+/// Initialize every template of the bundle `t` into the same directory in order, then report the
+/// existing files the templates replaced and the files a later template replaced
+pub fn init(nix: &impl NixBackend, t: &Template, opts: &InitOptions) -> Result<()> {
+    let mut config = Config::load()?;
+    if let Some(profile) = &opts.profile {
+        config.use_profile(profile)?;
+    }
+    let bundle = config
+        .bundle
+        .iter()
+        .find(|b| b.name == t.name)
+        .wrap_err_with(|| tr("bundle-unknown", &[("name", &t.name)]))?;
+    let parts: Vec<_> = bundle
+        .templates
+        .iter()
+        .map(|uri| part(&config, uri))
+        .collect();
+
+    let into = opts.into.as_deref().or(config.into.as_deref());
+    if config.confirm && !opts.yes && !confirm(bundle, into)? {
+        eprintln!("{}", tr("aborted", &[]));
+        return Ok(());
+    }
+
+    let created_dirs = match into {
+        Some(dir) => init::enter(dir)?,
+        None => {
+            let Some(dirs) = init::confirm_existing_flake(opts)? else {
+                eprintln!("{}", tr("aborted", &[]));
+                return Ok(());
+            };
+            dirs
+        }
+    };
+    let target = std::env::current_dir()?;
+
+    // one preview for every template, as each of them skips its own
+    let mut sources = vec![];
+    for part in parts.iter().filter(|p| p.flake_info.source_type.is_flake()) {
+        config.policy.check(&part.flake_info.uri)?;
+        let (nix_args, envs) = config
+            .flake(&part.flake_info.uri)
+            .map(|c| (c.nix_args(), c.envs()))
+            .unwrap_or_else(|| (crate::nix::args(), vec![]));
+        sources.push(crate::nix::template_path(nix, part, &nix_args, &envs)?);
+    }
+    let Some(confirmed) = preview::confirm(&sources, &target, opts.can_ask(), opts.yes)? else {
+        eprintln!("{}", tr("aborted", &[]));
+        return Ok(());
+    };
+    // the content of the existing files the templates may replace, to report the ones they did
+    let existing: BTreeMap<PathBuf, u64> = confirmed
+        .iter()
+        .map(|f| (f.clone(), fnv1a(&std::fs::read(f).unwrap_or_default())))
+        .collect();
+
+    let long = notify::Long::start(
+        config.notifications,
+        tr(
+            "notify-init-done",
+            &[("uri", &t.uri()), ("target", &target.display())],
+        ),
+        tr("notify-init-failed", &[("uri", &t.uri())]),
+    );
+    init::run_hooks(&bundle.hooks.pre_init, t, &target, opts)?;
+
+    // which template wrote each file last, and the hash of what it wrote
+    let mut written: BTreeMap<PathBuf, (String, u64)> = BTreeMap::new();
+    let mut conflicts = vec![];
+    let mut replaced = vec![];
+    for part in &parts {
+        let part_options = InitOptions {
+            into: None,
+            bundled: true,
+            // a later template wins over an earlier one, and that is reported below
+            overwrite: confirmed.iter().chain(written.keys()).cloned().collect(),
+            created_dirs: created_dirs.clone(),
+            ..opts.clone()
+        };
+        let (before, _) = init::snapshot(&target)?;
+        init::init_template(nix, part, &part_options)?;
+        let (after, _) = init::snapshot(&target)?;
+
+        for file in after {
+            let tracked = written.get(&file);
+            let original = existing.get(&file);
+            if tracked.is_none() && original.is_none() && before.contains(&file) {
+                continue;
+            }
+
+            let hash = fnv1a(&std::fs::read(&file).unwrap_or_default());
+            match (tracked, original) {
+                (Some((_, previous)), _) if *previous == hash => continue,
+                (Some((owner, _)), _) => conflicts.push((file.clone(), owner.clone(), part.uri())),
+                (None, Some(original)) if *original == hash => continue,
+                (None, Some(_)) => replaced.push((file.clone(), part.uri())),
+                (None, None) => {}
+            }
+            written.insert(file, (part.uri().to_string(), hash));
+        }
+    }
+
+    init::run_hooks(&bundle.hooks.post_init, t, &target, opts)?;
+
+    if !replaced.is_empty() {
+        eprintln!("{}", tr("bundle-replaced", &[("count", &replaced.len())]));
+        for (file, by) in replaced {
+            let file = file.strip_prefix(&target).unwrap_or(&file);
+            eprintln!("  - {}: {by}", file.display());
+        }
+    }
+    if !conflicts.is_empty() {
+        eprintln!("{}", tr("bundle-conflicts", &[("count", &conflicts.len())]));
+        for (file, from, by) in conflicts {
+            let file = file.strip_prefix(&target).unwrap_or(&file);
+            eprintln!("  - {}: {from} -> {by}", file.display());
+        }
+    }
+    long.succeeded();
+
+    Ok(())
+}
+
+/// The template `uri#name` refers to, `default` without a name
+fn part(config: &Config, uri: &str) -> Template {
+    let (flake, name) = uri.rsplit_once('#').unwrap_or((uri, "default"));
+
+    Template {
+        name: name.to_string(),
+        flake_info: FlakeInfo {
+            uri: flake.to_string(),
+            source_type: config
+                .flake(flake)
+                .map(|c| c.source_type)
+                .unwrap_or_default(),
+            ..Default::default()
+        },
+        description: String::new(),
+        tags: vec![],
+        boost: 0.,
+        demoted: false,
+        icon: None,
+        display_name: None,
+        deprecated: false,
+        replaced_by: None,
+        nar_hash: None,
+        keys: Default::default(),
+    }
+}
+
+/// Like the confirmation of a single template, with every template of the bundle
+fn confirm(bundle: &BundleConfig, into: Option<&Path>) -> Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(true);
+    }
+
+    let target = match into {
+        Some(dir) => std::path::absolute(dir)?,
+        None => std::env::current_dir()?,
+    };
+    let answer = prompt::ask(&tr(
+        "prompt-confirm-bundle",
+        &[
+            ("name", &bundle.name),
+            ("templates", &bundle.templates.join(", ")),
+            ("target", &target.display()),
+        ],
+    ))?;
+
+    Ok(matches!(answer.to_lowercase().as_str(), "" | "y" | "yes"))
+}
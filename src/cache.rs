@@ -0,0 +1,1414 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::{StreamExt as _, TryStreamExt as _};
+use ltrait::color_eyre::{
+    Report, Result,
+    eyre::{ContextCompat, ensure},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::archive;
+use crate::bundle;
+use crate::cancel;
+use crate::catalog;
+use crate::config::{self, CacheStoreKind, Config, SourceType};
+use crate::download;
+use crate::error::NitError;
+use crate::events::{self, Event};
+use crate::filter::Filter;
+use crate::locale::tr;
+use crate::nix::{self, NixBackend};
+use crate::notify;
+use crate::policy::Policy;
+#[cfg(feature = "sqlite-cache")]
+use crate::sqlite_cache::SqliteCache;
+use crate::{FlakeInfo, FlakeMetadata, Template};
+
+/// Where the templates collected from the config are kept between launches. [`load_cache`] only
+/// goes through this, so another storage needs nothing but an implementation and [`store`]
+pub trait CacheStore: Send + Sync + 'static {
+    type Templates: Iterator<Item = Template> + Send + 'static;
+
+    /// Whether anything was written yet
+    fn exists(&self) -> bool;
+
+    /// Whether the templates were written longer than `ttl` ago
+    fn is_stale(&self, ttl: Duration) -> bool;
+
+    /// The header is `None` for caches written before it existed
+    fn read(&self) -> impl Future<Output = Result<(Option<Header>, Self::Templates)>> + Send;
+
+    /// Replace everything stored with `data`
+    fn write(&self, data: &[Template], config_hash: u64)
+    -> impl Future<Output = Result<()>> + Send;
+
+    /// The templates whose uri or name starts with `prefix`, for completion
+    fn with_prefix(&self, prefix: &str) -> impl Future<Output = Result<Vec<Template>>> + Send {
+        let prefix = prefix.to_string();
+        async move {
+            let (_, templates) = self.read().await?;
+            Ok(templates
+                .filter(|t| t.uri().starts_with(&prefix) || t.name.starts_with(&prefix))
+                .collect())
+        }
+    }
+}
+
+/// The store `[cache] store` of the config picks, for `profile`
+pub fn store(profile: Option<&str>, config: Option<&Config>) -> Result<Store> {
+    match config.map(|c| c.cache.store).unwrap_or_default() {
+        CacheStoreKind::Json => Ok(Store::Json(Cache::new(profile)?)),
+        #[cfg(feature = "sqlite-cache")]
+        CacheStoreKind::Sqlite => Ok(Store::Sqlite(SqliteCache::new(profile)?)),
+        #[cfg(not(feature = "sqlite-cache"))]
+        CacheStoreKind::Sqlite => {
+            ltrait::color_eyre::eyre::bail!(
+                "`store = \"sqlite\"` needs nit built with the sqlite-cache feature"
+            )
+        }
+    }
+}
+
+/// The file of the cache of `profile`, as every profile has its own. So does every project with
+/// an [`overlay`](config::overlay), whose templates only belong to it
+pub fn file(profile: Option<&str>, extension: &str) -> Result<PathBuf> {
+    let mut file = match profile {
+        Some(profile) => format!("cache-{profile}"),
+        None => "cache".to_string(),
+    };
+    if let Some(overlay) = config::overlay() {
+        file.push_str(&format!(
+            "-{:016x}",
+            config::fnv1a(overlay.as_os_str().as_encoded_bytes())
+        ));
+    }
+    file.push('.');
+    file.push_str(extension);
+
+    Ok(crate::platform::cache_dir()
+        .wrap_err("Cache directory does'nt exit.")?
+        .join("nix-nit")
+        .join(file))
+}
+
+/// One of the stores, as picked by [`store`]
+pub enum Store {
+    Json(Cache),
+    #[cfg(feature = "sqlite-cache")]
+    Sqlite(SqliteCache),
+}
+
+impl Store {
+    pub fn path(&self) -> &Path {
+        match self {
+            Store::Json(cache) => cache.path(),
+            #[cfg(feature = "sqlite-cache")]
+            Store::Sqlite(cache) => cache.path(),
+        }
+    }
+}
+
+impl CacheStore for Store {
+    type Templates = Box<dyn Iterator<Item = Template> + Send>;
+
+    fn exists(&self) -> bool {
+        match self {
+            Store::Json(cache) => cache.exists(),
+            #[cfg(feature = "sqlite-cache")]
+            Store::Sqlite(cache) => cache.exists(),
+        }
+    }
+
+    fn is_stale(&self, ttl: Duration) -> bool {
+        match self {
+            Store::Json(cache) => cache.is_stale(ttl),
+            #[cfg(feature = "sqlite-cache")]
+            Store::Sqlite(cache) => cache.is_stale(ttl),
+        }
+    }
+
+    async fn read(&self) -> Result<(Option<Header>, Self::Templates)> {
+        Ok(match self {
+            Store::Json(cache) => {
+                let (header, templates) = cache.read().await?;
+                (header, Box::new(templates))
+            }
+            #[cfg(feature = "sqlite-cache")]
+            Store::Sqlite(cache) => {
+                let (header, templates) = cache.read().await?;
+                (header, Box::new(templates))
+            }
+        })
+    }
+
+    async fn write(&self, data: &[Template], config_hash: u64) -> Result<()> {
+        match self {
+            Store::Json(cache) => cache.write(data, config_hash).await,
+            #[cfg(feature = "sqlite-cache")]
+            Store::Sqlite(cache) => cache.write(data, config_hash).await,
+        }
+    }
+
+    async fn with_prefix(&self, prefix: &str) -> Result<Vec<Template>> {
+        match self {
+            Store::Json(cache) => cache.with_prefix(prefix).await,
+            #[cfg(feature = "sqlite-cache")]
+            Store::Sqlite(cache) => cache.with_prefix(prefix).await,
+        }
+    }
+}
+
+/// The default [`CacheStore`]: the templates as one JSON object per line so that they can be
+/// parsed lazily while the UI is already running. The first line is a [`Header`]
+pub struct Cache {
+    path: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Header {
+    /// [`Config::hash`] of the config the cache was built from
+    pub config_hash: u64,
+}
+
+/// Parses one line of the cache every time the next template is requested
+pub struct LazyTemplates {
+    content: String,
+    pos: usize,
+}
+
+impl Iterator for LazyTemplates {
+    type Item = Template;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pos < self.content.len() {
+            let rest = &self.content[self.pos..];
+            let line = rest.split_once('\n').map_or(rest, |(line, _)| line);
+            self.pos += line.len() + 1;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str(line) {
+                Ok(template) => return Some(template),
+                Err(e) => tracing::warn!("skipping a broken line of the cache: {e}"),
+            }
+        }
+
+        None
+    }
+}
+
+impl Cache {
+    pub fn new(profile: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            path: file(profile, "jsonl")?,
+        })
+    }
+
+    /// A cache at `path` instead of the one of a profile, e.g. for `nit bench`
+    pub fn at(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl CacheStore for Cache {
+    type Templates = LazyTemplates;
+
+    fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    fn is_stale(&self, ttl: Duration) -> bool {
+        std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > ttl)
+    }
+
+    async fn read(&self) -> Result<(Option<Header>, LazyTemplates)> {
+        let content = tokio::fs::read_to_string(&self.path)
+            .await
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::InvalidData => NitError::CacheCorrupt {
+                    path: self.path.clone(),
+                    message: e.to_string(),
+                }
+                .into(),
+                _ => Report::from(e),
+            })?;
+        let first_line = content.lines().next().unwrap_or_default();
+
+        Ok(match serde_json::from_str::<Header>(first_line) {
+            Ok(header) => {
+                let pos = first_line.len() + 1;
+                (Some(header), LazyTemplates { content, pos })
+            }
+            Err(_) => (None, LazyTemplates { content, pos: 0 }),
+        })
+    }
+
+    async fn write(&self, data: &[Template], config_hash: u64) -> Result<()> {
+        if let Some(parent) = self.path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let mut content = serde_json::to_string(&Header { config_hash })?;
+        content.push('\n');
+        for template in data {
+            content.push_str(&serde_json::to_string(template)?);
+            content.push('\n');
+        }
+
+        // a refresh killed halfway must not leave a truncated cache behind
+        let tmp = self.path.with_extension("jsonl.tmp");
+        cancel::writing(&tmp);
+        tokio::fs::write(&tmp, content).await?;
+        tokio::fs::rename(&tmp, &self.path).await?;
+        cancel::done_writing(&tmp);
+
+        Ok(())
+    }
+}
+
+pub struct Loaded {
+    pub templates: Box<dyn Iterator<Item = Template> + Send>,
+    /// Filled with the templates that weren't in the stale cache once the background refresh
+    /// finishes
+    pub refreshed: Option<Arc<Mutex<Vec<Template>>>>,
+}
+
+/// What to do with the `default` template of each flake
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Defaults {
+    #[default]
+    Show,
+    /// Like `hide_defaults` in the config
+    Hide,
+    /// One entry per flake. Flakes without a `default` template are left out
+    Only,
+}
+
+impl Defaults {
+    fn keep(self, t: &Template) -> bool {
+        match self {
+            Defaults::Show => true,
+            Defaults::Hide => !t.is_default(),
+            Defaults::Only => t.name == "default",
+        }
+    }
+}
+
+/// `impure` makes nix evaluate every flake with `--impure`. With `profile`, only the templates of
+/// `[profile.<name>]` are loaded. With `filter`, only the ones it matches. With `watch`, the cache
+/// is re-collected whenever the config is saved, see [`watch`]
+pub async fn load_cache(
+    re_cache: bool,
+    impure: bool,
+    profile: Option<&str>,
+    defaults: Defaults,
+    filter: Option<Arc<Filter>>,
+    watch: bool,
+) -> Result<Loaded> {
+    let mut config = Config::load_optional()?;
+    if let Some(c) = &config
+        && !c.catalog.is_empty()
+    {
+        catalog::fetch_all(c, re_cache).await?;
+        // with the templates of the catalogs fetched just now
+        config = Config::load_optional()?;
+    }
+    if let Some(config) = &mut config {
+        if let Some(profile) = profile {
+            config.use_profile(profile)?;
+        }
+        if impure {
+            config.force_impure();
+        }
+    }
+    let cache = store(profile, config.as_ref())?;
+    let defaults = match defaults {
+        Defaults::Show if config.as_ref().is_some_and(|c| c.hide_defaults) => Defaults::Hide,
+        defaults => defaults,
+    };
+    let keep = move |t: &Template| defaults.keep(t) && filter.as_ref().is_none_or(|f| f.matches(t));
+
+    let cached = if re_cache || !cache.exists() {
+        None
+    } else {
+        match (cache.read().await?, &config) {
+            // the config was edited after the cache was built
+            ((header, _), Some(config))
+                if header.as_ref().is_none_or(|h| h.config_hash != config.hash) =>
+            {
+                tracing::info!("the config changed, rebuilding the cache");
+                None
+            }
+            ((_, templates), _) => Some(templates),
+        }
+    };
+
+    let refreshed = watch.then(|| {
+        let refreshed = Arc::new(Mutex::new(vec![]));
+        cancel::track(
+            tokio::spawn(self::watch(
+                profile.map(str::to_string),
+                impure,
+                refreshed.clone(),
+                keep.clone(),
+            ))
+            .abort_handle(),
+        );
+        refreshed
+    });
+
+    let Some(templates) = cached else {
+        let config = match config {
+            Some(config) => config,
+            // reports that there is no config
+            None => Config::load()?,
+        };
+        // for the sources that are rate limited now
+        let previous: Vec<Template> = if cache.exists() {
+            cache
+                .read()
+                .await
+                .map(|(_, t)| t.collect())
+                .unwrap_or_default()
+        } else {
+            vec![]
+        };
+        let long = notify::Long::start(
+            config.notifications,
+            tr("notify-cache-done", &[]),
+            tr("notify-cache-failed", &[]),
+        );
+        let res = collect(&nix::Subprocess, &config, &previous).await?;
+        long.succeeded();
+        res.report(true);
+        cache.write(&res.templates, config.hash).await?;
+        events::emit(Event::CacheWritten {
+            templates: res.templates.len(),
+        });
+
+        return Ok(Loaded {
+            templates: Box::new(res.templates.into_iter().filter(keep)),
+            refreshed,
+        });
+    };
+
+    let refreshed = match config {
+        Some(config) if config.cache.ttl.is_some_and(|ttl| cache.is_stale(ttl)) => {
+            let refreshed = refreshed.unwrap_or_default();
+            cancel::track(
+                tokio::spawn(refresh(cache, config, refreshed.clone(), keep.clone()))
+                    .abort_handle(),
+            );
+            Some(refreshed)
+        }
+        _ => refreshed,
+    };
+
+    Ok(Loaded {
+        templates: Box::new(templates.filter(keep)),
+        refreshed,
+    })
+}
+
+/// `--watch-config`: re-collect the cache every time the config is saved, fetching only the
+/// sources whose entry changed. Templates that weren't there before reach `refreshed`, while
+/// removed ones stay listed until the next launch
+async fn watch(
+    profile: Option<String>,
+    impure: bool,
+    refreshed: Arc<Mutex<Vec<Template>>>,
+    keep: impl Fn(&Template) -> bool,
+) {
+    let load = || -> Result<Option<Config>> {
+        let mut config = Config::load_optional()?;
+        if let Some(config) = &mut config {
+            if let Some(profile) = &profile {
+                config.use_profile(profile)?;
+            }
+            if impure {
+                config.force_impure();
+            }
+        }
+        Ok(config)
+    };
+
+    let mut content = Config::content_hash();
+    let Ok(Some(mut old)) = load() else {
+        return;
+    };
+    let mut known = HashSet::new();
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let current = Config::content_hash();
+        if current == content {
+            continue;
+        }
+        content = current;
+
+        let new = match load() {
+            Ok(Some(new)) if new.hash != old.hash => new,
+            Ok(_) => continue,
+            Err(e) => {
+                tracing::warn!("not re-collecting the cache for the broken config: {e}");
+                continue;
+            }
+        };
+
+        let run = async {
+            let cache = store(profile.as_deref(), Some(&new))?;
+            let previous: Vec<Template> = if cache.exists() && new.cache.store == old.cache.store {
+                cache.read().await?.1.collect()
+            } else {
+                vec![]
+            };
+            if known.is_empty() {
+                known.extend(previous.iter().map(|t| t.uri().to_string()));
+            }
+
+            let res = collect_changed(&nix::Subprocess, &old, &new, &previous).await?;
+            res.report(false);
+            cache.write(&res.templates, new.hash).await?;
+            events::emit(Event::CacheWritten {
+                templates: res.templates.len(),
+            });
+
+            refreshed.lock().unwrap().extend(
+                res.templates
+                    .into_iter()
+                    .filter(|t| keep(t) && known.insert(t.uri().to_string())),
+            );
+
+            Result::<()>::Ok(())
+        };
+
+        match run.await {
+            Ok(()) => {
+                tracing::info!("re-collected the cache for the saved config");
+                old = new;
+            }
+            Err(e) => tracing::warn!("failed to re-collect the cache for the saved config: {e}"),
+        }
+    }
+}
+
+async fn refresh(
+    cache: impl CacheStore,
+    config: Config,
+    refreshed: Arc<Mutex<Vec<Template>>>,
+    keep: impl Fn(&Template) -> bool,
+) {
+    let started = Instant::now();
+    let run = async {
+        let previous: Vec<Template> = cache.read().await?.1.collect();
+        let known: HashSet<String> = previous.iter().map(|t| t.uri().to_string()).collect();
+
+        let res = collect(&nix::Subprocess, &config, &previous).await?;
+        res.report(false);
+        cache.write(&res.templates, config.hash).await?;
+        events::emit(Event::CacheWritten {
+            templates: res.templates.len(),
+        });
+
+        refreshed.lock().unwrap().extend(
+            res.templates
+                .into_iter()
+                .filter(|t| !known.contains(t.uri()) && keep(t)),
+        );
+
+        Result::<()>::Ok(())
+    };
+
+    match run.await {
+        Ok(()) => {
+            tracing::info!("refreshed the stale cache");
+            notify::finished(started, config.notifications, &tr("notify-cache-done", &[]));
+        }
+        Err(e) => {
+            tracing::warn!("failed to refresh the stale cache: {e}");
+            notify::finished(
+                started,
+                config.notifications,
+                &tr("notify-cache-failed", &[]),
+            );
+        }
+    }
+}
+
+/// Fetch every template the config refers to, `performance.fetch_concurrency` sources at a time.
+/// Sources that stay rate limited keep their templates in `previous`, the old cache if any
+async fn collect(
+    nix: &impl NixBackend,
+    config: &Config,
+    previous: &[Template],
+) -> Result<Collected> {
+    let archives = fetch_archives(config).await?;
+    let sources: Vec<_> = config.template.iter().chain(&archives).collect();
+
+    // lazy, so `buffered` still starts them a few at a time
+    let flakes: Vec<_> = sources
+        .iter()
+        .map(|flake| collect_flake_patiently(nix, flake, config, previous))
+        .collect();
+    let res: Vec<_> = futures::stream::iter(flakes)
+        .buffered(config.performance.fetch_concurrency.max(1))
+        .try_collect()
+        .await?;
+
+    Ok(Collected::new(config, &sources, res))
+}
+
+/// Like [`collect`], but the sources whose entry is the same in `old` keep their templates in
+/// `previous` instead of being fetched again
+async fn collect_changed(
+    nix: &impl NixBackend,
+    old: &Config,
+    new: &Config,
+    previous: &[Template],
+) -> Result<Collected> {
+    let archives = fetch_archives(new).await?;
+    let sources: Vec<_> = new.template.iter().chain(&archives).collect();
+    let old_archives = old
+        .sources
+        .archive
+        .iter()
+        .map(archive::template_config)
+        .collect::<Result<Vec<_>>>()?;
+    let old_archives = &old_archives;
+
+    let flakes: Vec<_> = sources
+        .iter()
+        .map(|flake| async move {
+            if old.template.contains(flake) || old_archives.contains(flake) {
+                Ok((previous_templates(flake, previous), false))
+            } else {
+                collect_flake_patiently(nix, flake, new, previous).await
+            }
+        })
+        .collect();
+    let res: Vec<_> = futures::stream::iter(flakes)
+        .buffered(new.performance.fetch_concurrency.max(1))
+        .try_collect()
+        .await?;
+
+    Ok(Collected::new(new, &sources, res))
+}
+
+/// Check the sources of `config` against the policy, and download the `[[sources.archive]]`s
+/// missing from the cache directory. The entries they are collected as
+async fn fetch_archives(config: &Config) -> Result<Vec<config::TemplateConfig>> {
+    for flake in &config.template {
+        config.policy.check(&flake.uri)?;
+    }
+    for archive in &config.sources.archive {
+        config.policy.check(&archive.url)?;
+    }
+
+    archive::fetch_all(
+        &config.sources.archive,
+        config.performance.fetch_concurrency,
+    )
+    .await?;
+
+    config
+        .sources
+        .archive
+        .iter()
+        .map(archive::template_config)
+        .collect()
+}
+
+/// The templates of every source, and the uris of the ones that were rate limited
+struct Collected {
+    templates: Vec<Template>,
+    rate_limited: Vec<String>,
+}
+
+impl Collected {
+    /// From the templates of each of `sources`, in order, with whether it was rate limited
+    fn new(
+        config: &Config,
+        sources: &[&config::TemplateConfig],
+        res: Vec<(Vec<Template>, bool)>,
+    ) -> Self {
+        let rate_limited = sources
+            .iter()
+            .zip(&res)
+            .filter(|(_, (_, limited))| *limited)
+            .map(|(flake, _)| flake.uri.clone())
+            .collect();
+
+        Self {
+            templates: res
+                .into_iter()
+                .flat_map(|(templates, _)| templates)
+                .chain(config.bundle.iter().map(bundle::template))
+                .collect(),
+            rate_limited,
+        }
+    }
+
+    /// Tell which sources were left as they were in the old cache, on stderr before the TUI
+    /// starts or in the log afterwards
+    fn report(&self, foreground: bool) {
+        if self.rate_limited.is_empty() {
+            return;
+        }
+
+        let message = tr(
+            "rate-limited",
+            &[
+                ("count", &self.rate_limited.len()),
+                ("sources", &self.rate_limited.join(", ")),
+            ],
+        );
+        if foreground {
+            eprintln!("{message}");
+        } else {
+            tracing::warn!("{message}");
+        }
+    }
+}
+
+/// The templates of the config entry `flake` in `previous`
+fn previous_templates(flake: &config::TemplateConfig, previous: &[Template]) -> Vec<Template> {
+    previous
+        .iter()
+        .filter(|t| t.flake_info.config_uri() == flake.uri)
+        .cloned()
+        .collect()
+}
+
+/// [`collect_flake`], retried while the source is rate limited and waiting twice as long each
+/// time. Once `performance.rate_limit_retries` are used up, its templates in `previous` and
+/// `true`
+async fn collect_flake_patiently(
+    nix: &impl NixBackend,
+    flake: &config::TemplateConfig,
+    config: &Config,
+    previous: &[Template],
+) -> Result<(Vec<Template>, bool)> {
+    let mut delay = Duration::from_secs(1);
+    let mut retries = 0;
+    events::emit(Event::FetchStarted { uri: &flake.uri });
+
+    let res = loop {
+        match collect_flake(nix, flake, &config.policy).await {
+            Err(e) if nix::rate_limited(&e) => {
+                if retries >= config.performance.rate_limit_retries {
+                    tracing::warn!("giving up on the rate limited {}: {e}", flake.uri);
+                    break Err(e);
+                }
+                tracing::info!("{} is rate limited, retrying in {delay:?}", flake.uri);
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                retries += 1;
+            }
+            res => break res,
+        }
+    };
+    events::emit(Event::FetchFinished {
+        uri: &flake.uri,
+        templates: res.as_ref().map_or(0, Vec::len),
+        error: res.as_ref().err().map(|e| e.to_string()),
+    });
+
+    match res {
+        Err(e) if nix::rate_limited(&e) => Ok((previous_templates(flake, previous), true)),
+        res => Ok((res?, false)),
+    }
+}
+
+/// The templates of one source, with the settings of its config entry applied
+async fn collect_flake(
+    nix: &impl NixBackend,
+    flake: &config::TemplateConfig,
+    policy: &Policy,
+) -> Result<Vec<Template>> {
+    ensure!(
+        flake.source_type != SourceType::Bundle,
+        "{} has type = \"bundle\", write it as a [[bundle]] instead",
+        flake.uri
+    );
+
+    let metadata = if flake.source_type.is_flake() {
+        let metadata = nix
+            .flake_metadata(&flake.uri, &flake.nix_args(), &flake.envs())
+            .await
+            .and_then(|json| Ok(FlakeMetadata::from_json(&json)?));
+        match metadata {
+            Ok(metadata) => Some(metadata),
+            Err(e) if flake.follow_registry => {
+                return Err(e.wrap_err(format!("failed to resolve {}", flake.uri)));
+            }
+            Err(e) => {
+                tracing::warn!("failed to get the metadata of {}: {e}", flake.uri);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    // the registry alias stays in `alias`, to find the config entry again
+    let (uri, alias) = match metadata.as_ref().and_then(|m| m.resolved_url.clone()) {
+        Some(resolved) if flake.follow_registry => (resolved, Some(flake.uri.clone())),
+        _ => (flake.uri.clone(), None),
+    };
+    // the alias was checked, but the registry may point it anywhere
+    if alias.is_some() {
+        policy.check(&uri)?;
+    }
+
+    let (mut data, mut evaluated) = if flake.source_type.is_flake() {
+        let show = nix
+            .flake_show(&uri, &flake.nix_args(), &flake.envs())
+            .await?;
+        (
+            parse_flake_show(&show, &uri)?,
+            evaluate(nix, flake, &uri).await,
+        )
+    } else {
+        (repository_templates(flake), HashMap::new())
+    };
+    for i in data.iter_mut() {
+        i.flake_info.source_type = flake.source_type;
+        i.flake_info.metadata = metadata.clone();
+        i.flake_info.alias = alias.clone();
+        if let Some(e) = evaluated.remove(&i.name) {
+            i.deprecated = e.deprecated;
+            i.replaced_by = e.replaced_by;
+            i.nar_hash = e.nar_hash;
+        }
+    }
+    if let Some(fil) = &flake.templates {
+        data.retain(|value| fil.contains(&value.name));
+    }
+    if let Some(fil) = &flake.execludes {
+        data.retain(|value| !fil.contains(&value.name));
+    }
+    if let Some(name) = &flake.name {
+        for i in data.iter_mut() {
+            i.flake_info.name = Some(name.clone());
+        }
+    }
+    let tags: Vec<String> = flake
+        .source_type
+        .tags()
+        .iter()
+        .map(|t| t.to_string())
+        .chain(flake.tags.iter().cloned())
+        .collect();
+    for i in data.iter_mut() {
+        i.tags.extend(tags.iter().cloned());
+        i.boost = flake.boost(&i.name);
+        i.demoted = flake.demoted(&i.name);
+
+        if let Some(o) = flake.template_override(&i.name) {
+            if let Some(description) = &o.description {
+                i.description = description.clone();
+            }
+            if let Some(tags) = &o.tags {
+                i.tags = tags.clone();
+            }
+            i.icon = o.icon.clone();
+            i.display_name = o.display_name.clone();
+            if o.deprecated || o.replaced_by.is_some() {
+                i.deprecated = true;
+            }
+            if let Some(replaced_by) = &o.replaced_by {
+                i.replaced_by = Some(replaced_by.clone());
+            }
+        }
+        i.demoted |= i.deprecated;
+    }
+
+    Ok(data)
+}
+
+/// Applied to the `templates` of a flake, for the store paths of their files
+const PATHS: &str = "ts: builtins.mapAttrs (_: t: { path = toString t.path; }) ts";
+
+/// [`PATHS`] and what `nix flake show` leaves out of the templates
+const DEPRECATIONS: &str = "ts: builtins.mapAttrs (_: t: { path = toString t.path; \
+    deprecated = t.deprecated or (t ? replacedBy); replacedBy = t.replacedBy or null; }) ts";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Evaluated {
+    path: Option<PathBuf>,
+    /// The narHash of the files at `path`
+    #[serde(skip)]
+    nar_hash: Option<String>,
+    #[serde(default)]
+    deprecated: bool,
+    replaced_by: Option<String>,
+}
+
+/// The paths of every template of the flake at `uri` with their narHash, to tell on init
+/// whether a template changed since, and with `deprecations` whether they are deprecated. By
+/// name, and none for flakes without `templates`, e.g. with only the old `defaultTemplate`
+async fn evaluate(
+    nix: &impl NixBackend,
+    flake: &config::TemplateConfig,
+    uri: &str,
+) -> HashMap<String, Evaluated> {
+    let res = nix
+        .eval_json(
+            &format!("{uri}#templates"),
+            if flake.deprecations {
+                DEPRECATIONS
+            } else {
+                PATHS
+            },
+            &flake.nix_args(),
+            &flake.envs(),
+        )
+        .await
+        .and_then(|json| Ok(serde_json::from_slice::<HashMap<String, Evaluated>>(&json)?));
+    let mut evaluated = match res {
+        Ok(evaluated) => evaluated,
+        Err(e) => {
+            tracing::debug!("couldn't evaluate the templates of {uri}: {e}");
+            return HashMap::new();
+        }
+    };
+
+    // nix fetched the flake for `nix flake show` already, so the files are in the store
+    let hashed = tokio::task::spawn_blocking(move || {
+        for e in evaluated.values_mut() {
+            e.nar_hash = e.path.as_deref().and_then(|path| {
+                download::nar_hash(path)
+                    .inspect_err(|err| tracing::debug!("couldn't hash {}: {err}", path.display()))
+                    .ok()
+            });
+        }
+        evaluated
+    })
+    .await;
+    hashed.unwrap_or_default()
+}
+
+/// A cookiecutter or cargo-generate repository has no listing, so each directory in `templates`
+/// is an entry. Without `templates`, the repository root is the only one, named `default`
+fn repository_templates(flake: &config::TemplateConfig) -> Vec<Template> {
+    let names = flake
+        .templates
+        .clone()
+        .unwrap_or_else(|| vec!["default".into()]);
+
+    names
+        .into_iter()
+        .map(|name| Template {
+            name,
+            flake_info: FlakeInfo {
+                uri: flake.uri.clone(),
+                ..Default::default()
+            },
+            description: format!("{} template", flake.source_type.tags()[0]),
+            tags: vec![],
+            boost: 0.,
+            demoted: false,
+            icon: None,
+            display_name: None,
+            deprecated: false,
+            replaced_by: None,
+            nar_hash: None,
+            keys: Default::default(),
+        })
+        .collect()
+}
+
+/// Collect the templates from the output of `nix flake show --json`, which differs between nix
+/// versions and implementations:
+///
+/// - `defaultTemplate` is only there for flakes still using the deprecated output
+/// - a template without a description has none in the output, not an empty one
+pub fn parse_flake_show(json: &[u8], flake_uri: &str) -> Result<Vec<Template>> {
+    let show: serde_json::Value = serde_json::from_slice(json)?;
+    let show = show
+        .as_object()
+        .wrap_err("the output of nix flake show is not an object")?;
+
+    let mut found = Vec::<(String, String)>::new();
+    if let Some(templates) = show.get("templates").and_then(|t| t.as_object()) {
+        for (name, value) in templates {
+            if let Some(description) = template_description(value) {
+                found.push((name.clone(), description));
+            }
+        }
+    }
+    if let Some(description) = show.get("defaultTemplate").and_then(template_description)
+        && !found.iter().any(|(n, _)| n == "default")
+    {
+        found.insert(0, ("default".into(), description));
+    }
+
+    Ok(found
+        .into_iter()
+        .map(|(name, description)| Template {
+            name,
+            flake_info: FlakeInfo {
+                uri: flake_uri.to_string(),
+                ..Default::default()
+            },
+            description,
+            tags: vec![],
+            boost: 0.,
+            demoted: false,
+            icon: None,
+            display_name: None,
+            deprecated: false,
+            replaced_by: None,
+            nar_hash: None,
+            keys: Default::default(),
+        })
+        .collect())
+}
+
+/// `Some` if `value` is a template entry, with its description or an empty one
+fn template_description(value: &serde_json::Value) -> Option<String> {
+    let entry = value.as_object()?;
+    let description = entry.get("description").and_then(|d| d.as_str());
+
+    let is_template = match entry.get("type").and_then(|t| t.as_str()) {
+        Some(ty) => ty == "template",
+        // without a type, only a string description tells a template from another output
+        None => description.is_some(),
+    };
+
+    is_template.then(|| description.unwrap_or_default().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nix::mock::Mock;
+
+    fn names(json: &str) -> Vec<String> {
+        parse_flake_show(json.as_bytes(), "github:x/y")
+            .unwrap()
+            .into_iter()
+            .map(|t| t.name)
+            .collect()
+    }
+
+    #[test]
+    fn nix_2_18() {
+        assert_eq!(
+            names(include_str!("../tests/fixtures/flake-show/nix-2.18.json")),
+            ["default", "haskell-hello", "rust", "trivial"]
+        );
+    }
+
+    #[test]
+    fn nix_2_24() {
+        assert_eq!(
+            names(include_str!("../tests/fixtures/flake-show/nix-2.24.json")),
+            ["default", "rust", "trivial"]
+        );
+    }
+
+    #[test]
+    fn lix() {
+        assert_eq!(
+            names(include_str!("../tests/fixtures/flake-show/lix.json")),
+            ["default", "go-hello", "rust", "trivial"]
+        );
+    }
+
+    #[test]
+    fn missing_templates() {
+        assert!(names(r#"{"packages":{}}"#).is_empty());
+    }
+
+    #[test]
+    fn default_template_only() {
+        let templates = parse_flake_show(
+            br#"{"defaultTemplate":{"description":"A flake","type":"template"}}"#,
+            "github:x/y",
+        )
+        .unwrap();
+
+        assert_eq!(templates.len(), 1);
+        assert_eq!(templates[0].uri(), "github:x/y#default");
+        assert_eq!(templates[0].description, "A flake");
+    }
+
+    #[test]
+    fn not_an_object() {
+        assert!(parse_flake_show(b"[]", "github:x/y").is_err());
+    }
+
+    fn flake_config(toml: &str) -> config::TemplateConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[tokio::test]
+    async fn collect_flake_applies_the_config() {
+        let mut nix = Mock::default();
+        nix.show.insert(
+            "github:x/y".into(),
+            include_str!("../tests/fixtures/flake-show/nix-2.24.json").into(),
+        );
+        nix.metadata.insert(
+            "github:x/y".into(),
+            r#"{"lastModified":1700000000,"revision":"abc"}"#.into(),
+        );
+
+        let templates = collect_flake(
+            &nix,
+            &flake_config(
+                r#"
+                uri = "github:x/y"
+                name = "x"
+                execludes = ["trivial"]
+                tags = ["mine"]
+                "#,
+            ),
+            &Policy::default(),
+        )
+        .await
+        .unwrap();
+
+        let names: Vec<_> = templates.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["default", "rust"]);
+        for t in &templates {
+            assert_eq!(t.flake_info.name.as_deref(), Some("x"));
+            assert!(t.tags.contains(&"mine".to_string()));
+            assert_eq!(
+                t.flake_info.metadata.as_ref().unwrap().revision.as_deref(),
+                Some("abc")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn collect_flake_applies_overrides() {
+        let mut nix = Mock::default();
+        nix.show.insert(
+            "github:x/y".into(),
+            include_str!("../tests/fixtures/flake-show/nix-2.24.json").into(),
+        );
+
+        let templates = collect_flake(
+            &nix,
+            &flake_config(
+                r#"
+                uri = "github:x/y"
+                tags = ["mine"]
+                [override."github:x/y#rust"]
+                description = "Rust with a devshell"
+                tags = ["rust"]
+                display_name = "Rust"
+                [override.trivial]
+                icon = "*"
+                "#,
+            ),
+            &Policy::default(),
+        )
+        .await
+        .unwrap();
+
+        let rust = templates.iter().find(|t| t.name == "rust").unwrap();
+        assert_eq!(rust.description, "Rust with a devshell");
+        assert_eq!(rust.tags, ["rust"]);
+        assert_eq!(rust.display_name.as_deref(), Some("Rust"));
+        let trivial = templates.iter().find(|t| t.name == "trivial").unwrap();
+        assert_eq!(trivial.icon.as_deref(), Some("*"));
+        assert!(trivial.tags.contains(&"mine".to_string()));
+    }
+
+    #[tokio::test]
+    async fn collect_flake_reads_deprecations() {
+        let mut nix = Mock::default();
+        nix.show.insert(
+            "github:x/y".into(),
+            include_str!("../tests/fixtures/flake-show/nix-2.24.json").into(),
+        );
+        nix.json.insert(
+            format!("github:x/y#templates --apply {DEPRECATIONS}"),
+            r#"{"rust": {"deprecated": true, "replacedBy": "github:me/t#rust"},
+                "trivial": {"deprecated": false, "replacedBy": null}}"#
+                .into(),
+        );
+        nix.json.insert(
+            format!("github:x/y#templates --apply {PATHS}"),
+            r#"{"rust": {"path": "/nix/store/x-source/rust"}}"#.into(),
+        );
+
+        let templates = collect_flake(
+            &nix,
+            &flake_config(
+                r#"
+                uri = "github:x/y"
+                deprecations = true
+                [override.trivial]
+                deprecated = true
+                "#,
+            ),
+            &Policy::default(),
+        )
+        .await
+        .unwrap();
+
+        let rust = templates.iter().find(|t| t.name == "rust").unwrap();
+        assert!(rust.deprecated && rust.demoted);
+        assert_eq!(rust.replaced_by.as_deref(), Some("github:me/t#rust"));
+        let trivial = templates.iter().find(|t| t.name == "trivial").unwrap();
+        assert!(trivial.deprecated && trivial.replaced_by.is_none());
+
+        // without asking for them, only the paths of the templates are evaluated
+        let templates = collect_flake(
+            &nix,
+            &flake_config(r#"uri = "github:x/y""#),
+            &Policy::default(),
+        )
+        .await
+        .unwrap();
+        assert!(!templates.iter().any(|t| t.deprecated));
+    }
+
+    #[tokio::test]
+    async fn collect_flake_hashes_each_template() {
+        let dir = std::env::temp_dir().join(format!("nit-hashes-{}", std::process::id()));
+        for (name, content) in [("rust", "fn main() {}"), ("trivial", "{}")] {
+            std::fs::create_dir_all(dir.join(name)).unwrap();
+            std::fs::write(dir.join(name).join("file"), content).unwrap();
+        }
+        let mut nix = Mock::default();
+        nix.show.insert(
+            "github:x/y".into(),
+            include_str!("../tests/fixtures/flake-show/nix-2.24.json").into(),
+        );
+        nix.json.insert(
+            format!("github:x/y#templates --apply {PATHS}"),
+            serde_json::json!({
+                "rust": { "path": dir.join("rust") },
+                "trivial": { "path": dir.join("trivial") },
+            })
+            .to_string(),
+        );
+
+        let templates = collect_flake(
+            &nix,
+            &flake_config(r#"uri = "github:x/y""#),
+            &Policy::default(),
+        )
+        .await
+        .unwrap();
+        let hash = |name: &str| {
+            templates
+                .iter()
+                .find(|t| t.name == name)
+                .unwrap()
+                .nar_hash
+                .clone()
+        };
+
+        assert_eq!(
+            hash("rust"),
+            Some(download::nar_hash(&dir.join("rust")).unwrap())
+        );
+        assert_ne!(hash("rust"), hash("trivial"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn collect_flake_follows_the_registry() {
+        let mut nix = Mock::default();
+        nix.show.insert(
+            "github:NixOS/templates".into(),
+            include_str!("../tests/fixtures/flake-show/nix-2.24.json").into(),
+        );
+        nix.metadata.insert(
+            "templates".into(),
+            r#"{"resolvedUrl":"github:NixOS/templates","revision":"abc"}"#.into(),
+        );
+
+        let templates = collect_flake(
+            &nix,
+            &flake_config(
+                r#"
+                uri = "templates"
+                follow_registry = true
+                "#,
+            ),
+            &Policy::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(!templates.is_empty());
+        for t in &templates {
+            assert_eq!(t.flake_info.uri, "github:NixOS/templates");
+            assert_eq!(t.flake_info.config_uri(), "templates");
+        }
+        let flake = flake_config("uri = \"templates\"\nfollow_registry = true");
+        assert!(
+            collect_flake(&Mock::default(), &flake, &Policy::default())
+                .await
+                .is_err()
+        );
+        let policy = Policy {
+            allow: vec![],
+            deny: vec!["github:NixOS/*".into()],
+        };
+        let denied = collect_flake(&nix, &flake, &policy).await.unwrap_err();
+        assert!(matches!(
+            denied.downcast_ref::<NitError>(),
+            Some(NitError::Denied { uri, .. }) if uri == "github:NixOS/templates"
+        ));
+    }
+
+    #[tokio::test]
+    async fn collect_flake_without_metadata() {
+        let mut nix = Mock::default();
+        nix.show.insert(
+            "github:x/y".into(),
+            r#"{"templates":{"rust":{"description":"Rust","type":"template"}}}"#.into(),
+        );
+
+        let templates = collect_flake(
+            &nix,
+            &flake_config(r#"uri = "github:x/y""#),
+            &Policy::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(templates.len(), 1);
+        assert!(templates[0].flake_info.metadata.is_none());
+
+        assert!(
+            collect_flake(
+                &nix,
+                &flake_config(r#"uri = "github:a/b""#),
+                &Policy::default()
+            )
+            .await
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn collect_changed_fetches_only_changed_sources() {
+        let old: Config = toml::from_str(
+            r#"
+            [[template]]
+            uri = "github:a/a"
+            [[template]]
+            uri = "github:b/b"
+            "#,
+        )
+        .unwrap();
+        let new: Config = toml::from_str(
+            r#"
+            [[template]]
+            uri = "github:a/a"
+            [[template]]
+            uri = "github:b/b"
+            tags = ["new"]
+            "#,
+        )
+        .unwrap();
+        let previous = [
+            parse_flake_show(br#"{"templates":{"x":{"description":""}}}"#, "github:a/a").unwrap(),
+            parse_flake_show(br#"{"templates":{"y":{"description":""}}}"#, "github:b/b").unwrap(),
+        ]
+        .concat();
+
+        // github:a/a has no answer, so fetching it would fail
+        let mut nix = Mock::default();
+        nix.show.insert(
+            "github:b/b".into(),
+            r#"{"templates":{"y":{"description":""},"z":{"description":""}}}"#.into(),
+        );
+
+        let templates = collect_changed(&nix, &old, &new, &previous)
+            .await
+            .unwrap()
+            .templates;
+        let uris: Vec<_> = templates.iter().map(|t| t.uri()).collect();
+        assert_eq!(uris, ["github:a/a#x", "github:b/b#y", "github:b/b#z"]);
+        assert!(templates[2].tags.contains(&"new".to_string()));
+    }
+
+    #[tokio::test]
+    async fn collect_keeps_rate_limited_sources() {
+        let config: Config = toml::from_str(
+            r#"
+            [performance]
+            rate_limit_retries = 0
+            [[template]]
+            uri = "github:a/a"
+            [[template]]
+            uri = "github:b/b"
+            "#,
+        )
+        .unwrap();
+        let previous =
+            parse_flake_show(br#"{"templates":{"x":{"description":""}}}"#, "github:a/a").unwrap();
+
+        let mut nix = Mock::default();
+        nix.rate_limited.push("github:a/a".into());
+        nix.show.insert(
+            "github:b/b".into(),
+            r#"{"templates":{"y":{"description":""}}}"#.into(),
+        );
+
+        let res = collect(&nix, &config, &previous).await.unwrap();
+        let uris: Vec<_> = res.templates.iter().map(|t| t.uri()).collect();
+        assert_eq!(uris, ["github:a/a#x", "github:b/b#y"]);
+        assert_eq!(res.rate_limited, ["github:a/a"]);
+
+        // anything else still fails the whole run
+        nix.show.remove("github:b/b");
+        assert!(collect(&nix, &config, &previous).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn json_file_round_trip() {
+        let dir = std::env::temp_dir().join(format!("nit-cache-test-{}", std::process::id()));
+        let cache = Cache {
+            path: dir.join("cache.jsonl"),
+        };
+        assert!(!cache.exists());
+
+        let templates = parse_flake_show(
+            include_str!("../tests/fixtures/flake-show/lix.json").as_bytes(),
+            "github:x/y",
+        )
+        .unwrap();
+        cache.write(&templates, 42).await.unwrap();
+        assert!(cache.exists());
+        assert!(!cache.is_stale(Duration::from_secs(60)));
+
+        let (header, read) = cache.read().await.unwrap();
+        assert_eq!(header.unwrap().config_hash, 42);
+        assert_eq!(
+            read.map(|t| t.uri().to_string()).collect::<Vec<_>>(),
+            [
+                "github:x/y#default",
+                "github:x/y#go-hello",
+                "github:x/y#rust",
+                "github:x/y#trivial"
+            ]
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::process::{Output, Stdio};
+use std::sync::Mutex;
+
+use tokio::task::AbortHandle;
+
+use crate::locale::tr;
+use crate::platform;
+
+/// Process groups of the running nix commands
+static GROUPS: Mutex<Vec<u32>> = Mutex::new(vec![]);
+/// Files, or directories, that are only complete once renamed into place
+static PARTIAL_FILES: Mutex<Vec<PathBuf>> = Mutex::new(vec![]);
+/// Tasks nobody waits for, like the refresh of a stale cache
+static BACKGROUND: Mutex<Vec<AbortHandle>> = Mutex::new(vec![]);
+
+/// Exit status for Ctrl-C, like shells report for SIGINT
+const CANCELLED: i32 = 128 + 2;
+
+/// On Ctrl-C, SIGTERM or SIGHUP, at any point from the TUI to the hooks, put the terminal back if
+/// the TUI has it, kill the running nix commands with everything they spawned, remove partial
+/// files and exit like shells report the signal, e.g. with [`CANCELLED`]
+pub fn install() {
+    tokio::spawn(async {
+        let Ok(code) = stopped().await else {
+            return;
+        };
+
+        crate::tui::restore_terminal();
+        for group in GROUPS.lock().unwrap().drain(..) {
+            platform::kill_group(group);
+        }
+        for file in PARTIAL_FILES.lock().unwrap().drain(..) {
+            if file.is_dir() {
+                let _ = std::fs::remove_dir_all(file);
+            } else {
+                let _ = std::fs::remove_file(file);
+            }
+        }
+
+        // the terminal may be gone after SIGHUP
+        if code == CANCELLED {
+            let _ = writeln!(io::stderr(), "{}", tr("cancelled", &[]));
+        }
+        std::process::exit(code);
+    });
+}
+
+/// Resolves with the exit code once nit is asked to stop or its terminal goes away
+#[cfg(unix)]
+async fn stopped() -> io::Result<i32> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut hangup = signal(SignalKind::hangup())?;
+
+    Ok(tokio::select! {
+        res = tokio::signal::ctrl_c() => res.map(|()| CANCELLED)?,
+        _ = terminate.recv() => 128 + 15,
+        _ = hangup.recv() => 128 + 1,
+    })
+}
+
+/// Resolves with the exit code once Ctrl-C or Ctrl+Break is pressed or the console window is
+/// closed
+#[cfg(windows)]
+async fn stopped() -> io::Result<i32> {
+    use tokio::signal::windows::{ctrl_break, ctrl_close};
+
+    let mut ctrl_break = ctrl_break()?;
+    let mut close = ctrl_close()?;
+
+    Ok(tokio::select! {
+        res = tokio::signal::ctrl_c() => res.map(|()| CANCELLED)?,
+        _ = ctrl_break.recv() => 128 + 15,
+        _ = close.recv() => 128 + 1,
+    })
+}
+
+/// Removed on Ctrl-C until [`done_writing`] is called
+pub fn writing(path: &Path) {
+    PARTIAL_FILES.lock().unwrap().push(path.to_path_buf());
+}
+
+pub fn done_writing(path: &Path) {
+    PARTIAL_FILES.lock().unwrap().retain(|p| p != path);
+}
+
+/// Aborted by [`background`]
+pub fn track(task: AbortHandle) {
+    BACKGROUND.lock().unwrap().push(task);
+}
+
+/// Abort the tracked tasks, once their result can't be shown anymore. The nix commands they are
+/// waiting for are killed with [`output_async`]
+pub fn background() {
+    for task in BACKGROUND.lock().unwrap().drain(..) {
+        task.abort();
+    }
+}
+
+/// Like [`std::process::Command::output`], killed with its process group on Ctrl-C.
+/// Only for non-interactive commands, since the group has no access to the terminal
+pub fn output(cmd: &mut std::process::Command) -> io::Result<Output> {
+    let child = platform::new_group(cmd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let id = child.id();
+
+    GROUPS.lock().unwrap().push(id);
+    let output = child.wait_with_output();
+    GROUPS.lock().unwrap().retain(|g| *g != id);
+
+    output
+}
+
+/// [`output`] for tokio. The process group is also killed when the future is dropped, e.g. by
+/// [`background`]
+pub async fn output_async(cmd: &mut tokio::process::Command) -> io::Result<Output> {
+    /// Kills the group unless the command finished
+    struct Group(Option<u32>);
+
+    impl Drop for Group {
+        fn drop(&mut self) {
+            if let Some(id) = self.0 {
+                GROUPS.lock().unwrap().retain(|g| *g != id);
+                platform::kill_group(id);
+            }
+        }
+    }
+
+    platform::new_group(cmd.as_std_mut());
+    let child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let mut group = Group(child.id());
+    if let Some(id) = group.0 {
+        GROUPS.lock().unwrap().push(id);
+    }
+    let output = child.wait_with_output().await;
+    if let Some(id) = group.0.take() {
+        GROUPS.lock().unwrap().retain(|g| *g != id);
+    }
+
+    output
+}
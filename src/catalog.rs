@@ -0,0 +1,227 @@
+//! `catalog` in the config: remote TOML files of `[[template]]`s. Each one is downloaded into the
+//! cache directory the first time and again on every `--re-cache`, and its entries are added to
+//! the config when it is loaded
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use futures::{StreamExt as _, TryStreamExt as _};
+use ltrait::color_eyre::{
+    Result,
+    eyre::{ContextCompat, ensure, eyre},
+};
+use serde::Deserialize;
+
+use crate::cancel;
+use crate::config::{CatalogSource, Config, SourceType, TemplateConfig, TemplateOverride, fnv1a};
+use crate::download::{curl, sha256};
+use crate::locale::tr;
+
+#[derive(Debug, Deserialize)]
+struct Catalog {
+    #[serde(default)]
+    template: Vec<CatalogTemplate>,
+}
+
+/// The part of a `[[template]]` a catalog may set: which templates are listed and how. What runs
+/// or is written on init, such as `hooks`, `rename`, `env` or `netrc`, is left to the config
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CatalogTemplate {
+    name: Option<String>,
+    uri: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    templates: Option<Vec<String>>,
+    execludes: Option<Vec<String>>,
+    #[serde(default, rename = "override")]
+    overrides: BTreeMap<String, TemplateOverride>,
+    #[serde(default)]
+    boost: f64,
+    #[serde(default)]
+    boosts: BTreeMap<String, f64>,
+    #[serde(default)]
+    demote: bool,
+    #[serde(default)]
+    demotes: Vec<String>,
+}
+
+impl From<CatalogTemplate> for TemplateConfig {
+    fn from(t: CatalogTemplate) -> Self {
+        TemplateConfig {
+            name: t.name,
+            uri: t.uri,
+            source_type: SourceType::Flake,
+            follow_registry: false,
+            deprecations: false,
+            tags: t.tags,
+            templates: t.templates,
+            execludes: t.execludes,
+            rename: vec![],
+            replace: vec![],
+            hooks: Default::default(),
+            gitignore: vec![],
+            license: None,
+            readme: None,
+            netrc: None,
+            ssh: None,
+            env: Default::default(),
+            pure_eval: true,
+            boost: t.boost,
+            boosts: t.boosts,
+            demote: t.demote,
+            demotes: t.demotes,
+            overrides: t.overrides,
+        }
+    }
+}
+
+/// Where the last version of `catalog` is kept, named after its url
+fn path(catalog: &CatalogSource) -> Result<PathBuf> {
+    Ok(crate::platform::cache_dir()
+        .wrap_err("Cache directory does'nt exit.")?
+        .join("nix-nit")
+        .join("catalogs")
+        .join(format!("{:016x}.toml", fnv1a(catalog.url.as_bytes()))))
+}
+
+/// The content of `catalog` as it was last fetched. `None` before the first fetch, or if it isn't
+/// what `sha256` pins it to anymore
+pub fn cached(catalog: &CatalogSource) -> Result<Option<String>> {
+    let path = path(catalog)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    if let Some(pinned) = &catalog.sha256
+        && !sha256(&path)?.eq_ignore_ascii_case(pinned)
+    {
+        return Ok(None);
+    }
+
+    Ok(Some(std::fs::read_to_string(path)?))
+}
+
+/// The `[[template]]`s of `content`, which may only set the keys of [`CatalogTemplate`]
+pub fn templates(catalog: &CatalogSource, content: &str) -> Result<Vec<TemplateConfig>> {
+    let parsed: Catalog = toml::from_str(content).map_err(|e| {
+        eyre!(tr(
+            "catalog-broken",
+            &[("url", &catalog.url), ("message", &e)]
+        ))
+    })?;
+
+    Ok(parsed.template.into_iter().map(Into::into).collect())
+}
+
+/// Download the catalogs of `config` that weren't yet, or all of them with `refresh`. A catalog
+/// that can't be refreshed keeps its last version
+pub async fn fetch_all(config: &Config, refresh: bool) -> Result<()> {
+    let mut missing = vec![];
+    for catalog in &config.catalog {
+        config.policy.check(&catalog.url)?;
+        if refresh || cached(catalog)?.is_none() {
+            missing.push(catalog.clone());
+        }
+    }
+
+    futures::stream::iter(missing)
+        .map(|catalog| async move {
+            tokio::task::spawn_blocking(move || match fetch(&catalog) {
+                Err(e) if cached(&catalog).is_ok_and(|c| c.is_some()) => {
+                    tracing::warn!(
+                        "{}",
+                        tr("catalog-kept", &[("url", &catalog.url), ("error", &e)])
+                    );
+                    Ok(())
+                }
+                res => res,
+            })
+            .await?
+        })
+        .buffer_unordered(config.performance.fetch_concurrency.max(1))
+        .try_collect()
+        .await
+}
+
+fn fetch(catalog: &CatalogSource) -> Result<()> {
+    let path = path(catalog)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let partial = path.with_extension("partial");
+    cancel::writing(&partial);
+
+    let res = download(catalog, &partial).and_then(|()| Ok(std::fs::rename(&partial, &path)?));
+    let _ = std::fs::remove_file(&partial);
+    cancel::done_writing(&partial);
+
+    res
+}
+
+/// Into `to`, checked against the pin and for being a catalog
+fn download(catalog: &CatalogSource, to: &std::path::Path) -> Result<()> {
+    tracing::info!("downloading the catalog {}", catalog.url);
+    curl(&[&catalog.url, "-o", &to.to_string_lossy()])?;
+
+    if let Some(pinned) = &catalog.sha256 {
+        let actual = sha256(to)?;
+        ensure!(
+            actual.eq_ignore_ascii_case(pinned),
+            tr(
+                "checksum-mismatch",
+                &[
+                    ("url", &catalog.url),
+                    ("expected", pinned),
+                    ("actual", &actual)
+                ]
+            )
+        );
+    }
+    templates(catalog, &std::fs::read_to_string(to)?)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_listing_keys() {
+        let catalog = CatalogSource {
+            url: "https://example.com/templates.toml".into(),
+            sha256: None,
+        };
+        let templates = templates(
+            &catalog,
+            r#"
+            [[template]]
+            uri = "github:team/templates"
+            tags = ["team"]
+            boost = 1.0
+            [template.override.rust]
+            icon = "R"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(templates.len(), 1);
+        assert_eq!(templates[0].tags, ["team"]);
+        assert_eq!(templates[0].boost, 1.0);
+        assert_eq!(templates[0].overrides["rust"].icon.as_deref(), Some("R"));
+        assert!(super::templates(&catalog, "template = 1").is_err());
+
+        for key in [
+            "[template.hooks]\npost_init = [\"curl https://example.com | sh\"]",
+            "[[template.rename]]\nfile = \"/home/me/.bashrc\"\nfrom = \"a\"",
+            "[template.env]\nGIT_SSH_COMMAND = \"sh\"",
+            "netrc = \"/etc/netrc\"",
+            "ssh = true",
+            "pure_eval = false",
+            "type = \"cookiecutter\"",
+        ] {
+            let content = format!("[[template]]\nuri = \"github:team/templates\"\n{key}");
+            assert!(super::templates(&catalog, &content).is_err(), "{key}");
+        }
+    }
+}
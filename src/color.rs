@@ -0,0 +1,210 @@
+use std::fmt;
+use std::io::IsTerminal as _;
+use std::sync::OnceLock;
+
+use ratatui::style::{Color, Style};
+
+/// How many colors the terminal shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Support {
+    None,
+    /// The 16 ANSI colors
+    Basic,
+    Ansi256,
+    TrueColor,
+}
+
+impl fmt::Display for Support {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Support::None => "no colors",
+            Support::Basic => "16 colors",
+            Support::Ansi256 => "256 colors",
+            Support::TrueColor => "truecolor",
+        })
+    }
+}
+
+static SUPPORT: OnceLock<Support> = OnceLock::new();
+
+/// Of the terminal on stderr, detected once
+pub fn support() -> Support {
+    *SUPPORT.get_or_init(|| {
+        detect(
+            |key| std::env::var(key).ok(),
+            std::io::stderr().is_terminal(),
+        )
+    })
+}
+
+/// `NO_COLOR` turns colors off and `CLICOLOR_FORCE` on, even without a terminal. Otherwise
+/// `CLICOLOR=0` and `TERM=dumb` turn them off, and `COLORTERM` and `TERM` tell how many there are
+fn detect(var: impl Fn(&str) -> Option<String>, terminal: bool) -> Support {
+    if var("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        return Support::None;
+    }
+
+    let forced = var("CLICOLOR_FORCE").is_some_and(|v| !v.is_empty() && v != "0");
+    let term = var("TERM").unwrap_or_default();
+    if !forced && (!terminal || var("CLICOLOR").as_deref() == Some("0") || term == "dumb") {
+        return Support::None;
+    }
+
+    match var("COLORTERM").as_deref() {
+        Some("truecolor" | "24bit") => Support::TrueColor,
+        _ if term.contains("256color") => Support::Ansi256,
+        _ => Support::Basic,
+    }
+}
+
+/// `style` with its colors replaced by the closest ones the terminal shows. Modifiers like bold
+/// stay, as `NO_COLOR` is only about colors
+pub fn degrade(style: Style) -> Style {
+    degrade_to(style, support())
+}
+
+fn degrade_to(mut style: Style, support: Support) -> Style {
+    style.fg = style.fg.and_then(|c| closest(c, support));
+    style.bg = style.bg.and_then(|c| closest(c, support));
+    style
+}
+
+fn closest(color: Color, support: Support) -> Option<Color> {
+    match (support, color) {
+        (Support::None, _) => None,
+        (Support::Ansi256, Color::Rgb(r, g, b)) => Some(Color::Indexed(indexed(r, g, b))),
+        (Support::Basic, Color::Rgb(r, g, b)) => Some(basic((r, g, b))),
+        (Support::Basic, Color::Indexed(i)) => Some(basic(rgb(i))),
+        _ => Some(color),
+    }
+}
+
+/// The xterm defaults of the 16 ANSI colors
+const BASIC: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+fn basic((r, g, b): (u8, u8, u8)) -> Color {
+    let distance = |(cr, cg, cb): (u8, u8, u8)| {
+        [(r, cr), (g, cg), (b, cb)]
+            .iter()
+            .map(|&(x, y)| (i32::from(x) - i32::from(y)).pow(2))
+            .sum::<i32>()
+    };
+
+    BASIC
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb))
+        .map(|(color, _)| *color)
+        .unwrap_or(Color::Reset)
+}
+
+/// Of the 6x6x6 cube of the 256 colors, or of its gray ramp for grays
+fn indexed(r: u8, g: u8, b: u8) -> u8 {
+    if r == g && g == b {
+        return match r {
+            0..8 => 16,
+            248.. => 231,
+            _ => 232 + (r - 8) / 10,
+        };
+    }
+
+    let level = |x: u8| ((u16::from(x) * 5 + 127) / 255) as u8;
+    16 + 36 * level(r) + 6 * level(g) + level(b)
+}
+
+fn rgb(i: u8) -> (u8, u8, u8) {
+    let level = |x: u8| if x == 0 { 0 } else { 55 + 40 * x };
+
+    match i {
+        0..16 => BASIC[usize::from(i)].1,
+        16..232 => {
+            let i = i - 16;
+            (level(i / 36), level(i / 6 % 6), level(i % 6))
+        }
+        _ => {
+            let gray = 8 + 10 * (i - 232);
+            (gray, gray, gray)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn detection() {
+        let detect_with = |vars: &[(&str, &str)], terminal| {
+            let vars: HashMap<_, _> = vars.iter().copied().collect();
+            detect(|key| vars.get(key).map(|v| v.to_string()), terminal)
+        };
+
+        assert_eq!(
+            detect_with(&[("TERM", "xterm-256color")], true),
+            Support::Ansi256
+        );
+        assert_eq!(
+            detect_with(&[("TERM", "xterm"), ("COLORTERM", "truecolor")], true),
+            Support::TrueColor
+        );
+        assert_eq!(detect_with(&[("TERM", "linux")], true), Support::Basic);
+        assert_eq!(
+            detect_with(&[("TERM", "xterm-256color"), ("NO_COLOR", "1")], true),
+            Support::None
+        );
+        assert_eq!(detect_with(&[("TERM", "dumb")], true), Support::None);
+        assert_eq!(
+            detect_with(&[("TERM", "xterm-256color")], false),
+            Support::None
+        );
+        assert_eq!(
+            detect_with(
+                &[("TERM", "xterm-256color"), ("CLICOLOR_FORCE", "1")],
+                false
+            ),
+            Support::Ansi256
+        );
+    }
+
+    #[test]
+    fn degrading() {
+        let style = Style::new()
+            .fg(Color::Rgb(250, 10, 10))
+            .bg(Color::Indexed(21));
+
+        assert_eq!(degrade_to(style, Support::TrueColor), style);
+        assert_eq!(
+            degrade_to(style, Support::Ansi256),
+            Style::new().fg(Color::Indexed(196)).bg(Color::Indexed(21))
+        );
+        assert_eq!(
+            degrade_to(style, Support::Basic),
+            Style::new().fg(Color::LightRed).bg(Color::Blue)
+        );
+        assert_eq!(
+            degrade_to(
+                style.add_modifier(ratatui::style::Modifier::DIM),
+                Support::None
+            ),
+            Style::new().add_modifier(ratatui::style::Modifier::DIM)
+        );
+    }
+}
@@ -0,0 +1,227 @@
+use clap::{Arg, Command, ValueHint};
+use ltrait::color_eyre::Result;
+
+use crate::cache::{self, CacheStore as _};
+use crate::config::Config;
+use crate::man;
+
+/// What `nit __complete` completes
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Kind {
+    /// `uri#name` of the cached templates
+    Templates,
+    /// The `[profile.<name>]`s of the config
+    Profiles,
+}
+
+/// Print one candidate per line for shell completion. Only the cache is read, since nix is too
+/// slow to wait for on TAB
+pub async fn run(kind: Kind, prefix: &str, profile: Option<&str>) -> Result<()> {
+    match kind {
+        Kind::Templates => {
+            let cache = cache::store(profile, Config::load_optional()?.as_ref())?;
+            if !cache.exists() {
+                return Ok(());
+            }
+
+            for t in cache.with_prefix(prefix).await? {
+                println!("{}", t.uri());
+            }
+        }
+        Kind::Profiles => {
+            let Some(config) = Config::load_optional()? else {
+                return Ok(());
+            };
+
+            for name in config.profile.keys().filter(|n| n.starts_with(prefix)) {
+                println!("{name}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The shells `nit completions` writes a completion for
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Shell {
+    /// `_nit`, for a directory in `fpath`
+    Zsh,
+}
+
+/// Print the completion for `shell`. With `dynamic`, templates and profiles are completed by
+/// calling `nit __complete` on TAB, instead of nothing
+pub fn script(mut cmd: Command, shell: Shell, dynamic: bool) {
+    // propagates the global options and adds --help
+    cmd.build();
+
+    match shell {
+        Shell::Zsh => print!("{}", zsh(&cmd, dynamic)),
+    }
+}
+
+fn zsh(cmd: &Command, dynamic: bool) -> String {
+    let mut script = String::from("#compdef nit\n\n_nit() {\n");
+    script.push_str("  local curcontext=\"$curcontext\" state line\n  typeset -A opt_args\n\n");
+
+    script.push_str("  _arguments -C \\\n");
+    for spec in zsh_specs(cmd, dynamic) {
+        script.push_str(&format!("    {spec} \\\n"));
+    }
+    script.push_str("    '1: :_nit_commands' \\\n    '*:: :->command'\n\n");
+
+    script.push_str("  case $state in\n    command)\n");
+    script.push_str(
+        "      curcontext=\"${curcontext%:*:*}:nit-$words[1]:\"\n      case $words[1] in\n",
+    );
+    for sub in man::visible_subcommands(cmd) {
+        script.push_str(&format!(
+            "        {})\n          _arguments",
+            sub.get_name()
+        ));
+        for spec in zsh_specs(sub, dynamic) {
+            script.push_str(&format!(" \\\n            {spec}"));
+        }
+        script.push_str("\n          ;;\n");
+    }
+    script.push_str("      esac\n      ;;\n  esac\n}\n\n");
+
+    script.push_str("_nit_commands() {\n  local -a commands\n  commands=(\n");
+    for sub in man::visible_subcommands(cmd) {
+        let about = sub.get_about().map(|a| a.to_string()).unwrap_or_default();
+        script.push_str(&format!(
+            "    {}\n",
+            quote(&format!("{}:{}", sub.get_name(), about.replace(':', "\\:")))
+        ));
+    }
+    script.push_str("  )\n  _describe -t commands command commands\n}\n\n");
+
+    if dynamic {
+        for kind in ["templates", "profiles"] {
+            script.push_str(&format!(
+                "_nit_{kind}() {{\n  compadd -- ${{(f)\"$(nit __complete {kind} \"$PREFIX\" \
+                 2>/dev/null)\"}}\n}}\n\n"
+            ));
+        }
+    }
+
+    script.push_str("_nit \"$@\"\n");
+    script
+}
+
+/// The `_arguments` specs of the options and positionals of `cmd`, quoted
+fn zsh_specs(cmd: &Command, dynamic: bool) -> Vec<String> {
+    let mut specs = vec![];
+
+    for arg in cmd.get_arguments().filter(|a| !a.is_hide_set()) {
+        let help = arg
+            .get_help()
+            .map(|h| h.to_string().replace('\n', " "))
+            .unwrap_or_default()
+            .replace('[', "\\[")
+            .replace(']', "\\]");
+        let takes_value = arg.get_action().takes_values();
+        let value = || {
+            let value_name = arg
+                .get_value_names()
+                .and_then(|v| v.first())
+                .map(|v| v.to_string())
+                .unwrap_or_else(|| arg.get_id().to_string());
+            format!(":{value_name}:{}", zsh_action(arg, dynamic))
+        };
+
+        if arg.is_positional() {
+            specs.push(quote(&value()));
+            continue;
+        }
+        let rest = if takes_value {
+            format!("[{help}]{}", value())
+        } else {
+            format!("[{help}]")
+        };
+        let short = arg
+            .get_short()
+            .map(|s| format!("-{s}{}", if takes_value { "+" } else { "" }));
+        let long = arg
+            .get_long()
+            .map(|l| format!("--{l}{}", if takes_value { "=" } else { "" }));
+        specs.push(match (short, long) {
+            // offered once, as either
+            (Some(short), Some(long)) => format!(
+                "{}{{{},{}}}{}",
+                quote(&format!(
+                    "({} {})",
+                    short.trim_end_matches('+'),
+                    long.trim_end_matches('=')
+                )),
+                quote(&short),
+                quote(&long),
+                quote(&rest)
+            ),
+            (Some(flag), None) | (None, Some(flag)) => quote(&format!("{flag}{rest}")),
+            (None, None) => continue,
+        });
+    }
+
+    specs
+}
+
+/// How zsh completes the value of `arg`
+fn zsh_action(arg: &Arg, dynamic: bool) -> String {
+    let possible: Vec<_> = arg
+        .get_possible_values()
+        .iter()
+        .filter(|v| !v.is_hide_set())
+        .map(|v| v.get_name().to_string())
+        .collect();
+    if !possible.is_empty() {
+        return format!("({})", possible.join(" "));
+    }
+
+    match (arg.get_id().as_str(), arg.get_value_hint()) {
+        ("template", _) if dynamic => "_nit_templates".into(),
+        ("profile", _) if dynamic => "_nit_profiles".into(),
+        (_, ValueHint::DirPath) => "_files -/".into(),
+        (_, ValueHint::FilePath | ValueHint::AnyPath) => "_files".into(),
+        _ => String::new(),
+    }
+}
+
+/// In single quotes for the shell
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory as _;
+
+    use super::*;
+
+    fn script(dynamic: bool) -> String {
+        let mut cmd = crate::Args::command();
+        cmd.build();
+        zsh(&cmd, dynamic)
+    }
+
+    #[test]
+    fn zsh_completion() {
+        let dynamic = script(true);
+        assert!(dynamic.starts_with("#compdef nit\n"));
+        assert!(dynamic.contains("':TEMPLATE:_nit_templates'"));
+        assert!(dynamic.contains("_nit_templates() {"));
+        assert!(dynamic.contains("'--into=[Initialize into this directory"));
+        assert!(dynamic.contains(":DIR:_files -/'"));
+        assert!(dynamic.contains("'(-y --yes)'{'-y','--yes'}"));
+        assert!(!dynamic.contains("__complete)"));
+
+        let fixed = script(false);
+        assert!(fixed.contains("':TEMPLATE:'"));
+        assert!(!fixed.contains("_nit_templates"));
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote("can't"), "'can'\\''t'");
+    }
+}
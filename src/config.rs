@@ -0,0 +1,840 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use ltrait::color_eyre::{Result, eyre::ContextCompat};
+use serde::{Deserialize, Serialize};
+
+use crate::error::NitError;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub template: Vec<TemplateConfig>,
+    /// Templates initialized one after another, shown as a single entry
+    #[serde(default)]
+    pub bundle: Vec<BundleConfig>,
+    /// Separate template sets, used instead of `template` with `--profile`
+    #[serde(default)]
+    pub profile: BTreeMap<String, Profile>,
+    /// Set by [`Config::use_profile`]
+    #[serde(skip)]
+    pub active_profile: Option<String>,
+    /// Where templates are initialized when `--into` is omitted, relative to the current directory
+    pub into: Option<PathBuf>,
+    /// `git add` the generated files when initializing inside a git repository
+    #[serde(default)]
+    pub git_add: bool,
+    #[serde(default)]
+    pub hooks: Hooks,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    #[serde(default)]
+    pub performance: PerformanceConfig,
+    /// The flake uris nit may run nix against
+    #[serde(default)]
+    pub policy: crate::policy::Policy,
+    /// Where templates come from besides `template`
+    #[serde(default)]
+    pub sources: Sources,
+    /// Remote TOML files whose `[[template]]`s are added to `template`, see [`crate::catalog`]
+    #[serde(default, deserialize_with = "deserialize_catalogs")]
+    pub catalog: Vec<CatalogSource>,
+    /// Drop the `default` template of every flake, which is usually also exported under a proper
+    /// name
+    #[serde(default)]
+    pub hide_defaults: bool,
+    /// Appended to the `.gitignore` of the project after init, unless already there
+    #[serde(default)]
+    pub gitignore: Vec<String>,
+    /// SPDX identifier of the license written to `LICENSE` after init, if the template has none
+    pub license: Option<String>,
+    /// The copyright holder in the license. `git config user.name` if omitted
+    pub author: Option<String>,
+    /// Write a `README.md` from the description and `welcomeText` of the template after init, if
+    /// it has none
+    #[serde(default)]
+    pub readme: bool,
+    /// Overridden by `--log-format`
+    #[serde(default)]
+    pub log_format: crate::logging::LogFormat,
+    #[serde(default)]
+    pub matcher: crate::matcher::MatcherConfig,
+    #[serde(default)]
+    pub sort: crate::frecency::SortMode,
+    /// Ask before initializing the selected template. Off like `--yes` on every launch
+    #[serde(default = "default_true")]
+    pub confirm: bool,
+    /// Like `--resume` on every launch
+    #[serde(default)]
+    pub resume: bool,
+    /// Of the messages, prompts and hints
+    #[serde(default)]
+    pub language: crate::locale::Language,
+    /// Like `--plain` on every launch
+    #[serde(default)]
+    pub accessible: bool,
+    /// Append `(×<uses>)` to the entries initialized before
+    #[serde(default)]
+    pub usage_counts: bool,
+    /// Notify the desktop when an init or a rebuild of the cache that took a while ended
+    #[serde(default)]
+    pub notifications: bool,
+    /// The program and leading arguments nix is run as, e.g. through `wsl.exe` on Windows
+    pub nix_command: Option<Vec<String>>,
+    /// Set for every process nit runs, nix, hooks and the pager included, with `${VAR}` expanded
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// The key bindings of the TUI
+    #[serde(default)]
+    pub keymap: crate::tui::Keymap,
+    #[serde(default)]
+    pub ui: UiConfig,
+    /// Bindings on top of `keymap`, e.g. `ctrl-n = "down"`
+    #[serde(default, deserialize_with = "deserialize_keys")]
+    pub keys: Vec<(crossterm::event::KeyEvent, crate::tui::Action)>,
+    /// Hash of the config file, to tell whether the cache was built from it
+    #[serde(skip)]
+    pub hash: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Profile {
+    #[serde(default)]
+    pub template: Vec<TemplateConfig>,
+}
+
+#[derive(Debug, Default, Deserialize, Clone)]
+pub struct CacheConfig {
+    /// How long the cache stays fresh, e.g. `"12h"` or `"7d"`. Stale caches are still used for the
+    /// launch, and refreshed in the background
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub ttl: Option<Duration>,
+    #[serde(default)]
+    pub store: CacheStoreKind,
+}
+
+/// Where the cache is kept
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheStoreKind {
+    /// A JSON object per line
+    #[default]
+    Json,
+    /// An indexed SQLite database, read in pages. Needs the `sqlite-cache` feature
+    Sqlite,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PerformanceConfig {
+    /// How many sources are fetched at once when building the cache. Lower it if GitHub rate
+    /// limits the fetches
+    #[serde(default = "default_fetch_concurrency")]
+    pub fetch_concurrency: usize,
+    /// How often a rate limited source is retried, waiting twice as long each time starting at a
+    /// second, before the cache is built without it
+    #[serde(default = "default_rate_limit_retries")]
+    pub rate_limit_retries: u32,
+}
+
+impl Default for PerformanceConfig {
+    fn default() -> Self {
+        Self {
+            fetch_concurrency: default_fetch_concurrency(),
+            rate_limit_retries: default_rate_limit_retries(),
+        }
+    }
+}
+
+/// How the TUI looks
+#[derive(Debug, Deserialize, Clone)]
+pub struct UiConfig {
+    /// In front of the highlighted entry
+    #[serde(default = "default_marker")]
+    pub marker: String,
+    /// In front of the other entries
+    #[serde(default = "default_filler")]
+    pub filler: String,
+    /// In front of the query
+    #[serde(default)]
+    pub prompt: String,
+    /// The picker of `--ui gui`, reading the entries from stdin and printing the chosen one
+    pub gui: Option<Vec<String>>,
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            marker: default_marker(),
+            filler: default_filler(),
+            prompt: String::new(),
+            gui: None,
+        }
+    }
+}
+
+fn default_marker() -> String {
+    ">".into()
+}
+
+fn default_filler() -> String {
+    " ".into()
+}
+
+fn default_fetch_concurrency() -> usize {
+    4
+}
+
+fn default_rate_limit_retries() -> u32 {
+    3
+}
+
+/// `[keys]`, parsed with [`crate::tui::parse_key`]
+fn deserialize_keys<'de, D>(
+    deserializer: D,
+) -> Result<Vec<(crossterm::event::KeyEvent, crate::tui::Action)>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    BTreeMap::<String, crate::tui::Action>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(key, action)| {
+            crate::tui::parse_key(&key)
+                .map(|key| (key, action))
+                .map_err(serde::de::Error::custom)
+        })
+        .collect()
+}
+
+/// A url or `{ url, sha256 }`, or a list of them
+fn deserialize_catalogs<'de, D>(deserializer: D) -> Result<Vec<CatalogSource>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum One {
+        Url(String),
+        Pinned(CatalogSource),
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        One(One),
+        Many(Vec<One>),
+    }
+
+    let catalogs = match Raw::deserialize(deserializer)? {
+        Raw::One(one) => vec![one],
+        Raw::Many(many) => many,
+    };
+    Ok(catalogs
+        .into_iter()
+        .map(|one| match one {
+            One::Url(url) => CatalogSource { url, sha256: None },
+            One::Pinned(catalog) => catalog,
+        })
+        .collect())
+}
+
+/// 64 hex digits. Checked here, as the checksum of an archive also names its directory
+fn deserialize_sha256<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let sha256 = String::deserialize(deserializer)?;
+    if sha256.len() != 64 || !sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(serde::de::Error::custom(format!(
+            "`{sha256}` isn't a sha256, expected 64 hex digits"
+        )));
+    }
+
+    Ok(sha256)
+}
+
+/// [`deserialize_sha256`], for an optional one
+fn deserialize_optional_sha256<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    deserialize_sha256(deserializer).map(Some)
+}
+
+/// `<number><s|m|h|d>`, or a plain number of seconds
+fn deserialize_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Secs(u64),
+        Text(String),
+    }
+
+    let secs = match Raw::deserialize(deserializer)? {
+        Raw::Secs(secs) => secs,
+        Raw::Text(text) => {
+            let text = text.trim();
+            let (number, unit) = text.split_at(
+                text.find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(text.len()),
+            );
+            let number: u64 = number.parse().map_err(serde::de::Error::custom)?;
+            number
+                * match unit.trim() {
+                    "" | "s" => 1,
+                    "m" => 60,
+                    "h" => 60 * 60,
+                    "d" => 60 * 60 * 24,
+                    unit => {
+                        return Err(serde::de::Error::custom(format!(
+                            "unknown unit `{unit}`, expected s, m, h or d"
+                        )));
+                    }
+                }
+        }
+    };
+
+    Ok(Some(Duration::from_secs(secs)))
+}
+
+/// Merged over the config when nit starts in the directory of this file, e.g. the root of a
+/// monorepo
+const OVERLAY: &str = ".nit/config.toml";
+
+/// The [`Overlay`] of the directory nit started in, if there is one. Looked up once, so that it
+/// stays the same after nit moved into the target
+pub fn overlay() -> Option<&'static Path> {
+    static PATH: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+    PATH.get_or_init(|| {
+        std::path::absolute(OVERLAY)
+            .ok()
+            .filter(|path| path.exists())
+    })
+    .as_deref()
+}
+
+/// `./.nit/config.toml`. Only what a project can decide for itself, so that `[policy]` and the
+/// like stay with the user. Hooks stay with the user too, as a cloned repository could run
+/// anything with them
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Overlay {
+    #[serde(default)]
+    pub template: Vec<TemplateConfig>,
+    #[serde(default)]
+    pub bundle: Vec<BundleConfig>,
+    pub into: Option<PathBuf>,
+}
+
+/// `[[bundle]]`
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct BundleConfig {
+    pub name: String,
+    /// The templates joined with ` + ` if omitted
+    #[serde(default)]
+    pub description: String,
+    /// `uri#name`s, initialized in this order into the same directory
+    pub templates: Vec<String>,
+    /// Run once, before the first template and after the last
+    #[serde(default)]
+    pub hooks: Hooks,
+}
+
+/// Shell commands run with `NIT_TEMPLATE_URI`, `NIT_TEMPLATE_NAME` and `NIT_TARGET_DIR` set
+#[derive(Debug, Default, Deserialize, Clone, PartialEq)]
+pub struct Hooks {
+    /// Run before `nix flake init`. Init is aborted if one of them fails
+    #[serde(default)]
+    pub pre_init: Vec<String>,
+    #[serde(default)]
+    pub post_init: Vec<String>,
+}
+
+/// `[sources]`
+#[derive(Debug, Default, Deserialize, Clone, PartialEq)]
+pub struct Sources {
+    /// Downloaded into the cache directory and collected as `path:` flakes
+    #[serde(default)]
+    pub archive: Vec<ArchiveSource>,
+}
+
+/// `[[sources.archive]]`: a tarball of a flake with templates, e.g. on an artifact store inside a
+/// network without access to GitHub
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct ArchiveSource {
+    pub url: String,
+    /// Of the tarball, checked before anything is unpacked
+    #[serde(deserialize_with = "deserialize_sha256")]
+    pub sha256: String,
+    /// Like `name` of [`TemplateConfig`]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// `catalog`: a TOML file of `[[template]]`s, e.g. the ones a platform team publishes for everyone
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct CatalogSource {
+    pub url: String,
+    /// Pins the catalog to this content. Without it, every `--re-cache` follows the catalog
+    #[serde(default, deserialize_with = "deserialize_optional_sha256")]
+    pub sha256: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct TemplateConfig {
+    pub name: Option<String>,
+    pub uri: String,
+    #[serde(rename = "type", default)]
+    pub source_type: SourceType,
+    /// Resolve `uri` through the flake registry, so that the templates of e.g. `templates` are
+    /// cached and shown under the url it points to
+    #[serde(default)]
+    pub follow_registry: bool,
+    /// Read `deprecated` and `replacedBy` of the templates, which `nix flake show` leaves out. One
+    /// more evaluation of the flake every time it is fetched
+    #[serde(default)]
+    pub deprecations: bool,
+    /// Attached to every template of the flake
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub templates: Option<Vec<String>>,
+    pub execludes: Option<Vec<String>>,
+    /// Where the project name is patched in after init
+    #[serde(default)]
+    pub rename: Vec<RenameRule>,
+    /// Applied to every file the template generated
+    #[serde(default)]
+    pub replace: Vec<ReplaceRule>,
+    /// Run after the global hooks
+    #[serde(default)]
+    pub hooks: Hooks,
+    /// Added to the global `gitignore`
+    #[serde(default)]
+    pub gitignore: Vec<String>,
+    /// Instead of the global `license`
+    pub license: Option<String>,
+    /// Instead of the global `readme`
+    pub readme: Option<bool>,
+    /// Passed to nix as `--netrc-file`, for flakes behind HTTP authentication
+    pub netrc: Option<PathBuf>,
+    pub ssh: Option<Ssh>,
+    /// Set for every command run for this flake, e.g. git configuration for fetching it
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// `false` passes `--impure` to nix, for flakes that read the environment while evaluating
+    #[serde(default = "default_true")]
+    pub pure_eval: bool,
+    /// Added to the frecency bonus every time a template of the flake is used, so that it climbs
+    /// faster than the others. Negative values sink it like `demote`
+    #[serde(default)]
+    pub boost: f64,
+    /// Per template name, instead of `boost`
+    #[serde(default)]
+    pub boosts: BTreeMap<String, f64>,
+    /// Sink every template of the flake to the bottom until a query is typed
+    #[serde(default)]
+    pub demote: bool,
+    /// Per template name, like `demote`
+    #[serde(default)]
+    pub demotes: Vec<String>,
+    /// Per `uri#name` or template name, instead of what the flake says about the template
+    #[serde(default, rename = "override")]
+    pub overrides: BTreeMap<String, TemplateOverride>,
+}
+
+/// `[[template]]`s appended to a config by `nit config add` and the first run
+#[derive(Debug, Serialize)]
+pub struct Snippet<'a> {
+    pub template: Vec<Entry<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Entry<'a> {
+    pub name: &'a str,
+    pub uri: &'a str,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub tags: &'a [String],
+}
+
+/// `[template.override."<uri>#<name>"]`
+#[derive(Debug, Default, Deserialize, Clone, PartialEq)]
+pub struct TemplateOverride {
+    pub description: Option<String>,
+    /// Instead of every tag, including the ones of the source type
+    pub tags: Option<Vec<String>>,
+    /// In front of the entry, left out with `--plain`
+    pub icon: Option<String>,
+    /// Shown instead of `uri#name`, which is still what gets initialized
+    pub display_name: Option<String>,
+    /// Marked, sunk like `demote` and replaced on selection if `replaced_by` is set
+    #[serde(default)]
+    pub deprecated: bool,
+    /// The `uri#name` to use instead. Deprecates the template too
+    pub replaced_by: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// How git reaches the flake over SSH
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Ssh {
+    /// Never ask for a password or host key, so that a missing key fails instead of hanging
+    Enabled(bool),
+    /// Authenticate with this key only
+    Key(PathBuf),
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SourceType {
+    #[default]
+    Flake,
+    /// A flake following devenv's template convention, e.g. `github:cachix/devenv`
+    Devenv,
+    /// A git URL of a cookiecutter template, initialized with `cookiecutter`
+    Cookiecutter,
+    /// A git URL of a cargo-generate template, initialized with `cargo generate`
+    CargoGenerate,
+    /// A `[[bundle]]` of other templates, not a `type` of `[[template]]`
+    Bundle,
+}
+
+impl SourceType {
+    /// The tags every template from this kind of source gets
+    pub fn tags(self) -> &'static [&'static str] {
+        match self {
+            SourceType::Flake => &[],
+            SourceType::Devenv => &["devenv"],
+            SourceType::Cookiecutter => &["cookiecutter"],
+            SourceType::CargoGenerate => &["cargo-generate", "rust"],
+            SourceType::Bundle => &["bundle"],
+        }
+    }
+
+    /// As written in `type` of the config
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SourceType::Flake => "flake",
+            SourceType::Devenv => "devenv",
+            SourceType::Cookiecutter => "cookiecutter",
+            SourceType::CargoGenerate => "cargo-generate",
+            SourceType::Bundle => "bundle",
+        }
+    }
+
+    /// Whether the templates are listed and initialized through nix
+    pub fn is_flake(self) -> bool {
+        matches!(self, SourceType::Flake | SourceType::Devenv)
+    }
+}
+
+/// Replace every `from` in `file` with the project name
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct RenameRule {
+    /// Relative to the directory the template is initialized in
+    pub file: PathBuf,
+    pub from: String,
+}
+
+/// Replace every `from` with `to`. `to` may contain `{dir_name}`, `{user}` and `{date}`
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct ReplaceRule {
+    pub from: String,
+    pub to: String,
+}
+
+impl TemplateConfig {
+    /// The override of `uri#name` if there is one, otherwise the one of `name`
+    pub fn template_override(&self, name: &str) -> Option<&TemplateOverride> {
+        self.overrides
+            .get(&format!("{}#{name}", self.uri))
+            .or_else(|| self.overrides.get(name))
+    }
+
+    pub fn boost(&self, template: &str) -> f64 {
+        self.boosts.get(template).copied().unwrap_or(self.boost)
+    }
+
+    pub fn demoted(&self, template: &str) -> bool {
+        self.demote || self.demotes.iter().any(|t| t == template)
+    }
+
+    /// Extra arguments for every nix command run for this flake
+    pub fn nix_args(&self) -> Vec<OsString> {
+        let mut args = crate::nix::args();
+
+        if !self.pure_eval {
+            args.push("--impure".into());
+        }
+
+        if let Some(netrc) = &self.netrc {
+            args.push("--netrc-file".into());
+            args.push(expand_tilde(netrc).into());
+        }
+
+        args
+    }
+
+    /// Extra environment variables for every command run for this flake
+    pub fn envs(&self) -> Vec<(String, String)> {
+        let mut envs = vec![];
+
+        let ssh = match &self.ssh {
+            Some(Ssh::Enabled(true)) => Some("ssh -o BatchMode=yes".to_string()),
+            Some(Ssh::Key(key)) => Some(format!(
+                "ssh -o BatchMode=yes -o IdentitiesOnly=yes -i '{}'",
+                expand_tilde(key).display()
+            )),
+            Some(Ssh::Enabled(false)) | None => None,
+        };
+        if let Some(ssh) = ssh {
+            envs.push(("GIT_SSH_COMMAND".into(), ssh));
+        }
+
+        // explicit ones win over the derived ones
+        envs.extend(self.env.iter().map(|(k, v)| (k.clone(), v.clone())));
+
+        envs
+    }
+}
+
+/// `value` with every `${VAR}` replaced by `var("VAR")`, or removed if that is `None`. A `$`
+/// without a `{` is kept as it is
+pub fn expand_vars(value: &str, var: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start + 2..].find('}') else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        out.push_str(&var(&rest[start + 2..start + 2 + len]).unwrap_or_default());
+        rest = &rest[start + 2 + len + 1..];
+    }
+    out.push_str(rest);
+
+    out
+}
+
+pub fn expand_tilde(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+impl Config {
+    pub fn path() -> Result<PathBuf> {
+        Ok(crate::platform::config_dir()
+            .wrap_err("Config directory  doesn't exit.")?
+            .join("nix-nit/config.toml"))
+    }
+
+    /// With the [`overlay`] merged over it. The overlay alone is enough
+    pub fn load() -> Result<Self> {
+        let config_path = Self::path()?;
+
+        let mut content = if config_path.exists() {
+            std::fs::read_to_string(&config_path)?
+        } else if overlay().is_some() {
+            String::new()
+        } else {
+            return Err(NitError::ConfigNotFound { path: config_path }.into());
+        };
+        let mut config: Self =
+            toml::from_str(&content).map_err(|e| NitError::from_toml(config_path, e))?;
+
+        if let Some(path) = overlay() {
+            let overlay = std::fs::read_to_string(path)?;
+            config.merge(
+                toml::from_str(&overlay).map_err(|e| NitError::from_toml(path.to_path_buf(), e))?,
+            );
+            // the project has a cache of its own, see `cache::file`
+            content.push_str(&overlay);
+        }
+        // fetched by the cache, and a new version of one rebuilds it
+        for catalog in config.catalog.clone() {
+            if let Some(fetched) = crate::catalog::cached(&catalog)? {
+                config.add_catalog(crate::catalog::templates(&catalog, &fetched)?);
+                content.push_str(&fetched);
+            }
+        }
+        config.hash = fnv1a(content.as_bytes());
+
+        Ok(config)
+    }
+
+    /// [`fnv1a`] of the config file and the overlay, `None` if there is neither. Unlike their
+    /// mtimes, it changes with every save, however quick the saves follow each other
+    pub fn content_hash() -> Option<u64> {
+        let files: Vec<Vec<u8>> = std::iter::once(Self::path().ok()?)
+            .chain(overlay().map(Path::to_path_buf))
+            .filter_map(|path| std::fs::read(path).ok())
+            .collect();
+        (!files.is_empty()).then(|| fnv1a(&files.join(&0)))
+    }
+
+    /// `None` if there is no config file and no overlay
+    pub fn load_optional() -> Result<Option<Self>> {
+        if Self::path()?.exists() || overlay().is_some() {
+            Self::load().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// The templates of a catalog, except the ones the config already has an entry for
+    fn add_catalog(&mut self, templates: Vec<TemplateConfig>) {
+        for flake in templates {
+            if self.flake(&flake.uri).is_none() {
+                self.template.push(flake);
+            }
+        }
+    }
+
+    /// The sources of `overlay` replace the ones with the same uri
+    fn merge(&mut self, overlay: Overlay) {
+        for flake in overlay.template {
+            self.template.retain(|t| t.uri != flake.uri);
+            self.template.push(flake);
+        }
+        for bundle in overlay.bundle {
+            self.bundle.retain(|b| b.name != bundle.name);
+            self.bundle.push(bundle);
+        }
+        if overlay.into.is_some() {
+            self.into = overlay.into;
+        }
+    }
+
+    /// Replace the templates with the ones of `[profile.<name>]`, for `--profile`
+    pub fn use_profile(&mut self, name: &str) -> Result<()> {
+        let Some(profile) = self.profile.remove(name) else {
+            return Err(NitError::UnknownProfile {
+                name: name.to_string(),
+                available: self.profile.keys().cloned().collect(),
+            }
+            .into());
+        };
+
+        self.template = profile.template;
+        self.active_profile = Some(name.to_string());
+
+        Ok(())
+    }
+
+    /// Evaluate every flake impurely, for `--impure`
+    pub fn force_impure(&mut self) {
+        for t in &mut self.template {
+            t.pure_eval = false;
+        }
+    }
+
+    /// The config entry the flake `uri` came from
+    pub fn flake(&self, uri: &str) -> Option<&TemplateConfig> {
+        self.template.iter().find(|t| t.uri == uri)
+    }
+}
+
+/// A hash that stays the same across builds, unlike `DefaultHasher`
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlay() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [hooks]
+            post_init = ["global"]
+            [[template]]
+            uri = "github:a/b"
+            [[template]]
+            uri = "github:c/d"
+            tags = ["global"]
+            "#,
+        )
+        .unwrap();
+        let overlay: Overlay = toml::from_str(
+            r#"
+            into = "packages"
+            [[template]]
+            uri = "github:c/d"
+            tags = ["project"]
+            [[template]]
+            uri = "github:e/f"
+            "#,
+        )
+        .unwrap();
+
+        config.merge(overlay);
+
+        let uris: Vec<_> = config.template.iter().map(|t| t.uri.as_str()).collect();
+        assert_eq!(uris, ["github:a/b", "github:c/d", "github:e/f"]);
+        assert_eq!(config.flake("github:c/d").unwrap().tags, ["project"]);
+        assert_eq!(config.hooks.post_init, ["global"]);
+        assert_eq!(config.into, Some(PathBuf::from("packages")));
+        assert!(toml::from_str::<Overlay>("[policy]\nallow = []").is_err());
+        assert!(toml::from_str::<Overlay>("[hooks]\npost_init = [\"rm -rf ~\"]").is_err());
+    }
+    #[test]
+    fn expanding_vars() {
+        let var = |name: &str| (name == "HOME").then(|| "/home/me".to_string());
+
+        assert_eq!(expand_vars("${HOME}/.ssh", var), "/home/me/.ssh");
+        assert_eq!(expand_vars("a${UNSET}b", var), "ab");
+        assert_eq!(expand_vars("$HOME ${HOME", var), "$HOME ${HOME");
+    }
+    #[test]
+    fn catalogs() {
+        let one: Config = toml::from_str(r#"catalog = "https://a/t.toml""#).unwrap();
+        assert_eq!(
+            one.catalog,
+            [CatalogSource {
+                url: "https://a/t.toml".into(),
+                sha256: None
+            }]
+        );
+
+        let sha256 = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        let many: Config = toml::from_str(&format!(
+            r#"catalog = ["https://a/t.toml", {{ url = "https://b/t.toml", sha256 = "{sha256}" }}]"#
+        ))
+        .unwrap();
+        assert_eq!(many.catalog.len(), 2);
+        assert_eq!(many.catalog[1].sha256.as_deref(), Some(sha256));
+        assert!(
+            toml::from_str::<Config>(r#"catalog = { url = "https://b/t.toml", sha256 = "ab" }"#)
+                .is_err()
+        );
+    }
+    #[test]
+    fn archive_checksums() {
+        let archive = |sha256: &str| {
+            toml::from_str::<Config>(&format!(
+                "[[sources.archive]]\nurl = \"https://a/t.tar.gz\"\nsha256 = \"{sha256}\""
+            ))
+        };
+
+        assert!(archive(&"0".repeat(64)).is_ok());
+        assert!(archive(&"0".repeat(63)).is_err());
+        assert!(archive(&format!("../../{}", "0".repeat(58))).is_err());
+        assert!(archive(&"g".repeat(64)).is_err());
+    }
+}
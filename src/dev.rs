@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use ltrait::color_eyre::{Result, eyre::ensure};
+
+use crate::locale::tr;
+use crate::nix::{self, NixBackend};
+use crate::preview::Changes;
+use crate::{cache, cancel};
+
+/// What applying one template of the watched flake gave, compared to the run before
+struct Applied {
+    name: String,
+    warnings: Vec<String>,
+    files: usize,
+    /// `None` on the first run of the template, when there is nothing to compare to
+    diff: Option<Diff>,
+}
+
+struct Diff {
+    changes: Changes,
+    removed: Vec<PathBuf>,
+    /// The new files and the diffs of the modified ones, see [`Changes::render`]
+    rendered: String,
+}
+
+/// `nit dev`: whenever a file of the template flake in `dir` changes, apply each of its templates
+/// into a throwaway directory and report what changed in the result since the last run
+pub async fn run(nix: &impl NixBackend, dir: &Path) -> Result<()> {
+    let dir = std::path::absolute(dir)?;
+    ensure!(
+        dir.join("flake.nix").is_file(),
+        tr("dev-no-flake", &[("dir", &dir.display())])
+    );
+    let uri = format!("path:{}", dir.display());
+    let nix_args = nix::args();
+
+    let work = std::env::temp_dir().join(format!("nit-dev-{}", std::process::id()));
+    // removed on Ctrl-C, the only way out
+    cancel::writing(&work);
+    std::fs::create_dir_all(&work)?;
+    eprintln!(
+        "{}",
+        tr(
+            "dev-watching",
+            &[("dir", &dir.display()), ("work", &work.display())]
+        )
+    );
+
+    let mut seen = None;
+    loop {
+        if seen.as_ref() == Some(&fingerprint(&dir)) {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            continue;
+        }
+
+        match apply(nix, &uri, &nix_args, &work).await {
+            Ok(applied) => print(&applied),
+            Err(e) => eprintln!("{e}"),
+        }
+        // after applying, as nix may have written a flake.lock
+        seen = Some(fingerprint(&dir));
+        eprintln!("{}", tr("dev-waiting", &[]));
+    }
+}
+
+/// Every file under `dir` outside of `.git`, with its size and modification time
+fn fingerprint(dir: &Path) -> BTreeMap<PathBuf, (u64, Option<SystemTime>)> {
+    let mut files = BTreeMap::new();
+    let mut queue = vec![dir.to_path_buf()];
+
+    while let Some(current) = queue.pop() {
+        let Ok(entries) = std::fs::read_dir(&current) else {
+            continue;
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            let Ok(metadata) = entry.path().symlink_metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                if entry.file_name() != ".git" {
+                    queue.push(entry.path());
+                }
+                continue;
+            }
+            files.insert(entry.path(), (metadata.len(), metadata.modified().ok()));
+        }
+    }
+
+    files
+}
+
+/// Apply every template of `uri` into `work/next`, compare each with `work/last` and make it the
+/// new `work/last`
+async fn apply(
+    nix: &impl NixBackend,
+    uri: &str,
+    nix_args: &[OsString],
+    work: &Path,
+) -> Result<Vec<Applied>> {
+    let show = nix.flake_show(uri, nix_args, &[]).await?;
+    let templates = cache::parse_flake_show(&show, uri)?;
+    ensure!(
+        !templates.is_empty(),
+        tr("edit-no-templates", &[("uri", &uri)])
+    );
+
+    let last = work.join("last");
+    let next = work.join("next");
+    if next.exists() {
+        std::fs::remove_dir_all(&next)?;
+    }
+
+    let mut applied = vec![];
+    for t in &templates {
+        let out = next.join(&t.name);
+        std::fs::create_dir_all(&out)?;
+        let warnings = nix.flake_init(t.uri(), &out, nix_args, &[])?;
+
+        let before = last.join(&t.name);
+        let diff = if before.is_dir() {
+            let changes = Changes::new(&out, &before)?;
+            Some(Diff {
+                rendered: changes.render(&out, &before)?,
+                removed: Changes::new(&before, &out)?.new,
+                changes,
+            })
+        } else {
+            None
+        };
+
+        applied.push(Applied {
+            name: t.name.clone(),
+            warnings,
+            files: fingerprint(&out).len(),
+            diff,
+        });
+    }
+
+    if last.exists() {
+        std::fs::remove_dir_all(&last)?;
+    }
+    std::fs::rename(&next, &last)?;
+
+    Ok(applied)
+}
+
+fn print(applied: &[Applied]) {
+    for a in applied {
+        for warning in &a.warnings {
+            println!("{}: {}", a.name, tr("warning", &[("message", warning)]));
+        }
+
+        let Some(diff) = &a.diff else {
+            println!(
+                "{}",
+                tr("dev-files", &[("name", &a.name), ("count", &a.files)])
+            );
+            continue;
+        };
+        if diff.changes.new.is_empty()
+            && diff.changes.modified.is_empty()
+            && diff.removed.is_empty()
+        {
+            println!("{}", tr("dev-unchanged", &[("name", &a.name)]));
+            continue;
+        }
+
+        println!(
+            "{}",
+            tr(
+                "dev-changed",
+                &[
+                    ("name", &a.name),
+                    ("new", &diff.changes.new.len()),
+                    ("modified", &diff.changes.modified.len()),
+                    ("removed", &diff.removed.len()),
+                ]
+            )
+        );
+        print!("{}", diff.rendered);
+        for file in &diff.removed {
+            println!("{}", tr("dev-removed-file", &[("path", &file.display())]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nix::mock::Mock;
+
+    #[tokio::test]
+    async fn apply_compares_with_the_last_run() {
+        let work = std::env::temp_dir().join(format!("nit-dev-test-{}", std::process::id()));
+        let mut nix = Mock::default();
+        nix.show.insert(
+            "path:/t".into(),
+            r#"{"templates":{"rust":{"description":"Rust"}}}"#.into(),
+        );
+        nix.files.insert(
+            "path:/t#rust".into(),
+            vec![("a".into(), "1".into()), ("b".into(), "1".into())],
+        );
+
+        let first = apply(&nix, "path:/t", &[], &work).await.unwrap();
+        assert_eq!(first[0].files, 2);
+        assert!(first[0].diff.is_none());
+
+        nix.files.insert(
+            "path:/t#rust".into(),
+            vec![("a".into(), "2".into()), ("c".into(), "1".into())],
+        );
+        let second = apply(&nix, "path:/t", &[], &work).await.unwrap();
+        let diff = second[0].diff.as_ref().unwrap();
+        assert_eq!(diff.changes.new, [PathBuf::from("c")]);
+        assert_eq!(diff.changes.modified, [PathBuf::from("a")]);
+        assert_eq!(diff.removed, [PathBuf::from("b")]);
+        assert!(!work.join("next").exists());
+
+        std::fs::remove_dir_all(&work).unwrap();
+    }
+}
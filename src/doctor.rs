@@ -0,0 +1,268 @@
+use std::io::IsTerminal as _;
+
+use ltrait::color_eyre::{Result, eyre::bail};
+
+use crate::cache::{self, CacheStore as _};
+use crate::config::Config;
+use crate::locale::tr;
+
+/// The outcome of one check. `Err` holds what went wrong and how to fix it
+type Check = std::result::Result<String, (String, String)>;
+
+/// Check everything nit depends on and print a PASS or FAIL line for each
+pub fn run(profile: Option<&str>) -> Result<()> {
+    let mut failures = 0;
+    let mut report = |name: &str, check: Check| match check {
+        Ok(detail) => println!(
+            "{}",
+            tr("doctor-pass", &[("name", &name), ("detail", &detail)])
+        ),
+        Err((detail, fix)) => {
+            failures += 1;
+            println!(
+                "{}",
+                tr("doctor-fail", &[("name", &name), ("detail", &detail)])
+            );
+            println!("{}", tr("doctor-fix", &[("fix", &fix)]));
+        }
+    };
+
+    let nix = nix_version();
+    let has_nix = nix.is_ok();
+    report("nix", nix);
+    if has_nix {
+        report("flakes", flakes_enabled());
+    }
+
+    let config = Config::load_optional().and_then(|mut config| {
+        if let (Some(config), Some(profile)) = (&mut config, profile) {
+            config.use_profile(profile)?;
+        }
+        Ok(config)
+    });
+    match &config {
+        Ok(Some(config)) => {
+            report(
+                "config",
+                Ok(tr(
+                    "doctor-config-sources",
+                    &[("count", &config.template.len())],
+                )),
+            );
+            if has_nix {
+                for flake in &config.template {
+                    let check = match config.policy.check(&flake.uri) {
+                        Ok(()) => resolve(flake),
+                        Err(e) => Err((e.to_string(), e.hint())),
+                    };
+                    report(&flake.uri, check);
+                }
+            }
+        }
+        Ok(None) => report(
+            "config",
+            Err((
+                tr("doctor-config-missing", &[]),
+                tr("doctor-config-missing-fix", &[]),
+            )),
+        ),
+        Err(e) => report(
+            "config",
+            Err((format!("{e}"), tr("doctor-config-broken-fix", &[]))),
+        ),
+    }
+
+    report(
+        "cache",
+        cache(profile, config.as_ref().ok().and_then(Option::as_ref)),
+    );
+    report("terminal", terminal());
+
+    if failures > 0 {
+        bail!(tr("doctor-failed", &[("count", &failures)]));
+    }
+
+    Ok(())
+}
+
+fn nix_version() -> Check {
+    if let Some(backend) = crate::nix::backend() {
+        return Ok(backend.to_string());
+    }
+
+    match crate::nix::command().arg("--version").output() {
+        Ok(out) if out.status.success() => Err((
+            tr(
+                "doctor-nix-unparsable",
+                &[("output", &String::from_utf8_lossy(&out.stdout).trim())],
+            ),
+            tr("doctor-nix-unparsable-fix", &[]),
+        )),
+        Ok(out) => Err((
+            String::from_utf8_lossy(&out.stderr).trim().to_string(),
+            tr("doctor-nix-broken-fix", &[]),
+        )),
+        Err(e) => Err((
+            tr("doctor-nix-missing", &[("error", &e)]),
+            tr("doctor-nix-missing-fix", &[]),
+        )),
+    }
+}
+
+fn flakes_enabled() -> Check {
+    // `nix show-config` was renamed to `nix config show`
+    let features = [
+        &["config", "show", "experimental-features"][..],
+        &["show-config", "experimental-features"],
+    ]
+    .iter()
+    .find_map(|args| {
+        let out = crate::nix::command().args(*args).output().ok()?;
+        out.status
+            .success()
+            .then(|| String::from_utf8_lossy(&out.stdout).into_owned())
+    });
+
+    let Some(features) = features else {
+        return Err((
+            tr("doctor-nix-config", &[]),
+            tr("doctor-nix-config-fix", &[]),
+        ));
+    };
+
+    let enabled: Vec<_> = features
+        .lines()
+        .filter_map(|l| match l.split_once('=') {
+            Some((key, value)) if key.trim() == "experimental-features" => Some(value),
+            Some(_) => None,
+            None => Some(l),
+        })
+        .flat_map(str::split_whitespace)
+        .collect();
+
+    let missing: Vec<_> = ["nix-command", "flakes"]
+        .into_iter()
+        .filter(|f| !enabled.contains(f))
+        .collect();
+
+    if missing.is_empty() {
+        Ok(tr("doctor-flakes-enabled", &[]))
+    } else if !crate::nix::args().is_empty() {
+        Ok(tr(
+            "doctor-flakes-by-nit",
+            &[("features", &missing.join(" and "))],
+        ))
+    } else {
+        Err((
+            tr(
+                "doctor-flakes-disabled",
+                &[("features", &missing.join(" and "))],
+            ),
+            tr("doctor-flakes-disabled-fix", &[]),
+        ))
+    }
+}
+
+fn resolve(flake: &crate::config::TemplateConfig) -> Check {
+    let out = if flake.source_type.is_flake() {
+        crate::nix::command()
+            .args(["flake", "metadata", "--json"])
+            .arg(&flake.uri)
+            .args(flake.nix_args())
+            .envs(flake.envs())
+            .output()
+    } else {
+        crate::platform::command("git")
+            .args(["ls-remote", "--exit-code"])
+            .arg(&flake.uri)
+            .arg("HEAD")
+            .envs(flake.envs())
+            .output()
+    };
+
+    match out {
+        Ok(out) if out.status.success() => Ok(tr("doctor-resolvable", &[])),
+        Ok(out) => Err((
+            String::from_utf8_lossy(&out.stderr)
+                .lines()
+                .last()
+                .unwrap_or("failed")
+                .to_string(),
+            tr("doctor-unresolvable-fix", &[]),
+        )),
+        Err(e) => Err((format!("{e}"), tr("doctor-resolve-fix", &[]))),
+    }
+}
+
+fn cache(profile: Option<&str>, config: Option<&Config>) -> Check {
+    let cache = cache::store(profile, config)
+        .map_err(|e| (format!("{e}"), tr("doctor-cache-dir-fix", &[])))?;
+    let dir = cache
+        .path()
+        .parent()
+        .map(|p| p.to_path_buf())
+        .unwrap_or_default();
+
+    if cache.exists() {
+        std::fs::read(cache.path()).map_err(|e| {
+            (
+                tr(
+                    "doctor-cache-unreadable",
+                    &[("path", &cache.path().display()), ("error", &e)],
+                ),
+                tr(
+                    "doctor-cache-unreadable-fix",
+                    &[("path", &cache.path().display())],
+                ),
+            )
+        })?;
+    }
+
+    let probe = dir.join(".doctor");
+    std::fs::create_dir_all(&dir)
+        .and_then(|_| std::fs::write(&probe, ""))
+        .and_then(|_| std::fs::remove_file(&probe))
+        .map_err(|e| {
+            (
+                tr(
+                    "doctor-cache-unwritable",
+                    &[("path", &dir.display()), ("error", &e)],
+                ),
+                tr("doctor-cache-unwritable-fix", &[("path", &dir.display())]),
+            )
+        })?;
+
+    Ok(if cache.exists() {
+        tr("doctor-cache-ok", &[("path", &cache.path().display())])
+    } else {
+        tr("doctor-cache-empty", &[("path", &dir.display())])
+    })
+}
+
+fn terminal() -> Check {
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        return Err((
+            tr("doctor-no-terminal", &[]),
+            tr("doctor-no-terminal-fix", &[]),
+        ));
+    }
+
+    let term = std::env::var("TERM").unwrap_or_default();
+    if (term.is_empty() && !cfg!(windows)) || term == "dumb" {
+        return Err((
+            tr("doctor-term", &[("term", &term)]),
+            tr("doctor-term-fix", &[]),
+        ));
+    }
+
+    match crossterm::terminal::size() {
+        Ok((cols, rows)) => Ok(format!(
+            "{term}, {cols}x{rows}, {}",
+            crate::color::support()
+        )),
+        Err(e) => Err((
+            tr("doctor-terminal-size", &[("error", &e)]),
+            tr("doctor-terminal-size-fix", &[]),
+        )),
+    }
+}
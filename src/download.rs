@@ -0,0 +1,319 @@
+use std::io::Read as _;
+use std::path::Path;
+
+use ltrait::color_eyre::{Result, eyre::ensure};
+
+/// Run `curl` with `args`, failing on HTTP errors. The body is returned unless `-o` is given
+pub fn curl(args: &[&str]) -> Result<Vec<u8>> {
+    let output = crate::platform::command("curl")
+        .args(["-fsSL", "-H", "User-Agent: nit"])
+        .args(args)
+        .output()?;
+
+    ensure!(
+        output.status.success(),
+        "failed to run curl {}, err: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr),
+    );
+
+    Ok(output.stdout)
+}
+
+/// The sha256 of the file at `path` in hex
+pub fn sha256(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::default();
+    feed(&mut hasher, path)?;
+
+    Ok(hasher.finish().iter().map(|b| format!("{b:02x}")).collect())
+}
+
+/// The sha256 of `path` serialized as a NAR in hex, what nix calls its narHash and
+/// `builtins.hashPath` computes
+pub fn nar_hash(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::default();
+    nar_str(&mut hasher, b"nix-archive-1");
+    nar(&mut hasher, path)?;
+
+    Ok(hasher.finish().iter().map(|b| format!("{b:02x}")).collect())
+}
+
+fn feed(hasher: &mut Sha256, path: &Path) -> Result<()> {
+    let mut file = std::fs::File::open(path)?;
+    let mut chunk = vec![0; 64 * 1024];
+    loop {
+        match file.read(&mut chunk)? {
+            0 => return Ok(()),
+            read => hasher.update(&chunk[..read]),
+        }
+    }
+}
+
+/// The length, the bytes and zeros up to a multiple of 8
+fn nar_str(hasher: &mut Sha256, bytes: &[u8]) {
+    hasher.update(&(bytes.len() as u64).to_le_bytes());
+    hasher.update(bytes);
+    nar_pad(hasher, bytes.len() as u64);
+}
+
+fn nar_pad(hasher: &mut Sha256, len: u64) {
+    hasher.update(&[0; 8][..(8 - len as usize % 8) % 8]);
+}
+
+fn nar(hasher: &mut Sha256, path: &Path) -> Result<()> {
+    let metadata = path.symlink_metadata()?;
+    nar_str(hasher, b"(");
+    nar_str(hasher, b"type");
+
+    if metadata.is_symlink() {
+        nar_str(hasher, b"symlink");
+        nar_str(hasher, b"target");
+        nar_str(
+            hasher,
+            std::fs::read_link(path)?.as_os_str().as_encoded_bytes(),
+        );
+    } else if metadata.is_dir() {
+        nar_str(hasher, b"directory");
+        // sorted by their bytes, like nix does
+        let mut names = std::fs::read_dir(path)?
+            .map(|entry| Ok(entry?.file_name()))
+            .collect::<Result<Vec<_>>>()?;
+        names.sort_by(|a, b| a.as_encoded_bytes().cmp(b.as_encoded_bytes()));
+        for name in names {
+            nar_str(hasher, b"entry");
+            nar_str(hasher, b"(");
+            nar_str(hasher, b"name");
+            nar_str(hasher, name.as_encoded_bytes());
+            nar_str(hasher, b"node");
+            nar(hasher, &path.join(name))?;
+            nar_str(hasher, b")");
+        }
+    } else {
+        nar_str(hasher, b"regular");
+        #[cfg(unix)]
+        if std::os::unix::fs::PermissionsExt::mode(&metadata.permissions()) & 0o100 != 0 {
+            nar_str(hasher, b"executable");
+            nar_str(hasher, b"");
+        }
+        nar_str(hasher, b"contents");
+        hasher.update(&metadata.len().to_le_bytes());
+        feed(hasher, path)?;
+        nar_pad(hasher, metadata.len());
+    }
+
+    nar_str(hasher, b")");
+    Ok(())
+}
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// SHA-256 as in FIPS 180-4, fed in pieces of any size
+struct Sha256 {
+    state: [u32; 8],
+    /// The start of the next block
+    pending: Vec<u8>,
+    /// Bytes fed so far
+    len: u64,
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Self {
+            state: [
+                0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+                0x5be0cd19,
+            ],
+            pending: Vec::with_capacity(64),
+            len: 0,
+        }
+    }
+}
+
+impl Sha256 {
+    fn update(&mut self, mut data: &[u8]) {
+        self.len += data.len() as u64;
+
+        if !self.pending.is_empty() {
+            let take = data.len().min(64 - self.pending.len());
+            self.pending.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.pending.len() < 64 {
+                return;
+            }
+            let block = std::mem::take(&mut self.pending);
+            self.compress(&block);
+        }
+
+        let mut blocks = data.chunks_exact(64);
+        for block in &mut blocks {
+            self.compress(block);
+        }
+        self.pending.extend_from_slice(blocks.remainder());
+    }
+
+    fn finish(mut self) -> [u8; 32] {
+        let bits = self.len * 8;
+        // a 1 bit, zeros up to 8 bytes before the end of a block, and the length in bits
+        let mut padding = vec![0x80];
+        padding.resize(1 + (64 + 55 - self.pending.len()) % 64, 0);
+        padding.extend_from_slice(&bits.to_be_bytes());
+        self.update(&padding);
+
+        let mut digest = [0; 32];
+        for (bytes, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    fn compress(&mut self, block: &[u8]) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(data: &[u8], piece: usize) -> String {
+        let mut hasher = Sha256::default();
+        for chunk in data.chunks(piece) {
+            hasher.update(chunk);
+        }
+        hasher.finish().iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    #[test]
+    fn nar_hashes() {
+        let dir = std::env::temp_dir().join(format!("nit-nar-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("sub/b"), "hello").unwrap();
+        std::fs::write(dir.join("a"), "").unwrap();
+
+        // written out by hand from the NAR format, entries in byte order
+        let mut nar = vec![];
+        let mut str = |s: &[u8]| {
+            nar.extend((s.len() as u64).to_le_bytes());
+            nar.extend(s);
+            nar.resize(nar.len().next_multiple_of(8), 0);
+        };
+        for s in [
+            &b"nix-archive-1"[..],
+            b"(",
+            b"type",
+            b"directory",
+            b"entry",
+            b"(",
+            b"name",
+            b"a",
+            b"node",
+            b"(",
+            b"type",
+            b"regular",
+            b"contents",
+            b"",
+            b")",
+            b")",
+            b"entry",
+            b"(",
+            b"name",
+            b"sub",
+            b"node",
+            b"(",
+            b"type",
+            b"directory",
+            b"entry",
+            b"(",
+            b"name",
+            b"b",
+            b"node",
+            b"(",
+            b"type",
+            b"regular",
+            b"contents",
+            b"hello",
+            b")",
+            b")",
+            b")",
+            b")",
+            b")",
+        ] {
+            str(s);
+        }
+
+        assert_eq!(nar_hash(&dir).unwrap(), hex(&nar, 64));
+        std::fs::write(dir.join("sub/b"), "hello!").unwrap();
+        assert_ne!(nar_hash(&dir).unwrap(), hex(&nar, 64));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sha256_vectors() {
+        assert_eq!(
+            hex(b"", 1),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex(b"abc", 1),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let two_blocks = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+        for piece in [1, 7, 56] {
+            assert_eq!(
+                hex(two_blocks, piece),
+                "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+            );
+        }
+        assert_eq!(
+            hex(&[b'a'; 1_000_000], 1000),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+    }
+}
@@ -0,0 +1,284 @@
+use std::io::IsTerminal as _;
+
+use ltrait::color_eyre::{
+    Result,
+    eyre::{bail, ensure},
+};
+
+use crate::cache;
+use crate::config::{Config, Entry, Snippet};
+use crate::error::NitError;
+use crate::locale::tr;
+use crate::nix::{self, NixBackend};
+use crate::prompt;
+
+/// `nit config add`: append a `[[template]]` for `uri` to the config once `nix flake show` found
+/// templates in it. Without `name`, the one suggested from the uri is asked about at a terminal
+pub async fn add(
+    nix: &impl NixBackend,
+    uri: &str,
+    name: Option<String>,
+    tags: &[String],
+) -> Result<()> {
+    let path = Config::path()?;
+    let content = if path.exists() {
+        std::fs::read_to_string(&path)?
+    } else {
+        String::new()
+    };
+    let config: Config =
+        toml::from_str(&content).map_err(|e| NitError::from_toml(path.clone(), e))?;
+    ensure!(
+        config.flake(uri).is_none(),
+        tr("edit-exists", &[("uri", &uri), ("path", &path.display())])
+    );
+    config.policy.check(uri)?;
+
+    let show = nix.flake_show(uri, &nix::args(), &[]).await?;
+    let templates = cache::parse_flake_show(&show, uri)?;
+    if templates.is_empty() {
+        bail!(tr("edit-no-templates", &[("uri", &uri)]));
+    }
+
+    let name = match name {
+        Some(name) => name,
+        None => {
+            let suggested = suggest_name(uri);
+            let answer = if std::io::stdin().is_terminal() {
+                prompt::ask(&tr("prompt-source-name", &[("name", &suggested)]))?
+            } else {
+                String::new()
+            };
+            if answer.is_empty() { suggested } else { answer }
+        }
+    };
+
+    let mut edited = content;
+    if !edited.is_empty() {
+        if !edited.ends_with('\n') {
+            edited.push('\n');
+        }
+        edited.push('\n');
+    }
+    edited.push_str(&toml::to_string(&Snippet {
+        template: vec![Entry {
+            name: &name,
+            uri,
+            tags,
+        }],
+    })?);
+    write(&path, &edited)?;
+
+    println!(
+        "{}",
+        tr(
+            "edit-added",
+            &[
+                ("uri", &uri),
+                ("name", &name),
+                ("count", &templates.len()),
+                ("path", &path.display()),
+            ]
+        )
+    );
+
+    Ok(())
+}
+
+/// `nit config remove`: delete the `[[template]]` of `uri` from the config, leaving the comments
+/// and everything else as they are
+pub fn remove(uri: &str) -> Result<()> {
+    let path = Config::path()?;
+    let content = if path.exists() {
+        std::fs::read_to_string(&path)?
+    } else {
+        String::new()
+    };
+
+    let Some(edited) = without(&content, uri) else {
+        bail!(tr(
+            "edit-missing",
+            &[("uri", &uri), ("path", &path.display())]
+        ));
+    };
+    let before: Config =
+        toml::from_str(&content).map_err(|e| NitError::from_toml(path.clone(), e))?;
+    let after: Config = toml::from_str(&edited)?;
+    // the lines removed were more than the entry, e.g. because of a multi-line string
+    ensure!(
+        after.template.len() + 1 == before.template.len() && after.flake(uri).is_none(),
+        tr(
+            "edit-remove-failed",
+            &[("uri", &uri), ("path", &path.display())]
+        )
+    );
+    write(&path, &edited)?;
+
+    println!(
+        "{}",
+        tr("edit-removed", &[("uri", &uri), ("path", &path.display())])
+    );
+
+    Ok(())
+}
+
+/// Write `content`, after checking it's still a config nit reads
+fn write(path: &std::path::Path, content: &str) -> Result<()> {
+    let _: Config = toml::from_str(content)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, content)?;
+
+    Ok(())
+}
+
+/// The owner of a repository on a forge, or the last part of the path otherwise, e.g. `NixOS` for
+/// `github:NixOS/templates`
+fn suggest_name(uri: &str) -> String {
+    let (scheme, rest) = uri.split_once(':').unwrap_or(("", uri));
+    let path = rest
+        .split(['?', '#'])
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches('/');
+    let mut parts = path.split('/').filter(|p| !p.is_empty());
+
+    let name = match scheme {
+        "github" | "gitlab" | "sourcehut" => parts.next(),
+        _ => parts.next_back(),
+    };
+    name.map(|n| n.trim_start_matches('~').trim_end_matches(".git"))
+        .filter(|n| !n.is_empty())
+        .unwrap_or(uri)
+        .to_string()
+}
+
+/// `content` without the `[[template]]` whose uri is `uri`, `None` if there is none. Its tables
+/// like `[template.override.x]` and the comment right above it go with it
+fn without(content: &str, uri: &str) -> Option<String> {
+    let lines: Vec<_> = content.lines().collect();
+    // a table of the entry, like `[template.override.x]`, or of something else
+    let ends_entry = |line: &str| {
+        let line = line.trim();
+        line == "[[template]]"
+            || line.starts_with('[')
+                && !line.starts_with("[template.")
+                && !line.starts_with("[[template.")
+    };
+    let is_comment = |line: &str| line.trim_start().starts_with('#');
+
+    let mut start = 0;
+    while start < lines.len() {
+        if lines[start].trim() != "[[template]]" {
+            start += 1;
+            continue;
+        }
+
+        let mut end = start + 1;
+        while end < lines.len() && !ends_entry(lines[end]) {
+            end += 1;
+        }
+        while end > start + 1 && (lines[end - 1].trim().is_empty() || is_comment(lines[end - 1])) {
+            end -= 1;
+        }
+
+        let block = lines[start..end].join("\n");
+        let found = toml::from_str::<toml::Table>(&block).ok().is_some_and(|t| {
+            t.get("template")
+                .and_then(|t| t.get(0))
+                .and_then(|t| t.get("uri"))
+                .and_then(|u| u.as_str())
+                == Some(uri)
+        });
+        if !found {
+            start = end;
+            continue;
+        }
+
+        let mut first = start;
+        while first > 0 && is_comment(lines[first - 1]) {
+            first -= 1;
+        }
+        let mut rest = end;
+        while rest < lines.len() && lines[rest].trim().is_empty() {
+            rest += 1;
+        }
+        let mut kept: Vec<_> = lines[..first].to_vec();
+        kept.extend(&lines[rest..]);
+        while kept.last().is_some_and(|l| l.trim().is_empty()) {
+            kept.pop();
+        }
+
+        let mut out = kept.join("\n");
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        return Some(out);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert_eq!(suggest_name("github:NixOS/templates"), "NixOS");
+        assert_eq!(suggest_name("sourcehut:~me/templates?ref=main"), "me");
+        assert_eq!(
+            suggest_name("git+https://example.com/me/templates.git"),
+            "templates"
+        );
+        assert_eq!(suggest_name("templates"), "templates");
+    }
+
+    #[test]
+    fn removing() {
+        let content = r#"readme = true
+
+[[template]]
+uri = "github:a/b"
+
+# the one at work
+[[template]]
+uri = "github:c/d"
+tags = ["work"]
+[template.override.rust]
+icon = "R"
+
+[[template]]
+uri = "github:e/f"
+"#;
+
+        assert_eq!(
+            without(content, "github:c/d").unwrap(),
+            r#"readme = true
+
+[[template]]
+uri = "github:a/b"
+
+[[template]]
+uri = "github:e/f"
+"#
+        );
+        assert_eq!(
+            without(content, "github:e/f").unwrap(),
+            r#"readme = true
+
+[[template]]
+uri = "github:a/b"
+
+# the one at work
+[[template]]
+uri = "github:c/d"
+tags = ["work"]
+[template.override.rust]
+icon = "R"
+"#
+        );
+        assert!(without(content, "github:x/y").is_none());
+    }
+}
@@ -0,0 +1,71 @@
+use std::path::Path;
+
+use ltrait::color_eyre::{
+    Result,
+    eyre::{bail, ensure},
+};
+
+use crate::locale::tr;
+use crate::{Template, config::Config};
+
+/// Copy the files of `t` into `dir` as they are in the nix store, without `nix flake init`
+pub fn run(t: &Template, dir: &Path, config: Option<&Config>) -> Result<()> {
+    ensure!(
+        t.flake_info.source_type.is_flake(),
+        tr(
+            "eject-not-a-flake",
+            &[
+                ("uri", &t.uri()),
+                ("type", &format!("{:?}", t.flake_info.source_type))
+            ]
+        )
+    );
+
+    if let Some(config) = config {
+        config.policy.check(&t.flake_info.uri)?;
+    }
+    let flake_config = config.and_then(|c| c.flake(t.flake_info.config_uri()));
+    let (nix_args, envs) = flake_config
+        .map(|c| (c.nix_args(), c.envs()))
+        .unwrap_or_else(|| (crate::nix::args(), vec![]));
+
+    let source = crate::nix::template_path(&crate::nix::Subprocess, t, &nix_args, &envs)?;
+    std::fs::create_dir_all(dir)?;
+    copy_dir(&source, dir)?;
+
+    println!(
+        "{}",
+        tr("eject-done", &[("uri", &t.uri()), ("dir", &dir.display())])
+    );
+
+    Ok(())
+}
+
+/// Copy `from` into `to` keeping the modes and symlinks. Existing files are never overwritten
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        let file_type = entry.file_type()?;
+
+        if target.symlink_metadata().is_ok() && !file_type.is_dir() {
+            bail!(tr("eject-exists", &[("path", &target.display())]));
+        }
+
+        if file_type.is_symlink() {
+            crate::platform::symlink(&std::fs::read_link(entry.path())?, &target)?;
+        } else if file_type.is_dir() {
+            if !target.exists() {
+                std::fs::create_dir(&target)?;
+            }
+            copy_dir(&entry.path(), &target)?;
+            // read-only directories can only be filled before their mode is copied
+            std::fs::set_permissions(&target, entry.metadata()?.permissions())?;
+        } else {
+            // copies the mode too
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+
+    Ok(())
+}
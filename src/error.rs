@@ -0,0 +1,213 @@
+use std::ops::Range;
+use std::path::PathBuf;
+
+use ltrait::color_eyre::{Report, Section as _};
+use serde::Serialize;
+
+use crate::locale::tr;
+
+/// The failures users can do something about. Everything else stays a plain eyre report
+#[derive(Debug, thiserror::Error)]
+pub enum NitError {
+    ConfigNotFound {
+        path: PathBuf,
+    },
+    ConfigParse {
+        path: PathBuf,
+        message: String,
+        /// Byte range of the offending part of the file
+        span: Option<Range<usize>>,
+    },
+    NixFailed {
+        cmd: String,
+        stderr: String,
+    },
+    CacheCorrupt {
+        path: PathBuf,
+        message: String,
+    },
+    NoSelection {
+        query: String,
+    },
+    UnknownTemplate {
+        query: String,
+        /// The closest `uri#name`s, closest first
+        suggestions: Vec<String>,
+    },
+    NoHistory,
+    UnknownProfile {
+        name: String,
+        available: Vec<String>,
+    },
+    InvalidFilter {
+        filter: String,
+        message: String,
+    },
+    /// By `[policy]`. `rule` is the `deny` pattern, `None` when it isn't in `allow`
+    Denied {
+        uri: String,
+        rule: Option<String>,
+    },
+    /// The narHash of the files of the template differs from the one in the cache
+    Changed {
+        uri: String,
+        cached: String,
+        current: String,
+    },
+}
+
+impl NitError {
+    /// Stable identifier for scripts, printed with `--json`
+    pub fn code(&self) -> &'static str {
+        match self {
+            NitError::ConfigNotFound { .. } => "config-not-found",
+            NitError::ConfigParse { .. } => "config-parse",
+            NitError::NixFailed { .. } => "nix-failed",
+            NitError::CacheCorrupt { .. } => "cache-corrupt",
+            NitError::NoSelection { .. } => "no-selection",
+            NitError::UnknownTemplate { .. } => "unknown-template",
+            NitError::NoHistory => "no-history",
+            NitError::UnknownProfile { .. } => "unknown-profile",
+            NitError::InvalidFilter { .. } => "invalid-filter",
+            NitError::Denied { .. } => "denied",
+            NitError::Changed { .. } => "changed",
+        }
+    }
+
+    pub fn hint(&self) -> String {
+        match self {
+            NitError::ConfigNotFound { .. } => tr("hint-config-not-found", &[]),
+            NitError::ConfigParse { .. } => tr("hint-config-parse", &[]),
+            NitError::NixFailed { .. } => tr("hint-nix-failed", &[]),
+            NitError::CacheCorrupt { .. } => tr("hint-cache-corrupt", &[]),
+            NitError::NoSelection { .. } => tr("hint-no-selection", &[]),
+            NitError::UnknownTemplate { suggestions, .. } if !suggestions.is_empty() => tr(
+                "hint-did-you-mean",
+                &[("suggestions", &suggestions.join("\n    "))],
+            ),
+            NitError::UnknownTemplate { .. } => tr("hint-unknown-template", &[]),
+            NitError::NoHistory => tr("hint-no-history", &[]),
+            NitError::UnknownProfile { available, .. } if available.is_empty() => {
+                tr("hint-no-profiles", &[])
+            }
+            NitError::UnknownProfile { available, .. } => {
+                tr("hint-profiles", &[("profiles", &available.join(", "))])
+            }
+            NitError::InvalidFilter { .. } => tr("hint-invalid-filter", &[]),
+            NitError::Denied { .. } => tr("hint-denied", &[]),
+            NitError::Changed { .. } => tr("hint-changed", &[]),
+        }
+    }
+
+    pub fn from_toml(path: PathBuf, e: toml::de::Error) -> Self {
+        NitError::ConfigParse {
+            path,
+            span: e.span(),
+            message: e.to_string(),
+        }
+    }
+}
+
+/// In the language of [`crate::locale`]
+impl std::fmt::Display for NitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            NitError::ConfigNotFound { path } => {
+                tr("error-config-not-found", &[("path", &path.display())])
+            }
+            NitError::ConfigParse { path, message, .. } => tr(
+                "error-config-parse",
+                &[("path", &path.display()), ("message", message)],
+            ),
+            NitError::NixFailed { cmd, stderr } => {
+                tr("error-nix-failed", &[("cmd", cmd), ("stderr", stderr)])
+            }
+            NitError::CacheCorrupt { path, message } => tr(
+                "error-cache-corrupt",
+                &[("path", &path.display()), ("message", message)],
+            ),
+            NitError::NoSelection { query } => tr("error-no-selection", &[("query", query)]),
+            NitError::UnknownTemplate { query, .. } => {
+                tr("error-unknown-template", &[("query", query)])
+            }
+            NitError::NoHistory => tr("error-no-history", &[]),
+            NitError::UnknownProfile { name, .. } => tr("error-unknown-profile", &[("name", name)]),
+            NitError::InvalidFilter { filter, message } => tr(
+                "error-invalid-filter",
+                &[("filter", filter), ("message", message)],
+            ),
+            NitError::Denied {
+                uri,
+                rule: Some(rule),
+            } => tr("error-denied", &[("uri", uri), ("rule", rule)]),
+            NitError::Denied { uri, rule: None } => tr("error-not-allowed", &[("uri", uri)]),
+            NitError::Changed {
+                uri,
+                cached,
+                current,
+            } => tr(
+                "error-changed",
+                &[("uri", uri), ("cached", cached), ("current", current)],
+            ),
+        };
+
+        f.write_str(&message)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct JsonError {
+    code: &'static str,
+    message: String,
+    hint: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    span: Option<Range<usize>>,
+}
+
+/// Add the hint of the [`NitError`] in `report`, if any
+pub fn with_hint(report: Report) -> Report {
+    match report.downcast_ref::<NitError>().map(NitError::hint) {
+        Some(hint) => report.suggestion(hint),
+        None => report,
+    }
+}
+
+/// `error:`, `caused by:` and `hint:` lines on stderr, without colors or a backtrace
+pub fn print_plain(report: &Report) {
+    eprintln!("{}", tr("plain-error", &[("error", report)]));
+    for cause in report.chain().skip(1) {
+        eprintln!("{}", tr("plain-caused-by", &[("cause", &cause)]));
+    }
+    if let Some(hint) = report.downcast_ref::<NitError>().map(NitError::hint) {
+        eprintln!("{}", tr("plain-hint", &[("hint", &hint)]));
+    }
+}
+
+/// `{"error": {"code": ..., "message": ..., "hint": ...}}` on stdout. Reports without a
+/// [`NitError`] get the code `other`
+pub fn print_json(report: &Report) {
+    println!("{}", serde_json::json!({ "error": to_json(report) }));
+}
+
+/// What [`print_json`] prints as `error`
+pub fn to_json(report: &Report) -> serde_json::Value {
+    let error = match report.downcast_ref::<NitError>() {
+        Some(e) => JsonError {
+            code: e.code(),
+            message: e.to_string(),
+            hint: e.hint(),
+            span: match e {
+                NitError::ConfigParse { span, .. } => span.clone(),
+                _ => None,
+            },
+        },
+        None => JsonError {
+            code: "other",
+            message: format!("{report:#}"),
+            hint: String::new(),
+            span: None,
+        },
+    };
+
+    serde_json::json!(error)
+}
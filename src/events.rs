@@ -0,0 +1,162 @@
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use ltrait::color_eyre::Result;
+use serde::Serialize;
+
+/// `--events`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum EventFormat {
+    /// One JSON object per line, `{"time": ..., "event": "fetch-started", ...}`
+    Jsonl,
+}
+
+/// What nit is doing, for GUI wrappers and editor plugins to show progress with
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event<'a> {
+    FetchStarted {
+        uri: &'a str,
+    },
+    /// `error` is `None` if it succeeded
+    FetchFinished {
+        uri: &'a str,
+        templates: usize,
+        error: Option<String>,
+    },
+    CacheWritten {
+        templates: usize,
+    },
+    InitStarted {
+        uri: &'a str,
+        target: &'a Path,
+    },
+    HookRan {
+        command: &'a str,
+        success: bool,
+    },
+    InitFinished {
+        uri: &'a str,
+        target: &'a Path,
+        success: bool,
+    },
+}
+
+#[derive(Serialize)]
+struct Line<'a> {
+    time: chrono::DateTime<chrono::Utc>,
+    #[serde(flatten)]
+    event: Event<'a>,
+}
+
+static SINK: OnceLock<Mutex<Box<dyn std::io::Write + Send>>> = OnceLock::new();
+
+/// Shows the events in nit itself, e.g. the progress of the first fetch
+type Watcher = Box<dyn Fn(&Event<'_>) + Send + Sync>;
+
+static WATCHER: OnceLock<Watcher> = OnceLock::new();
+
+/// Emit the events from now on into `to`, e.g. `/dev/fd/3`, or stderr
+pub fn init(format: EventFormat, to: Option<&Path>) -> Result<()> {
+    let sink: Box<dyn std::io::Write + Send> = match (format, to) {
+        (EventFormat::Jsonl, Some(path)) => Box::new(
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?,
+        ),
+        (EventFormat::Jsonl, None) => Box::new(std::io::stderr()),
+    };
+    let _ = SINK.set(Mutex::new(sink));
+
+    Ok(())
+}
+
+/// Call `f` with every event from now on, whether or not [`init`] was called
+pub fn watch(f: impl Fn(&Event<'_>) + Send + Sync + 'static) {
+    let _ = WATCHER.set(Box::new(f));
+}
+
+/// Write `event` if [`init`] was called. A reader that went away doesn't stop nit
+pub fn emit(event: Event<'_>) {
+    if let Some(watcher) = WATCHER.get() {
+        watcher(&event);
+    }
+    let Some(sink) = SINK.get() else {
+        return;
+    };
+    let Ok(line) = serde_json::to_string(&Line {
+        time: chrono::Utc::now(),
+        event,
+    }) else {
+        return;
+    };
+
+    let mut sink = sink.lock().unwrap();
+    let _ = writeln!(sink, "{line}");
+    let _ = sink.flush();
+}
+
+/// Emits `init-started`, and `init-finished` once dropped. The init only counts as successful if
+/// [`Init::succeeded`] was called, so every early return reports a failure
+pub struct Init {
+    uri: String,
+    target: PathBuf,
+    success: bool,
+}
+
+impl Init {
+    pub fn start(uri: &str, target: &Path) -> Self {
+        emit(Event::InitStarted { uri, target });
+
+        Self {
+            uri: uri.to_string(),
+            target: target.to_path_buf(),
+            success: false,
+        }
+    }
+
+    pub fn succeeded(mut self) {
+        self.success = true;
+    }
+}
+
+impl Drop for Init {
+    fn drop(&mut self) {
+        emit(Event::InitFinished {
+            uri: &self.uri,
+            target: &self.target,
+            success: self.success,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines() {
+        let line = serde_json::to_value(Line {
+            time: chrono::DateTime::from_timestamp(0, 0).unwrap(),
+            event: Event::FetchFinished {
+                uri: "github:x/y",
+                templates: 2,
+                error: None,
+            },
+        })
+        .unwrap();
+
+        assert_eq!(
+            line,
+            serde_json::json!({
+                "time": "1970-01-01T00:00:00Z",
+                "event": "fetch-finished",
+                "uri": "github:x/y",
+                "templates": 2,
+                "error": null,
+            })
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use ltrait::color_eyre::Result;
+use serde::Serialize;
+
+use crate::config::{Config, SourceType};
+use crate::history::Record;
+
+#[derive(Debug, Serialize)]
+struct Snippet<'a> {
+    template: [Entry<'a>; 1],
+}
+
+/// A `[[template]]` with only the templates that were used
+#[derive(Debug, Serialize)]
+struct Entry<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    uri: &'a str,
+    #[serde(rename = "type", skip_serializing_if = "is_flake")]
+    source_type: SourceType,
+    templates: Vec<&'a str>,
+}
+
+fn is_flake(source_type: &SourceType) -> bool {
+    *source_type == SourceType::Flake
+}
+
+#[derive(Debug, Default)]
+struct Usage<'a> {
+    record: Option<&'a Record>,
+    count: usize,
+    last: Option<DateTime<Utc>>,
+    /// By template name
+    templates: BTreeMap<&'a str, usize>,
+}
+
+/// `nit export-config --from-history`: a `[[template]]` for every flake that was initialized from
+/// successfully and not undone, most used first, with only the templates that were used. The
+/// sources of `config` that never were are listed in a comment at the end, to trim it
+pub fn from_history(records: &[Record], config: Option<&Config>) -> Result<String> {
+    let mut flakes = BTreeMap::<&str, Usage>::new();
+    for r in records.iter().filter(|r| r.success && !r.undone) {
+        // a bundle is in the config as it is, not as a flake
+        if r.template.flake_info.source_type == SourceType::Bundle {
+            continue;
+        }
+
+        let usage = flakes
+            .entry(r.template.flake_info.config_uri())
+            .or_default();
+        usage.record = Some(r);
+        usage.count += 1;
+        usage.last = usage.last.max(Some(r.timestamp));
+        *usage.templates.entry(&r.template.name).or_default() += 1;
+    }
+
+    let mut flakes: Vec<_> = flakes.into_iter().collect();
+    flakes.sort_by_key(|(_, usage)| std::cmp::Reverse(usage.count));
+
+    let mut out = String::new();
+    for (uri, usage) in &flakes {
+        let Some(record) = usage.record else {
+            continue;
+        };
+        let mut templates: Vec<_> = usage.templates.iter().collect();
+        templates.sort_by_key(|(_, count)| std::cmp::Reverse(**count));
+
+        if let Some(last) = usage.last {
+            out.push_str(&format!(
+                "# {} inits, the last on {}\n",
+                usage.count,
+                last.format("%Y-%m-%d")
+            ));
+        }
+        out.push_str(&toml::to_string(&Snippet {
+            template: [Entry {
+                name: record.template.flake_info.name.as_deref(),
+                uri,
+                source_type: record.template.flake_info.source_type,
+                templates: templates.into_iter().map(|(name, _)| *name).collect(),
+            }],
+        })?);
+        out.push('\n');
+    }
+
+    let unused: Vec<_> = config
+        .map(|c| &c.template[..])
+        .unwrap_or_default()
+        .iter()
+        .filter(|t| !flakes.iter().any(|(uri, _)| *uri == t.uri))
+        .collect();
+    if !unused.is_empty() {
+        out.push_str("# never initialized from:\n");
+        for t in unused {
+            out.push_str(&format!("# {}\n", t.uri));
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::parse_flake_show;
+
+    #[test]
+    fn snippet() {
+        let templates = parse_flake_show(
+            br#"{"templates":{"go":{"description":""},"rust":{"description":""}}}"#,
+            "github:x/y",
+        )
+        .unwrap();
+        let record = |i: usize, success| Record {
+            template: templates[i].clone(),
+            revision: None,
+            target: "/tmp".into(),
+            timestamp: DateTime::from_timestamp(86400 * i as i64, 0).unwrap(),
+            success,
+            created: vec![],
+            undone: false,
+        };
+        let config: Config = toml::from_str(
+            r#"
+            [[template]]
+            uri = "github:x/y"
+            [[template]]
+            uri = "github:a/b"
+            "#,
+        )
+        .unwrap();
+
+        let out = from_history(
+            &[
+                record(1, true),
+                record(1, true),
+                record(0, true),
+                record(0, false),
+            ],
+            Some(&config),
+        )
+        .unwrap();
+        assert_eq!(
+            out,
+            r#"# 3 inits, the last on 1970-01-02
+[[template]]
+uri = "github:x/y"
+templates = ["rust", "go"]
+
+# never initialized from:
+# github:a/b
+"#
+        );
+    }
+}
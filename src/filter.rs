@@ -0,0 +1,290 @@
+use std::str::FromStr;
+
+use crate::Template;
+use crate::error::NitError;
+
+/// `--filter`, e.g. `flake == "github:NixOS/templates" && (tag == "rust" || name ~ "rust")`.
+/// `==` and `!=` compare whole values, `~` looks for a substring. A `tag` comparison holds when
+/// one of the tags matches, and `tag != ...` when none does
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Compare { field: Field, op: Op, value: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Name,
+    Flake,
+    Uri,
+    Tag,
+    Description,
+    Type,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Contains,
+}
+
+impl Filter {
+    pub fn matches(&self, t: &Template) -> bool {
+        match self {
+            Filter::And(a, b) => a.matches(t) && b.matches(t),
+            Filter::Or(a, b) => a.matches(t) || b.matches(t),
+            Filter::Not(f) => !f.matches(t),
+            Filter::Compare {
+                field: Field::Tag,
+                op: Op::Ne,
+                value,
+            } => !t.tags.contains(value),
+            Filter::Compare { field, op, value } => {
+                let compare = |actual: &str| match op {
+                    Op::Eq => actual == value,
+                    Op::Ne => actual != value,
+                    Op::Contains => actual.contains(value.as_str()),
+                };
+
+                match field {
+                    Field::Name => compare(&t.name),
+                    Field::Flake => compare(&t.flake_info.uri),
+                    Field::Uri => compare(t.uri()),
+                    Field::Tag => t.tags.iter().any(|tag| compare(tag)),
+                    Field::Description => compare(&t.description),
+                    Field::Type => compare(t.flake_info.source_type.as_str()),
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = NitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |message: String| NitError::InvalidFilter {
+            filter: s.to_string(),
+            message,
+        };
+
+        let tokens = tokenize(s).map_err(error)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let filter = parser.or().map_err(error)?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(filter),
+            Some(token) => Err(error(format!("unexpected {token:?}"))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Eq,
+    Ne,
+    Contains,
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '~' => Token::Contains,
+            '=' if chars.next_if_eq(&'=').is_some() => Token::Eq,
+            '!' if chars.next_if_eq(&'=').is_some() => Token::Ne,
+            '!' => Token::Not,
+            '&' if chars.next_if_eq(&'&').is_some() => Token::And,
+            '|' if chars.next_if_eq(&'|').is_some() => Token::Or,
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped) => value.push(escaped),
+                            None => return Err("unterminated string".into()),
+                        },
+                        Some(c) => value.push(c),
+                        None => return Err("unterminated string".into()),
+                    }
+                }
+                Token::Str(value)
+            }
+            c if c.is_ascii_alphabetic() => {
+                let mut ident = c.to_string();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    ident.push(c);
+                }
+                Token::Ident(ident)
+            }
+            c => return Err(format!("unexpected `{c}`")),
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+/// Precedence from low to high: `||`, `&&`, `!`
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let matched = self.tokens.get(self.pos) == Some(token);
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn or(&mut self) -> Result<Filter, String> {
+        let mut filter = self.and()?;
+        while self.eat(&Token::Or) {
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<Filter, String> {
+        let mut filter = self.unary()?;
+        while self.eat(&Token::And) {
+            filter = Filter::And(Box::new(filter), Box::new(self.unary()?));
+        }
+        Ok(filter)
+    }
+
+    fn unary(&mut self) -> Result<Filter, String> {
+        match self.next() {
+            Some(Token::Not) => Ok(Filter::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let filter = self.or()?;
+                if !self.eat(&Token::Close) {
+                    return Err("missing `)`".into());
+                }
+                Ok(filter)
+            }
+            Some(Token::Ident(ident)) => {
+                let field = match ident.as_str() {
+                    "name" => Field::Name,
+                    "flake" => Field::Flake,
+                    "uri" => Field::Uri,
+                    "tag" => Field::Tag,
+                    "description" => Field::Description,
+                    "type" => Field::Type,
+                    _ => return Err(format!("unknown field `{ident}`")),
+                };
+                let op = match self.next() {
+                    Some(Token::Eq) => Op::Eq,
+                    Some(Token::Ne) => Op::Ne,
+                    Some(Token::Contains) => Op::Contains,
+                    _ => return Err(format!("expected `==`, `!=` or `~` after `{ident}`")),
+                };
+                let Some(Token::Str(value)) = self.next() else {
+                    return Err(format!("expected a quoted string after `{ident}`"));
+                };
+
+                Ok(Filter::Compare { field, op, value })
+            }
+            Some(token) => Err(format!("unexpected {token:?}")),
+            None => Err("unexpected end".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(name: &str, uri: &str, tags: &[&str]) -> Template {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "flake_info": { "name": null, "uri": uri },
+            "description": "",
+            "tags": tags,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn precedence() {
+        let filter: Filter = r#"name == "a" || name == "b" && tag == "x""#.parse().unwrap();
+        assert!(matches!(filter, Filter::Or(..)));
+
+        let filter: Filter = r#"(name == "a" || name == "b") && tag == "x""#.parse().unwrap();
+        assert!(matches!(filter, Filter::And(..)));
+    }
+
+    #[test]
+    fn matches() {
+        let rust = template("rust", "github:NixOS/templates", &["rust", "devenv"]);
+        let go = template("go", "github:NixOS/templates", &[]);
+
+        let filter: Filter = r#"flake == "github:NixOS/templates" && tag == "rust""#
+            .parse()
+            .unwrap();
+        assert!(filter.matches(&rust));
+        assert!(!filter.matches(&go));
+
+        let filter: Filter = r#"tag != "rust" && !(name ~ "ru")"#.parse().unwrap();
+        assert!(!filter.matches(&rust));
+        assert!(filter.matches(&go));
+
+        let filter: Filter = r#"uri == "github:NixOS/templates#go" && type == "flake""#
+            .parse()
+            .unwrap();
+        assert!(filter.matches(&go));
+    }
+
+    #[test]
+    fn escapes() {
+        let filter: Filter = r#"description ~ "say \"hi\"""#.parse().unwrap();
+        assert_eq!(
+            filter,
+            Filter::Compare {
+                field: Field::Description,
+                op: Op::Contains,
+                value: r#"say "hi""#.into(),
+            }
+        );
+    }
+
+    #[test]
+    fn errors() {
+        for invalid in [
+            "",
+            "name",
+            r#"name = "a""#,
+            r#"nam == "a""#,
+            r#"name == a"#,
+            r#"name == "a"#,
+            r#"(name == "a""#,
+            r#"name == "a" name == "b""#,
+        ] {
+            assert!(invalid.parse::<Filter>().is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::str::FromStr;
+
+use crate::Template;
+
+/// `--format` of `list` and `search`, e.g. `{uri}\t{description:40}`. `{field:N}` cuts the
+/// value to N characters, `{{` and `}}` are literal braces, and `\t`, `\n` and `\\` are escapes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Format(Vec<Segment>);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    Field { field: Field, width: Option<usize> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    /// `uri#name`, like `uri` of a filter
+    Uri,
+    /// The flake uri, without the name, like `flake` of a filter
+    Flake,
+    Name,
+    Description,
+    /// Comma separated
+    Tags,
+    Type,
+}
+
+impl Format {
+    pub fn render(&self, t: &Template) -> String {
+        let mut out = String::new();
+
+        for segment in &self.0 {
+            match segment {
+                Segment::Literal(literal) => out.push_str(literal),
+                Segment::Field { field, width } => {
+                    let value = match field {
+                        Field::Uri => t.uri().to_string(),
+                        Field::Flake => t.flake_info.uri.clone(),
+                        Field::Name => t.name.clone(),
+                        Field::Description => t.description.clone(),
+                        Field::Tags => t.tags.join(","),
+                        Field::Type => t.flake_info.source_type.as_str().to_string(),
+                    };
+
+                    match width {
+                        Some(width) => out.extend(value.chars().take(*width)),
+                        None => out.push_str(&value),
+                    }
+                }
+            }
+        }
+
+        out
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut segments = vec![];
+        let mut literal = String::new();
+        let mut chars = s.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('t') => literal.push('\t'),
+                    Some('n') => literal.push('\n'),
+                    Some('\\') => literal.push('\\'),
+                    Some(c) => {
+                        literal.push('\\');
+                        literal.push(c);
+                    }
+                    None => literal.push('\\'),
+                },
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let Some(end) = rest.find('}') else {
+                        return Err(format!("unclosed `{{` in `{s}`"));
+                    };
+                    let placeholder = &rest[..end];
+                    chars = rest[end + 1..].chars();
+
+                    let (name, width) = match placeholder.split_once(':') {
+                        Some((name, width)) => (
+                            name,
+                            Some(width.parse().map_err(|_| {
+                                format!("`{width}` in `{{{placeholder}}}` is not a width")
+                            })?),
+                        ),
+                        None => (placeholder, None),
+                    };
+                    let field = match name {
+                        "uri" => Field::Uri,
+                        "flake" => Field::Flake,
+                        "name" => Field::Name,
+                        "description" => Field::Description,
+                        "tags" => Field::Tags,
+                        "type" => Field::Type,
+                        _ => {
+                            return Err(format!(
+                                "unknown placeholder `{{{name}}}`, use uri, flake, name, description, tags or type"
+                            ));
+                        }
+                    };
+
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Field { field, width });
+                }
+                '}' => return Err(format!("unmatched `}}` in `{s}`, write `}}}}` for a brace")),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        Ok(Self(segments))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render() {
+        let t: Template = serde_json::from_value(serde_json::json!({
+            "name": "rust",
+            "flake_info": { "name": null, "uri": "github:NixOS/templates" },
+            "description": "A Rust project",
+            "tags": ["rust", "cargo"],
+        }))
+        .unwrap();
+
+        let format: Format = r"{uri}\t{description:6} [{tags}] {{{type}}}"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            format.render(&t),
+            "github:NixOS/templates#rust\tA Rust [rust,cargo] {flake}"
+        );
+        let format: Format = "{flake} {name}".parse().unwrap();
+        assert_eq!(format.render(&t), "github:NixOS/templates rust");
+    }
+
+    #[test]
+    fn errors() {
+        for invalid in ["{name", "{nam}", "{name:x}", "name}"] {
+            assert!(invalid.parse::<Format>().is_err(), "{invalid}");
+        }
+    }
+}
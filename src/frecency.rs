@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use ltrait::Sorter;
+use ltrait::color_eyre::Result;
+use ltrait_sorter_frecency::{Frecency, FrecencyConfig};
+
+use crate::Template;
+use crate::history;
+
+/// `sort` in the config: what [`BoostedFrecency`] orders by
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortMode {
+    #[default]
+    Frecency,
+    /// The last successful init alone, most recent first
+    Recent,
+}
+
+impl SortMode {
+    pub fn toggled(self) -> Self {
+        match self {
+            SortMode::Frecency => SortMode::Recent,
+            SortMode::Recent => SortMode::Frecency,
+        }
+    }
+}
+
+/// Switched at runtime by the TUI
+pub type SharedSortMode = Arc<Mutex<SortMode>>;
+
+/// The sorter of `ltrait_sorter_frecency`, which already has [`Template::boost`] in the score
+/// through [`Template::frecency_context`], with demoted templates below the others.
+///
+/// With [`SortMode::Recent`], only the time of the last use counts.
+///
+/// Demoted templates and negative boosts only sink them while the query is empty. Once something
+/// is typed they rank like the others, so that the matcher can still bring them up
+pub struct BoostedFrecency {
+    frecency: Frecency,
+    mode: SharedSortMode,
+    /// From the history, for [`SortMode::Recent`]
+    last_used: HashMap<String, DateTime<Utc>>,
+}
+
+impl BoostedFrecency {
+    pub fn new(config: &FrecencyConfig) -> Result<Self> {
+        Ok(Self {
+            frecency: Frecency::new(config.clone())?,
+            mode: SharedSortMode::default(),
+            last_used: HashMap::new(),
+        })
+    }
+
+    /// Order by `mode` instead of always by frecency
+    pub fn sort_mode(mut self, mode: SharedSortMode) -> Result<Self> {
+        self.last_used = history::last_used_at()?;
+        self.mode = mode;
+        Ok(self)
+    }
+}
+
+impl Sorter for BoostedFrecency {
+    type Context = Template;
+
+    fn compare(&self, lhs: &Self::Context, rhs: &Self::Context, input: &str) -> std::cmp::Ordering {
+        // the best entry is the last one, so `false < true` puts the demoted ones first
+        let sunk = |t: &Template| t.demoted || t.boost < 0.;
+        let demoted = if input.is_empty() {
+            (!sunk(lhs)).cmp(&!sunk(rhs))
+        } else {
+            std::cmp::Ordering::Equal
+        };
+
+        if *self.mode.lock().unwrap() == SortMode::Recent {
+            // `None < Some`, so the unused ones come first and the most recent one last
+            return demoted.then(
+                self.last_used
+                    .get(lhs.uri())
+                    .cmp(&self.last_used.get(rhs.uri())),
+            );
+        }
+
+        demoted.then_with(|| {
+            self.frecency
+                .compare(&lhs.frecency_context(), &rhs.frecency_context(), input)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ltrait::Action as _;
+
+    use super::*;
+
+    #[test]
+    fn usage_outweighs_a_small_boost() {
+        let template = |name: &str, boost: f64| -> Template {
+            let mut t = crate::cache::parse_flake_show(
+                format!(r#"{{"templates":{{"{name}":{{"description":""}}}}}}"#).as_bytes(),
+                "github:nit/boost-test",
+            )
+            .unwrap()
+            .remove(0);
+            t.boost = boost;
+            t
+        };
+        let used = template("used", 0.);
+        let boosted = template("boosted", 0.1);
+        let sunk = template("sunk", -1.);
+        assert_eq!(boosted.frecency_context().bonus, 15.1);
+        assert_eq!(sunk.frecency_context().bonus, 15.);
+
+        let config = FrecencyConfig {
+            half_life: std::time::Duration::from_secs(30 * 24 * 3600),
+            type_ident: "nit-boost-test".into(),
+        };
+        // like separate runs of nit, as a `Frecency` only reads the scores when it is created
+        let use_times = |t: &Template, times| {
+            for _ in 0..times {
+                Frecency::new(config.clone())
+                    .unwrap()
+                    .act(&t.frecency_context())
+                    .unwrap();
+            }
+        };
+        use_times(&used, 5);
+        use_times(&boosted, 2);
+
+        let sorter = BoostedFrecency::new(&config).unwrap();
+        // the best entry is the last one
+        assert!(sorter.compare(&boosted, &used, "").is_lt());
+        assert!(sorter.compare(&sunk, &boosted, "").is_lt());
+        assert!(sorter.compare(&sunk, &template("unused", 0.), "x").is_eq());
+    }
+}
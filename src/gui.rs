@@ -0,0 +1,70 @@
+use std::io::Write as _;
+use std::process::Stdio;
+
+use ltrait::color_eyre::{
+    Result,
+    eyre::{ContextCompat as _, WrapErr as _},
+};
+
+use crate::locale::tr;
+
+/// dmenu-compatible pickers, tried in order when `[ui] gui` is omitted. The first two are native
+/// on Wayland
+const PICKERS: &[&[&str]] = &[
+    &["fuzzel", "--dmenu", "--prompt", "nit> "],
+    &["wofi", "--dmenu", "--prompt", "nit"],
+    &["rofi", "-dmenu", "-i", "-p", "nit"],
+    &["bemenu", "-i", "-p", "nit"],
+    &["dmenu", "-i", "-p", "nit"],
+];
+
+/// Show `lines` in a desktop picker and return the index of the chosen one. `None` when the
+/// picker was closed without choosing
+pub fn pick(command: Option<&[String]>, lines: &[String]) -> Result<Option<usize>> {
+    let command: Vec<String> = match command {
+        Some(command) => command.to_vec(),
+        None => PICKERS
+            .iter()
+            .find(|picker| in_path(picker[0]))
+            .wrap_err_with(|| tr("gui-no-picker", &[]))?
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect(),
+    };
+    let (program, args) = command
+        .split_first()
+        .wrap_err_with(|| tr("gui-empty", &[]))?;
+
+    let mut child = crate::platform::command(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .wrap_err_with(|| tr("gui-failed", &[("program", program)]))?;
+
+    // pickers read every line before showing up
+    let mut stdin = child
+        .stdin
+        .take()
+        .wrap_err_with(|| tr("gui-no-stdin", &[]))?;
+    match stdin.write_all(lines.join("\n").as_bytes()) {
+        Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => return Err(e.into()),
+        _ => drop(stdin),
+    }
+
+    let out = child.wait_with_output()?;
+    if !out.status.success() {
+        // Esc exits with 1 in all of them
+        return Ok(None);
+    }
+
+    let chosen = String::from_utf8_lossy(&out.stdout);
+    Ok(lines
+        .iter()
+        .position(|line| line == chosen.trim_end_matches('\n')))
+}
+
+fn in_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
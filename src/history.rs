@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use ltrait::color_eyre::{Result, eyre::ContextCompat};
+use serde::{Deserialize, Serialize};
+
+use crate::error::NitError;
+use crate::{Template, cancel};
+
+/// One `nix flake init` run, stored as a line of `<state dir>/nix-nit/history.jsonl`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Record {
+    pub template: Template,
+    /// The revision of the flake the template was resolved to, if nix reported one
+    pub revision: Option<String>,
+    pub target: PathBuf,
+    pub timestamp: DateTime<Utc>,
+    pub success: bool,
+    /// What the init added to `target`, for `nit undo`
+    #[serde(default)]
+    pub created: Vec<Created>,
+    /// Whether `nit undo` removed `created`
+    #[serde(default)]
+    pub undone: bool,
+}
+
+/// A file or directory added by an init
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Created {
+    pub path: PathBuf,
+    /// Hash of the content the init left, to tell whether the file was edited since. `None` for
+    /// directories
+    pub hash: Option<u64>,
+}
+
+impl Created {
+    pub fn file(path: PathBuf) -> Result<Self> {
+        Ok(Self {
+            hash: Some(content_hash(&path)?),
+            path,
+        })
+    }
+
+    pub fn dir(path: PathBuf) -> Self {
+        Self { path, hash: None }
+    }
+
+    /// Whether the file is still as the init left it
+    pub fn unmodified(&self) -> Result<bool> {
+        Ok(self.hash.is_none() || self.hash == Some(content_hash(&self.path)?))
+    }
+}
+
+/// Hash of the content of a file, or of where a symlink points to
+fn content_hash(path: &Path) -> Result<u64> {
+    let bytes = if path.symlink_metadata()?.is_symlink() {
+        std::fs::read_link(path)?
+            .into_os_string()
+            .into_encoded_bytes()
+    } else {
+        std::fs::read(path)?
+    };
+
+    Ok(crate::config::fnv1a(&bytes))
+}
+
+fn history_path() -> Result<PathBuf> {
+    Ok(crate::platform::state_dir()
+        .wrap_err("State directory doesn't exist.")?
+        .join("nix-nit/history.jsonl"))
+}
+
+pub fn append(record: &Record) -> Result<()> {
+    let path = history_path()?;
+
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+
+    Ok(())
+}
+
+/// Replace the whole history with `records`
+pub fn save(records: &[Record]) -> Result<()> {
+    let path = history_path()?;
+    let tmp = path.with_extension("jsonl.tmp");
+
+    let mut content = String::new();
+    for record in records {
+        content.push_str(&serde_json::to_string(record)?);
+        content.push('\n');
+    }
+
+    cancel::writing(&tmp);
+    std::fs::write(&tmp, content)?;
+    std::fs::rename(&tmp, &path)?;
+    cancel::done_writing(&tmp);
+
+    Ok(())
+}
+
+/// Set what the init at `timestamp` created, once it is done
+pub fn set_created(timestamp: DateTime<Utc>, created: Vec<Created>) -> Result<()> {
+    let mut records = load()?;
+    if let Some(record) = records.iter_mut().rev().find(|r| r.timestamp == timestamp) {
+        record.created = created;
+        save(&records)?;
+    }
+
+    Ok(())
+}
+
+/// Oldest first
+pub fn load() -> Result<Vec<Record>> {
+    let path = history_path()?;
+
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    std::fs::read_to_string(&path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
+
+/// When every template was last initialized successfully, by `uri#name`
+pub fn last_used_at() -> Result<HashMap<String, DateTime<Utc>>> {
+    Ok(load()?
+        .into_iter()
+        .filter(|r| r.success)
+        .map(|r| (r.template.uri().to_string(), r.timestamp))
+        .collect())
+}
+
+/// How many times every template was initialized successfully, by `uri#name`
+pub fn use_counts() -> Result<HashMap<String, usize>> {
+    let mut counts = HashMap::new();
+    for r in load()?.into_iter().filter(|r| r.success) {
+        *counts.entry(r.template.uri().to_string()).or_default() += 1;
+    }
+
+    Ok(counts)
+}
+
+/// The template of the most recent successful init
+pub fn last_used() -> Result<Template> {
+    load()?
+        .into_iter()
+        .rev()
+        .find(|r| r.success)
+        .map(|r| r.template)
+        .ok_or_else(|| NitError::NoHistory.into())
+}
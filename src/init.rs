@@ -0,0 +1,820 @@
+use std::collections::BTreeSet;
+use std::ffi::OsString;
+use std::io::IsTerminal as _;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use ltrait::color_eyre::{
+    Result,
+    eyre::{bail, ensure},
+};
+
+use crate::cache::{self, CacheStore as _};
+use crate::config::{Config, Hooks, RenameRule, ReplaceRule, SourceType};
+use crate::error::NitError;
+use crate::events::{self, Event};
+use crate::history;
+use crate::license;
+use crate::locale::tr;
+use crate::nix::NixBackend;
+use crate::notify;
+use crate::preview;
+use crate::readme;
+use crate::staging::Staging;
+use crate::{FlakeMetadata, Template};
+use crate::{prompt, spec};
+
+#[derive(Debug, Default, Clone)]
+pub struct InitOptions {
+    /// The project name to patch in. Asked for when `None` and the template has rename rules
+    pub name: Option<String>,
+    /// Evaluate the flake with `--impure` regardless of the config
+    pub impure: bool,
+    /// `[profile.<name>]` the template is looked up in
+    pub profile: Option<String>,
+    /// Initialize in this directory, created if needed, instead of the current one
+    pub into: Option<PathBuf>,
+    /// Skip the confirmation before init, regardless of the config, and agree to replace existing
+    /// files
+    pub yes: bool,
+    /// Print what was initialized where, with the warnings of nix, as JSON on stdout
+    pub json: bool,
+    /// One of the templates of a bundle, which asked its questions already
+    pub bundled: bool,
+    /// Fail instead of warning when the flake changed since the cache was built
+    pub strict: bool,
+    /// Existing files the template may replace. Any other one is kept, like `nix flake init` does
+    pub overwrite: Vec<PathBuf>,
+    /// Directories a bundle created before this init, recorded with its files for `nit undo`
+    pub created_dirs: Vec<history::Created>,
+    /// Serving `--stdio`, whose stdin and stdout carry the requests and the answers. Nothing is
+    /// asked, and the hooks and tools nit runs get neither
+    pub stdio: bool,
+}
+
+impl InitOptions {
+    /// Whether the user can be asked something
+    pub fn can_ask(&self) -> bool {
+        !self.stdio && std::io::stdin().is_terminal()
+    }
+
+    /// Keep `cmd` off the stdin and stdout of `--stdio`: it reads nothing, and writes to stderr
+    pub fn detach<'a>(&self, cmd: &'a mut Command) -> &'a mut Command {
+        if self.stdio {
+            cmd.stdin(Stdio::null()).stdout(std::io::stderr());
+        }
+        cmd
+    }
+}
+
+/// What to initialize for `t`: its replacement in the cache if it is deprecated and the user takes
+/// it. Without a terminal to ask, or with `--yes`, `t` with a warning
+pub async fn follow_replacement(t: Template, opts: &InitOptions) -> Result<Template> {
+    if !t.deprecated {
+        return Ok(t);
+    }
+
+    let replacement = match t.replaced_by.as_deref() {
+        Some(uri) => replacement(uri, opts).await,
+        None => None,
+    };
+    let Some(replacement) = replacement else {
+        eprintln!(
+            "{}",
+            tr(
+                "warning",
+                &[("message", &tr("deprecated", &[("uri", &t.uri())]))]
+            )
+        );
+        return Ok(t);
+    };
+
+    if opts.yes || opts.json || !std::io::stdin().is_terminal() {
+        let message = tr(
+            "deprecated-replaced",
+            &[("uri", &t.uri()), ("replacement", &replacement.uri())],
+        );
+        eprintln!("{}", tr("warning", &[("message", &message)]));
+        return Ok(t);
+    }
+
+    let answer = prompt::ask(&tr(
+        "prompt-replacement",
+        &[("uri", &t.uri()), ("replacement", &replacement.uri())],
+    ))?;
+    Ok(match answer.to_lowercase().as_str() {
+        "" | "y" | "yes" => replacement,
+        _ => t,
+    })
+}
+
+/// `uri` in the cache of the profile, read only once a deprecated template was picked
+async fn replacement(uri: &str, opts: &InitOptions) -> Option<Template> {
+    let config = Config::load_optional().ok().flatten();
+    let cache = cache::store(opts.profile.as_deref(), config.as_ref()).ok()?;
+    // by name, as the uri may be a registry alias the cache only knows resolved
+    let name = uri.rsplit_once('#').map_or(uri, |(_, name)| name);
+
+    find(cache.with_prefix(name).await.ok()?, uri).ok()
+}
+
+/// The template whose `uri#name`, or name if only one template has it, is `query`. Fails with the
+/// closest names when there is none
+pub fn find(templates: impl IntoIterator<Item = Template>, query: &str) -> Result<Template> {
+    let templates: Vec<_> = templates.into_iter().collect();
+
+    // registry aliases work too
+    if let Some(t) = templates
+        .iter()
+        .find(|t| t.uri() == query || format!("{}#{}", t.flake_info.config_uri(), t.name) == query)
+    {
+        return Ok(t.clone());
+    }
+    if let [t] = &templates
+        .iter()
+        .filter(|t| t.name == query)
+        .collect::<Vec<_>>()[..]
+    {
+        return Ok((*t).clone());
+    }
+
+    let mut candidates: Vec<_> = templates
+        .iter()
+        .map(|t| {
+            let uri = t.uri();
+            let close = strsim::normalized_damerau_levenshtein;
+            let similarity = match query.rsplit_once('#') {
+                // every uri of a flake shares its prefix, so the names have to be close too
+                Some((_, query_name)) => close(query, uri).min(close(query_name, &t.name)),
+                None => close(query, &t.name),
+            };
+            (similarity, uri)
+        })
+        .filter(|(similarity, _)| *similarity > 0.3)
+        .collect();
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    Err(NitError::UnknownTemplate {
+        query: query.to_string(),
+        suggestions: candidates
+            .into_iter()
+            .take(3)
+            .map(|(_, uri)| uri.to_string())
+            .collect(),
+    }
+    .into())
+}
+
+/// Run `nix flake init` for `t` in the current directory and record it in the history
+pub fn init_template(nix: &impl NixBackend, t: &Template, opts: &InitOptions) -> Result<()> {
+    if t.flake_info.source_type == SourceType::Bundle {
+        return crate::bundle::init(nix, t, opts);
+    }
+
+    let mut config = Config::load()?;
+    if let Some(profile) = &opts.profile {
+        config.use_profile(profile)?;
+    }
+    if opts.impure {
+        config.force_impure();
+    }
+    // templates from the history may come from sources denied since
+    config.policy.check(&t.flake_info.uri)?;
+    let flake_config = config.flake(t.flake_info.config_uri());
+    // the templates of a bundle are already where they belong
+    let into = opts
+        .into
+        .as_deref()
+        .or(config.into.as_deref().filter(|_| !opts.bundled));
+
+    let (mut nix_args, envs) = flake_config
+        .map(|c| (c.nix_args(), c.envs()))
+        .unwrap_or_else(|| (crate::nix::args(), vec![]));
+    // templates from the history may no longer be in the config
+    if opts.impure && flake_config.is_none() {
+        nix_args.push("--impure".into());
+    }
+
+    let current = t
+        .flake_info
+        .source_type
+        .is_flake()
+        .then(|| resolve_metadata(nix, &t.flake_info.uri, &nix_args, &envs))
+        .flatten();
+    // where the files of the template are in the store, to verify and preview them
+    let source = (t.flake_info.source_type.is_flake() && (t.nar_hash.is_some() || !opts.bundled))
+        .then(|| crate::nix::template_path(nix, t, &nix_args, &envs));
+    let nar_hash = t
+        .nar_hash
+        .as_ref()
+        .and_then(|_| source.as_ref()?.as_ref().ok())
+        .and_then(|source| crate::download::nar_hash(source).ok());
+    if let Err(e) = verify(t, nar_hash.as_deref()) {
+        if opts.strict {
+            return Err(e.into());
+        }
+        eprintln!("{}", tr("warning", &[("message", &e)]));
+    }
+    let revision = current.and_then(|m| m.revision);
+    if config.confirm && !opts.yes && !opts.bundled && !confirm_init(t, revision.as_deref(), into)?
+    {
+        eprintln!("{}", tr("aborted", &[]));
+        return Ok(());
+    }
+
+    let mut created_dirs = opts.created_dirs.clone();
+    if let Some(dir) = into {
+        created_dirs.extend(enter(dir)?);
+    } else if !opts.bundled {
+        let Some(dirs) = confirm_existing_flake(opts)? else {
+            eprintln!("{}", tr("aborted", &[]));
+            return Ok(());
+        };
+        created_dirs.extend(dirs);
+    }
+    let target = std::env::current_dir()?;
+
+    let mut overwrite = opts.overwrite.clone();
+    // the bundle reports what its templates replaced afterwards
+    if let Some(source) = source.filter(|_| !opts.bundled) {
+        let Some(confirmed) = preview::confirm(&[source?], &target, opts.can_ask(), opts.yes)?
+        else {
+            eprintln!("{}", tr("aborted", &[]));
+            return Ok(());
+        };
+        overwrite.extend(confirmed);
+    }
+
+    let hooks: Vec<&Hooks> = std::iter::once(&config.hooks)
+        .chain(flake_config.map(|c| &c.hooks))
+        .collect();
+
+    let progress = events::Init::start(t.uri(), &target);
+    // a bundle notifies once for all of its templates
+    let long = notify::Long::start(
+        config.notifications && !opts.bundled,
+        tr(
+            "notify-init-done",
+            &[("uri", &t.uri()), ("target", &target.display())],
+        ),
+        tr("notify-init-failed", &[("uri", &t.uri())]),
+    );
+    run_hooks(hooks.iter().flat_map(|h| &h.pre_init), t, &target, opts)?;
+
+    let (before, dirs_before) = snapshot(&target)?;
+
+    // flake templates reach the target only once every step before the post_init hooks succeeded
+    let mut staging = t
+        .flake_info
+        .source_type
+        .is_flake()
+        .then(|| Staging::new(&target))
+        .transpose()?;
+    let work = staging
+        .as_ref()
+        .map(|s| s.dir())
+        .unwrap_or_else(|| target.clone());
+
+    let template_uri = t.uri();
+    let exited = |status: std::process::ExitStatus| -> Result<Vec<String>> {
+        ensure!(
+            status.success(),
+            tr(
+                "init-failed",
+                &[("uri", &template_uri), ("status", &status)]
+            )
+        );
+        Ok(vec![])
+    };
+    let (init, revision) = match t.flake_info.source_type {
+        SourceType::Flake | SourceType::Devenv => (
+            nix.flake_init(template_uri, &work, &nix_args, &envs),
+            revision,
+        ),
+        SourceType::Cookiecutter => {
+            // cookiecutter asks its questions itself, so it gets the terminal
+            let mut cookiecutter = crate::platform::command("cookiecutter");
+            cookiecutter
+                .arg(&t.flake_info.uri)
+                .envs(envs.iter().cloned());
+            if t.name != "default" {
+                cookiecutter.args(["--directory", &t.name]);
+            }
+
+            (exited(opts.detach(&mut cookiecutter).status()?), None)
+        }
+        SourceType::CargoGenerate => {
+            // cargo-generate asks for the project name itself
+            let mut cargo_generate = crate::platform::command("cargo");
+            cargo_generate
+                .args(["generate", "--git"])
+                .arg(&t.flake_info.uri)
+                .arg("--destination")
+                .arg(&target)
+                .envs(envs.iter().cloned());
+            if t.name != "default" {
+                cargo_generate.arg(&t.name);
+            }
+
+            (exited(opts.detach(&mut cargo_generate).status()?), None)
+        }
+        SourceType::Bundle => bail!(tr("init-bundle", &[("uri", &template_uri)])),
+    };
+
+    let timestamp = chrono::Utc::now();
+    history::append(&history::Record {
+        template: t.clone(),
+        revision,
+        target: target.clone(),
+        timestamp,
+        success: init.is_ok(),
+        // kept even if a later step fails
+        created: created_dirs.clone(),
+        undone: false,
+    })?;
+    // nix only saw the staging directory
+    let mut warnings: Vec<_> = init?
+        .into_iter()
+        .map(|w| w.replace(&*work.to_string_lossy(), &target.to_string_lossy()))
+        .collect();
+
+    let mut generated: Vec<_> = snapshot(&work)?.0.difference(&before).cloned().collect();
+
+    if let Some(spec) = spec::apply(&work, &generated, opts.can_ask())? {
+        generated.retain(|f| *f != spec);
+    }
+
+    let mut project_name = opts.name.clone();
+    if let Some(rules) = flake_config.map(|c| &c.rename)
+        && !rules.is_empty()
+    {
+        if project_name.is_none() {
+            project_name = prompt_project_name(opts)?;
+        }
+
+        if let Some(name) = &project_name {
+            rename(rules, name, &work)?;
+        }
+    }
+
+    if let Some(rules) = flake_config.map(|c| &c.replace)
+        && !rules.is_empty()
+    {
+        replace(rules, &generated, &target)?;
+    }
+
+    if let Some(id) = flake_config
+        .and_then(|c| c.license.as_ref())
+        .or(config.license.as_ref())
+        && !license::exists(&target)?
+        && !license::exists(&work)?
+    {
+        license::write(&work, id, config.author.as_deref())?;
+        generated.push(work.join("LICENSE"));
+    }
+
+    if flake_config.and_then(|c| c.readme).unwrap_or(config.readme)
+        && !readme::exists(&target)?
+        && !readme::exists(&work)?
+    {
+        let welcome_text = t
+            .flake_info
+            .source_type
+            .is_flake()
+            .then(|| readme::welcome_text(nix, t, &nix_args, &envs))
+            .flatten();
+        let project_name = project_name.unwrap_or_else(|| {
+            target
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        });
+
+        readme::write(&work, &project_name, t, welcome_text.as_deref())?;
+        generated.push(work.join("README.md"));
+    }
+
+    let patterns: Vec<&String> = config
+        .gitignore
+        .iter()
+        .chain(flake_config.into_iter().flat_map(|c| &c.gitignore))
+        .collect();
+    if !patterns.is_empty() {
+        let gitignore = work.join(".gitignore");
+        let existing = target.join(".gitignore");
+        // extended in the staging directory, and replaced on commit
+        if gitignore != existing && !gitignore.exists() && existing.exists() {
+            std::fs::copy(&existing, &gitignore)?;
+            overwrite.push(existing);
+        }
+        if extend_gitignore(&gitignore, &patterns)? && !generated.contains(&gitignore) {
+            generated.push(gitignore);
+        }
+    }
+
+    if let Some(staging) = &mut staging {
+        let kept = staging.commit(&overwrite)?;
+        for file in &mut generated {
+            if let Ok(relative) = file.strip_prefix(&work) {
+                *file = target.join(relative);
+            }
+        }
+        generated.retain(|f| !kept.contains(f));
+        warnings.extend(
+            kept.iter()
+                .map(|f| tr("init-kept", &[("path", &f.display())])),
+        );
+    }
+
+    run_hooks(hooks.iter().flat_map(|h| &h.post_init), t, &target, opts)?;
+    if let Some(staging) = staging {
+        staging.finish()?;
+    }
+
+    let (files, dirs) = snapshot(&target)?;
+    let mut created = files
+        .difference(&before)
+        .map(|path| history::Created::file(path.clone()))
+        .collect::<Result<Vec<_>>>()?;
+    created.extend(
+        dirs.difference(&dirs_before)
+            .map(|path| history::Created::dir(path.clone())),
+    );
+    created.extend(created_dirs);
+    history::set_created(timestamp, created)?;
+
+    // `nix flake init` marks the files it wrote with intent-to-add so that the flake sees them,
+    // but it only saw the staging directory
+    let intent = !config.git_add && t.flake_info.source_type.is_flake();
+    if (config.git_add || intent) && !generated.is_empty() && in_git_repo(&target) {
+        let add = crate::platform::command("git")
+            .arg("add")
+            .args(if intent {
+                &["--intent-to-add", "--force"][..]
+            } else {
+                &[]
+            })
+            .arg("--")
+            .args(&generated)
+            .current_dir(&target)
+            .output()?;
+
+        ensure!(
+            add.status.success(),
+            tr(
+                "init-git-add-failed",
+                &[("stderr", &String::from_utf8(add.stderr)?)]
+            )
+        );
+    }
+
+    if opts.json {
+        println!(
+            "{}",
+            serde_json::json!({
+                "uri": template_uri,
+                "target": target,
+                "warnings": warnings,
+            })
+        );
+    } else if !warnings.is_empty() {
+        eprintln!("\n{}", tr("init-warnings", &[("count", &warnings.len())]));
+        for warning in &warnings {
+            eprintln!("  - {}", warning.replace('\n', "\n    "));
+        }
+        eprintln!();
+    }
+
+    print_wiring_guidance(&target);
+    progress.succeeded();
+    long.succeeded();
+
+    Ok(())
+}
+
+/// Append the `patterns` that aren't in `gitignore` yet to it. `true` if it was created
+fn extend_gitignore(gitignore: &Path, patterns: &[&String]) -> Result<bool> {
+    let created = !gitignore.exists();
+    let mut content = if created {
+        String::new()
+    } else {
+        std::fs::read_to_string(gitignore)?
+    };
+
+    let existing: BTreeSet<String> = content.lines().map(|l| l.trim().to_string()).collect();
+    let mut added = BTreeSet::new();
+    let missing: Vec<_> = patterns
+        .iter()
+        .map(|p| p.trim())
+        .filter(|p| !existing.contains(*p) && added.insert(*p))
+        .collect();
+    if missing.is_empty() {
+        return Ok(false);
+    }
+
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    for pattern in missing {
+        content.push_str(pattern);
+        content.push('\n');
+    }
+    std::fs::write(gitignore, content)?;
+
+    Ok(created)
+}
+
+/// When the template made `target` a flake nested in another one, tell how the outer flake can
+/// use it
+fn print_wiring_guidance(target: &Path) {
+    if !target.join("flake.nix").exists() {
+        return;
+    }
+    let Some(root) = target
+        .ancestors()
+        .skip(1)
+        .find(|dir| dir.join("flake.nix").exists())
+    else {
+        return;
+    };
+    let Ok(relative) = target.strip_prefix(root) else {
+        return;
+    };
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    eprintln!(
+        "{}",
+        tr(
+            "init-wiring",
+            &[
+                ("path", &relative.display()),
+                ("flake", &root.join("flake.nix").display()),
+                ("name", &name),
+            ]
+        )
+    );
+}
+
+pub fn run_hooks<'a>(
+    hooks: impl IntoIterator<Item = &'a String>,
+    t: &Template,
+    target: &Path,
+    opts: &InitOptions,
+) -> Result<()> {
+    for hook in hooks {
+        let status = opts
+            .detach(
+                crate::platform::shell(hook)
+                    .env("NIT_TEMPLATE_URI", t.uri())
+                    .env("NIT_TEMPLATE_NAME", &t.name)
+                    .env("NIT_TARGET_DIR", target),
+            )
+            .status()?;
+        events::emit(Event::HookRan {
+            command: hook,
+            success: status.success(),
+        });
+
+        ensure!(
+            status.success(),
+            tr("init-hook-failed", &[("hook", hook), ("status", &status)])
+        );
+    }
+
+    Ok(())
+}
+
+/// Show what is about to be initialized where, and wait for `y` or Enter. Nobody is asked
+/// outside of a terminal
+fn confirm_init(t: &Template, revision: Option<&str>, into: Option<&Path>) -> Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(true);
+    }
+
+    let target = match into {
+        Some(dir) => std::path::absolute(dir)?,
+        None => std::env::current_dir()?,
+    };
+    let answer = prompt::ask(&tr(
+        "prompt-confirm",
+        &[
+            ("uri", &t.uri()),
+            ("revision", &revision.unwrap_or("-")),
+            ("target", &target.display()),
+        ],
+    ))?;
+
+    Ok(matches!(answer.to_lowercase().as_str(), "" | "y" | "yes"))
+}
+
+/// Create `dir` and move into it. The directories that didn't exist yet, deepest first
+pub fn enter(dir: &Path) -> Result<Vec<history::Created>> {
+    let dir = std::path::absolute(dir)?;
+    let created = dir
+        .ancestors()
+        .take_while(|d| !d.exists())
+        .map(|d| history::Created::dir(d.to_path_buf()))
+        .collect();
+    std::fs::create_dir_all(&dir)?;
+    std::env::set_current_dir(dir)?;
+
+    Ok(created)
+}
+
+/// Ask before initializing inside a directory that already belongs to a flake. Answering with a
+/// subdirectory creates it and moves into it. The directories created for that, `None` if the
+/// user declined
+pub fn confirm_existing_flake(opts: &InitOptions) -> Result<Option<Vec<history::Created>>> {
+    let cwd = std::env::current_dir()?;
+    let Some(existing) = cwd
+        .ancestors()
+        .map(|dir| dir.join("flake.nix"))
+        .find(|flake| flake.exists())
+    else {
+        return Ok(Some(vec![]));
+    };
+
+    // nobody to ask, e.g. in scripts
+    if !opts.can_ask() {
+        tracing::warn!(
+            "{}",
+            tr("init-inside-flake", &[("path", &existing.display())])
+        );
+        return Ok(Some(vec![]));
+    }
+
+    let message = if existing.parent() == Some(&cwd) {
+        tr("prompt-exists", &[("path", &existing.display())])
+    } else {
+        tr("prompt-inside-flake", &[("path", &existing.display())])
+    };
+
+    loop {
+        let answer = prompt::ask(&tr("prompt-continue", &[("message", &message)]))?;
+
+        match answer.to_lowercase().as_str() {
+            "y" | "yes" => return Ok(Some(vec![])),
+            "" | "n" | "no" => return Ok(None),
+            "s" | "subdirectory" => {
+                let dir = prompt::ask(&tr("prompt-subdirectory", &[]))?;
+                if dir.is_empty() {
+                    continue;
+                }
+
+                return enter(Path::new(&dir)).map(Some);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn prompt_project_name(opts: &InitOptions) -> Result<Option<String>> {
+    // stdin may be a pipe with something else to say, like the requests of `--stdio`
+    if !opts.can_ask() {
+        return Ok(None);
+    }
+
+    let name = prompt::ask(&tr("prompt-project-name", &[]))?;
+    Ok((!name.is_empty()).then_some(name))
+}
+
+/// `dir` is where the template was initialized
+fn rename(rules: &[RenameRule], name: &str, dir: &Path) -> Result<()> {
+    for rule in rules {
+        let file = dir.join(&rule.file);
+        // not every template of the flake ships every file
+        if !file.exists() {
+            continue;
+        }
+
+        let content = std::fs::read_to_string(&file)?;
+        std::fs::write(&file, content.replace(&rule.from, name))?;
+    }
+
+    Ok(())
+}
+
+/// The files and the directories below `dir`, as two sets, skipping `.git` and everything in it
+pub fn snapshot(dir: &Path) -> Result<(BTreeSet<PathBuf>, BTreeSet<PathBuf>)> {
+    let mut files = BTreeSet::new();
+    let mut dirs = BTreeSet::new();
+    let mut queue = vec![dir.to_path_buf()];
+
+    while let Some(dir) = queue.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+
+            if file_type.is_dir() {
+                if entry.file_name() != ".git" {
+                    dirs.insert(entry.path());
+                    queue.push(entry.path());
+                }
+            } else {
+                files.insert(entry.path());
+            }
+        }
+    }
+
+    Ok((files, dirs))
+}
+
+fn replace(rules: &[ReplaceRule], files: &[PathBuf], target: &Path) -> Result<()> {
+    let dir_name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let user = std::env::var("USER").unwrap_or_default();
+    let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+
+    let rules: Vec<_> = rules
+        .iter()
+        .map(|r| {
+            let to =
+                r.to.replace("{dir_name}", &dir_name)
+                    .replace("{user}", &user)
+                    .replace("{date}", &date);
+            (&r.from, to)
+        })
+        .collect();
+
+    for file in files {
+        // binary files are left alone
+        let Ok(content) = std::fs::read_to_string(file) else {
+            continue;
+        };
+
+        let replaced = rules
+            .iter()
+            .fold(content.clone(), |acc, (from, to)| acc.replace(*from, to));
+
+        if replaced != content {
+            std::fs::write(file, replaced)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn in_git_repo(dir: &Path) -> bool {
+    crate::platform::command("git")
+        .args(["rev-parse", "--is-inside-work-tree"])
+        .current_dir(dir)
+        .output()
+        .is_ok_and(|o| o.status.success())
+}
+
+/// Ask nix what `flake_uri` currently resolves to. `None` if nix couldn't tell
+fn resolve_metadata(
+    nix: &impl NixBackend,
+    flake_uri: &str,
+    nix_args: &[OsString],
+    envs: &[(String, String)],
+) -> Option<FlakeMetadata> {
+    let metadata = nix
+        .flake_metadata_blocking(flake_uri, nix_args, envs)
+        .ok()?;
+
+    FlakeMetadata::from_json(&metadata).ok()
+}
+
+/// Whether the files of `t` still have the narHash they had when the cache was built, so that
+/// commits touching only other templates of the flake pass. Templates without one on either
+/// side, e.g. from before nit kept it, pass too
+fn verify(t: &Template, current: Option<&str>) -> Result<(), NitError> {
+    match (t.nar_hash.as_deref(), current) {
+        (Some(cached), Some(current)) if cached != current => Err(NitError::Changed {
+            uri: t.uri().to_string(),
+            cached: cached.to_string(),
+            current: current.to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_compares_nar_hashes() {
+        let mut t = crate::cache::parse_flake_show(
+            br#"{"templates":{"x":{"description":""}}}"#,
+            "github:a/b",
+        )
+        .unwrap()
+        .remove(0);
+
+        assert!(verify(&t, Some("a")).is_ok());
+        t.nar_hash = Some("a".into());
+        assert!(verify(&t, Some("a")).is_ok());
+        assert!(verify(&t, None).is_ok());
+        assert!(matches!(
+            verify(&t, Some("b")),
+            Err(NitError::Changed { .. })
+        ));
+    }
+}
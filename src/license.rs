@@ -0,0 +1,82 @@
+use std::path::Path;
+
+use ltrait::color_eyre::{Result, eyre::ensure};
+
+const LICENSE_LIST: &str = "https://raw.githubusercontent.com/spdx/license-list-data/main/text";
+
+/// Short licenses ship with nit, with `{year}` and `{author}` to fill in
+const BUNDLED: &[(&str, &str)] = &[
+    ("0BSD", include_str!("licenses/0BSD.txt")),
+    ("BSD-2-Clause", include_str!("licenses/BSD-2-Clause.txt")),
+    ("BSD-3-Clause", include_str!("licenses/BSD-3-Clause.txt")),
+    ("ISC", include_str!("licenses/ISC.txt")),
+    ("MIT", include_str!("licenses/MIT.txt")),
+    ("Unlicense", include_str!("licenses/Unlicense.txt")),
+];
+
+/// Whether `dir` has a license file, e.g. `LICENSE`, `LICENSE-MIT` or `COPYING.md`
+pub fn exists(dir: &Path) -> Result<bool> {
+    for entry in std::fs::read_dir(dir)? {
+        let name = entry?.file_name().to_string_lossy().to_uppercase();
+        if name.starts_with("LICENSE") || name.starts_with("LICENCE") || name.starts_with("COPYING")
+        {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Write the text of the SPDX license `id` to `dir/LICENSE`. Licenses nit doesn't ship are fetched
+/// from the SPDX license list
+pub fn write(dir: &Path, id: &str, author: Option<&str>) -> Result<()> {
+    let text = match BUNDLED.iter().find(|(i, _)| i.eq_ignore_ascii_case(id)) {
+        Some((_, text)) => text.to_string(),
+        None => fetch(id)?,
+    };
+
+    let author = match author {
+        Some(author) => author.to_string(),
+        None => git_user().unwrap_or_default(),
+    };
+    let year = chrono::Local::now().format("%Y").to_string();
+
+    let text = text
+        .replace("{year}", &year)
+        .replace("{author}", &author)
+        // placeholders of the SPDX texts
+        .replace("<year>", &year)
+        .replace("[yyyy]", &year)
+        .replace("<copyright holders>", &author)
+        .replace("<name of author>", &author)
+        .replace("[name of copyright owner]", &author);
+
+    std::fs::write(dir.join("LICENSE"), text)?;
+
+    Ok(())
+}
+
+fn fetch(id: &str) -> Result<String> {
+    let out = crate::platform::command("curl")
+        .args(["-fsSL", &format!("{LICENSE_LIST}/{id}.txt")])
+        .output()?;
+
+    ensure!(
+        out.status.success(),
+        "couldn't get the text of the license `{id}`, is it an SPDX identifier? err: {}",
+        String::from_utf8_lossy(&out.stderr).trim()
+    );
+
+    Ok(String::from_utf8(out.stdout)?)
+}
+
+/// `git config user.name`
+fn git_user() -> Option<String> {
+    let out = crate::platform::command("git")
+        .args(["config", "user.name"])
+        .output()
+        .ok()?;
+
+    let name = String::from_utf8(out.stdout).ok()?.trim().to_string();
+    (out.status.success() && !name.is_empty()).then_some(name)
+}
@@ -0,0 +1,146 @@
+use ltrait::Sorter as _;
+use ltrait::color_eyre::Result;
+use ltrait_extra::scorer::Scorer as _;
+use ltrait_sorter_frecency::FrecencyConfig;
+
+use crate::Template;
+use crate::format::Format;
+use crate::frecency::BoostedFrecency;
+use crate::history;
+use crate::matcher::{Matcher, MatcherConfig};
+use crate::output::{self, Output};
+
+/// How `list` and `search` order their output. Ties are ordered by name
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Sort {
+    Name,
+    /// By flake uri
+    Flake,
+    /// Most recently initialized first
+    Recent,
+    /// Highest frecency first, like the TUI without a query
+    Frecency,
+}
+
+/// The options `list` and `search` share
+#[derive(Debug, Default, Clone, clap::Args)]
+pub struct Options {
+    /// Instead of the order of the cache, or the best match first for `search`
+    #[arg(long)]
+    pub sort: Option<Sort>,
+    /// Print the last one first, before `--limit` applies
+    #[arg(long)]
+    pub reverse: bool,
+    /// Print at most N templates
+    #[arg(long, value_name = "N")]
+    pub limit: Option<usize>,
+    /// Print each template as FORMAT, e.g. `{uri}\t{description:40}`. The placeholders are uri
+    /// (`uri#name`), flake, name, description, tags and type, and `:N` cuts them to N characters
+    #[arg(long, value_name = "FORMAT", conflicts_with_all = ["json", "output"])]
+    pub format: Option<Format>,
+}
+
+impl Options {
+    pub fn apply(&self, templates: &mut Vec<Template>, frecency: &FrecencyConfig) -> Result<()> {
+        if let Some(by) = self.sort {
+            sort(templates, by, frecency)?;
+        }
+        if self.reverse {
+            templates.reverse();
+        }
+        if let Some(limit) = self.limit {
+            templates.truncate(limit);
+        }
+
+        Ok(())
+    }
+}
+
+/// The templates matching `query`, best match first
+pub fn search(
+    templates: impl IntoIterator<Item = Template>,
+    query: &str,
+    config: MatcherConfig,
+) -> Vec<Template> {
+    let matcher = Matcher::new(config);
+
+    let mut matched: Vec<_> = templates
+        .into_iter()
+        .map(|t| {
+            let score = matcher.predicate_score(&t, query);
+            (score, t)
+        })
+        .filter(|(score, _)| *score > 0)
+        .collect();
+    matched.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+    matched.into_iter().map(|(_, t)| t).collect()
+}
+
+fn sort(templates: &mut [Template], sort: Sort, frecency: &FrecencyConfig) -> Result<()> {
+    match sort {
+        Sort::Name => templates.sort_by(|a, b| a.name.cmp(&b.name)),
+        Sort::Flake => templates.sort_by(|a, b| {
+            a.flake_info
+                .uri
+                .cmp(&b.flake_info.uri)
+                .then(a.name.cmp(&b.name))
+        }),
+        Sort::Recent => {
+            let last_used = history::last_used_at()?;
+            templates.sort_by(|a, b| {
+                last_used
+                    .get(b.uri())
+                    .cmp(&last_used.get(a.uri()))
+                    .then(a.name.cmp(&b.name))
+            });
+        }
+        Sort::Frecency => {
+            let frecency = BoostedFrecency::new(frecency)?;
+            templates.sort_by(|a, b| frecency.compare(b, a, "").then(a.name.cmp(&b.name)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the templates one per line as `uri#name<TAB>description`, in `format`, or in `output`
+pub fn run(
+    templates: impl IntoIterator<Item = Template>,
+    format: Option<&Format>,
+    output: Option<Output>,
+) -> Result<()> {
+    let templates = templates.into_iter();
+
+    match (output, format) {
+        (Some(Output::Json), _) => output::print_lines(
+            templates
+                .map(|t| serde_json::to_string(&t))
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        (_, Some(format)) => output::print_lines(templates.map(|t| format.render(&t))),
+        (None, None) => {
+            output::print_lines(templates.map(|t| format!("{}\t{}", t.uri(), t.description)))
+        }
+        (Some(output), None) => {
+            let rows: Vec<_> = templates
+                .map(|t| {
+                    vec![
+                        t.uri().to_string(),
+                        t.name.clone(),
+                        t.flake_info.uri.clone(),
+                        t.description.clone(),
+                        t.tags.join(","),
+                        t.flake_info.source_type.as_str().to_string(),
+                    ]
+                })
+                .collect();
+
+            output::print_rows(
+                output,
+                &["uri", "name", "flake", "description", "tags", "type"],
+                &rows,
+            )
+        }
+    }
+}
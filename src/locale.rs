@@ -0,0 +1,831 @@
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+/// `language` in the config
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    /// From `$LC_ALL`, `$LC_MESSAGES` or `$LANG`
+    #[default]
+    Auto,
+    En,
+    Ja,
+}
+
+static CURRENT: OnceLock<Language> = OnceLock::new();
+
+/// Pick the language of [`tr`]. Messages created before are still translated when printed
+pub fn init(language: Language) {
+    let _ = CURRENT.set(match language {
+        Language::Auto => from_env(),
+        language => language,
+    });
+}
+
+fn from_env() -> Language {
+    // the first one set wins, like in gettext
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+
+    if locale.starts_with("ja") {
+        Language::Ja
+    } else {
+        Language::En
+    }
+}
+
+/// `(id, English, Japanese)`. `{name}` is replaced with the argument called `name`
+const CATALOG: &[(&str, &str, &str)] = &[
+    (
+        "error-config-not-found",
+        "couldn't find a config at {path}",
+        "{path} に設定ファイルが見つかりません",
+    ),
+    (
+        "error-config-parse",
+        "failed to parse {path}: {message}",
+        "{path} を解析できませんでした: {message}",
+    ),
+    (
+        "error-nix-failed",
+        "failed to run {cmd}, err: {stderr}",
+        "{cmd} の実行に失敗しました。エラー: {stderr}",
+    ),
+    (
+        "error-cache-corrupt",
+        "the cache at {path} is corrupt: {message}",
+        "{path} のキャッシュが壊れています: {message}",
+    ),
+    (
+        "error-no-selection",
+        "no template matched the query `{query}`",
+        "クエリ `{query}` に一致するテンプレートがありません",
+    ),
+    (
+        "error-unknown-template",
+        "no template named `{query}`",
+        "`{query}` という名前のテンプレートはありません",
+    ),
+    (
+        "error-no-history",
+        "no template has been used yet",
+        "まだどのテンプレートも使われていません",
+    ),
+    (
+        "error-unknown-profile",
+        "no profile named `{name}` in the config",
+        "設定に `{name}` というプロファイルはありません",
+    ),
+    (
+        "error-invalid-filter",
+        "invalid filter `{filter}`: {message}",
+        "フィルタ `{filter}` が不正です: {message}",
+    ),
+    (
+        "error-denied",
+        "{uri} is denied by `{rule}` in [policy]",
+        "{uri} は [policy] の `{rule}` で拒否されています",
+    ),
+    (
+        "error-not-allowed",
+        "{uri} isn't in the allow list of [policy]",
+        "{uri} は [policy] の allow に含まれていません",
+    ),
+    (
+        "error-changed",
+        "{uri} changed since the cache was built, its narHash was {cached} and is {current} now",
+        "{uri} はキャッシュの作成後に変更されています。narHash は {cached} でしたが、現在は {current} です",
+    ),
+    (
+        "hint-config-not-found",
+        "create it, see `nit --help` for an example",
+        "作成してください。例は `nit --help` にあります",
+    ),
+    (
+        "hint-config-parse",
+        "see `nit --help` for the format",
+        "書式は `nit --help` を参照してください",
+    ),
+    (
+        "hint-nix-failed",
+        "`nit doctor` checks the nix installation",
+        "`nit doctor` で nix のインストールを確認できます",
+    ),
+    (
+        "hint-cache-corrupt",
+        "rebuild it with `nit --re-cache`",
+        "`nit --re-cache` で作り直してください",
+    ),
+    (
+        "hint-no-selection",
+        "try a shorter query",
+        "もっと短いクエリを試してください",
+    ),
+    (
+        "hint-did-you-mean",
+        "did you mean\n    {suggestions}",
+        "もしかして\n    {suggestions}",
+    ),
+    (
+        "hint-unknown-template",
+        "`nit` shows every template",
+        "`nit` ですべてのテンプレートを表示できます",
+    ),
+    (
+        "hint-no-history",
+        "pick one with `nit` first",
+        "まず `nit` で選んでください",
+    ),
+    (
+        "hint-no-profiles",
+        "add one as [profile.<name>] with its own [[profile.<name>.template]]",
+        "[profile.<name>] と [[profile.<name>.template]] で追加してください",
+    ),
+    (
+        "hint-profiles",
+        "the profiles are {profiles}",
+        "プロファイルは {profiles} です",
+    ),
+    (
+        "hint-invalid-filter",
+        "compare name, flake, uri, tag, description or type with ==, != or ~ to a quoted string, \
+         and combine them with &&, || and !",
+        "name, flake, uri, tag, description, type を ==, != か ~ で引用符付きの文字列と比べ、\
+         &&, || と ! で組み合わせてください",
+    ),
+    (
+        "hint-denied",
+        "ask whoever manages the config to change [policy], or remove the source",
+        "設定の管理者に [policy] の変更を依頼するか、そのソースを削除してください",
+    ),
+    (
+        "hint-changed",
+        "check what changed upstream, then accept it with `nit --re-cache`",
+        "上流の変更を確認してから `nit --re-cache` で受け入れてください",
+    ),
+    ("warning", "warning: {message}", "警告: {message}"),
+    ("deprecated-marker", "(deprecated)", "(非推奨)"),
+    ("tui-too-small", "terminal too small", "端末が小さすぎます"),
+    ("deprecated", "{uri} is deprecated", "{uri} は非推奨です"),
+    (
+        "deprecated-replaced",
+        "{uri} is deprecated, use {replacement} instead",
+        "{uri} は非推奨です。代わりに {replacement} を使ってください",
+    ),
+    (
+        "prompt-replacement",
+        "{uri} is deprecated in favor of {replacement}. Use it instead? [Y/n]: ",
+        "{uri} は非推奨で、{replacement} に置き換えられています。そちらを使いますか? [Y/n]: ",
+    ),
+    (
+        "wizard-welcome",
+        "There is no config for nit yet. Some flakes with templates to start from:",
+        "nit の設定ファイルがまだありません。テンプレートのあるフレークの例:",
+    ),
+    (
+        "prompt-wizard-flakes",
+        "Add which? Numbers like `1 3`, Enter for all, `n` for none: ",
+        "どれを追加しますか? `1 3` のような番号、Enter ですべて、`n` で追加しません: ",
+    ),
+    (
+        "wizard-written",
+        "wrote {path}, fetching the templates of {count} flake(s)",
+        "{path} を書き込みました。{count} 個のフレークのテンプレートを取得します",
+    ),
+    (
+        "wizard-fetched",
+        "[{done}/{count}] {uri}: {templates} template(s)",
+        "[{done}/{count}] {uri}: {templates} 個のテンプレート",
+    ),
+    (
+        "wizard-fetch-failed",
+        "[{done}/{count}] {uri}: failed, {error}",
+        "[{done}/{count}] {uri}: 失敗しました。{error}",
+    ),
+    (
+        "wizard-skipped",
+        "nothing added. Write {path} to add template flakes, see `nit --help` for an example",
+        "何も追加しませんでした。{path} にテンプレートのフレークを書いてください。例は `nit --help` にあります",
+    ),
+    (
+        "prompt-source-name",
+        "Name shown before its templates [{name}]: ",
+        "テンプレートの前に表示する名前 [{name}]: ",
+    ),
+    (
+        "init-warnings",
+        "nix printed {count} warning(s) while initializing:",
+        "初期化中に nix が {count} 件の警告を出しました:",
+    ),
+    (
+        "init-kept",
+        "refusing to overwrite existing file '{path}'",
+        "既存のファイル '{path}' は上書きしません",
+    ),
+    (
+        "prompt-confirm",
+        "Initialize {uri}\n  revision: {revision}\n  into:     {target}\nContinue? [Y/n]: ",
+        "{uri} を初期化します\n  リビジョン: {revision}\n  場所:       {target}\n続行しますか? [Y/n]: ",
+    ),
+    (
+        "prompt-confirm-bundle",
+        "Initialize the bundle {name}\n  templates: {templates}\n  into:      {target}\nContinue? [Y/n]: ",
+        "バンドル {name} を初期化します\n  テンプレート: {templates}\n  場所:         {target}\n続行しますか? [Y/n]: ",
+    ),
+    (
+        "bundle-replaced",
+        "{count} existing file(s) were replaced by the bundle:",
+        "{count} 個の既存のファイルがバンドルで置き換えられました:",
+    ),
+    (
+        "bundle-conflicts",
+        "{count} file(s) were replaced by a later template of the bundle:",
+        "{count} 個のファイルがバンドルの後のテンプレートで置き換えられました:",
+    ),
+    (
+        "rate-limited",
+        "{count} source(s) stayed rate limited and keep the templates of the previous cache, if \
+         any: {sources}\nrun `nit --re-cache` later to fetch them again",
+        "{count} 個のソースがレート制限されたままのため、以前のキャッシュのテンプレートを使います: \
+         {sources}\n後で `nit --re-cache` を実行して取得し直してください",
+    ),
+    (
+        "notify-init-done",
+        "{uri} is initialized in {target}",
+        "{uri} を {target} に初期化しました",
+    ),
+    (
+        "notify-init-failed",
+        "initializing {uri} failed",
+        "{uri} の初期化に失敗しました",
+    ),
+    (
+        "notify-cache-done",
+        "the cache of nit is rebuilt",
+        "nit のキャッシュを再構築しました",
+    ),
+    (
+        "notify-cache-failed",
+        "rebuilding the cache of nit failed",
+        "nit のキャッシュの再構築に失敗しました",
+    ),
+    (
+        "prompt-exists",
+        "{path} already exists, the template will overwrite or merge into it.\n",
+        "{path} は既に存在します。テンプレートが上書きまたはマージします。\n",
+    ),
+    (
+        "prompt-inside-flake",
+        "This directory is inside the flake {path}.\n",
+        "このディレクトリはフレーク {path} の中にあります。\n",
+    ),
+    (
+        "prompt-continue",
+        "{message}Continue? [y]es / [N]o / [s]ubdirectory: ",
+        "{message}続行しますか? [y]es / [N]o / [s]ubdirectory: ",
+    ),
+    (
+        "prompt-subdirectory",
+        "Subdirectory: ",
+        "サブディレクトリ: ",
+    ),
+    (
+        "prompt-project-name",
+        "Project name (leave empty to skip): ",
+        "プロジェクト名 (空欄でスキップ): ",
+    ),
+    (
+        "prompt-changes",
+        "The template changes {count} existing file(s). Continue? [y/N]: ",
+        "テンプレートが既存のファイルを {count} 個変更します。続行しますか? [y/N]: ",
+    ),
+    (
+        "prompt-select",
+        "Select [1-{count}] (empty to quit): ",
+        "選択 [1-{count}] (空欄で終了): ",
+    ),
+    (
+        "prompt-not-a-choice",
+        "`{answer}` is not one of 1-{count}",
+        "`{answer}` は 1-{count} のどれでもありません",
+    ),
+    (
+        "error-stdin-closed",
+        "stdin was closed before an answer was given",
+        "回答の前に標準入力が閉じられました",
+    ),
+    (
+        "error-spec-no-terminal",
+        "{file} of the template asks questions, but stdin isn't a terminal",
+        "テンプレートの {file} は質問をしますが、標準入力が端末ではありません",
+    ),
+    (
+        "prompt-remove-anyway",
+        "Remove them anyway? [y/N]: ",
+        "それでも削除しますか? [y/N]: ",
+    ),
+    (
+        "doctor-pass",
+        "PASS {name}: {detail}",
+        "OK {name}: {detail}",
+    ),
+    (
+        "doctor-fail",
+        "FAIL {name}: {detail}",
+        "NG {name}: {detail}",
+    ),
+    ("doctor-fix", "     fix: {fix}", "     対処: {fix}"),
+    (
+        "doctor-failed",
+        "{count} check(s) failed",
+        "{count} 個のチェックが失敗しました",
+    ),
+    (
+        "doctor-config-sources",
+        "{count} template source(s)",
+        "テンプレートのソースが {count} 個",
+    ),
+    (
+        "doctor-config-missing",
+        "~/.config/nix-nit/config.toml doesn't exist",
+        "~/.config/nix-nit/config.toml がありません",
+    ),
+    (
+        "doctor-config-missing-fix",
+        "create it, see `nit --help` for an example",
+        "作成してください。例は `nit --help` にあります",
+    ),
+    (
+        "doctor-config-broken-fix",
+        "fix the config, see `nit --help` for the format",
+        "設定を直してください。書式は `nit --help` にあります",
+    ),
+    (
+        "doctor-nix-unparsable",
+        "couldn't parse `{output}`",
+        "`{output}` を解析できませんでした",
+    ),
+    (
+        "doctor-nix-unparsable-fix",
+        "report the output of nix --version",
+        "nix --version の出力を報告してください",
+    ),
+    (
+        "doctor-nix-broken-fix",
+        "reinstall nix",
+        "nix を再インストールしてください",
+    ),
+    (
+        "doctor-nix-missing",
+        "couldn't run nix: {error}",
+        "nix を実行できませんでした: {error}",
+    ),
+    (
+        "doctor-nix-missing-fix",
+        "install nix and make sure it's in PATH",
+        "nix をインストールし、PATH に含まれているか確認してください",
+    ),
+    (
+        "doctor-nix-config",
+        "couldn't read the nix configuration",
+        "nix の設定を読めませんでした",
+    ),
+    (
+        "doctor-nix-config-fix",
+        "check that `nix config show` works",
+        "`nix config show` が動くか確認してください",
+    ),
+    (
+        "doctor-flakes-enabled",
+        "nix-command and flakes are enabled",
+        "nix-command と flakes は有効です",
+    ),
+    (
+        "doctor-flakes-by-nit",
+        "{features} not enabled, nit enables them for its own commands",
+        "{features} は無効ですが、nit は自身のコマンドで有効にします",
+    ),
+    (
+        "doctor-flakes-disabled",
+        "{features} not enabled",
+        "{features} が無効です",
+    ),
+    (
+        "doctor-flakes-disabled-fix",
+        "add `experimental-features = nix-command flakes` to ~/.config/nix/nix.conf",
+        "~/.config/nix/nix.conf に `experimental-features = nix-command flakes` を追加してください",
+    ),
+    ("doctor-resolvable", "resolvable", "解決できます"),
+    (
+        "doctor-unresolvable-fix",
+        "check the uri, the network and its credentials (netrc, ssh)",
+        "uri、ネットワーク、認証情報 (netrc, ssh) を確認してください",
+    ),
+    (
+        "doctor-resolve-fix",
+        "check that nix and git are in PATH",
+        "nix と git が PATH に含まれているか確認してください",
+    ),
+    (
+        "doctor-cache-dir-fix",
+        "set XDG_CACHE_HOME or HOME, and check `[cache] store`",
+        "XDG_CACHE_HOME か HOME を設定し、`[cache] store` を確認してください",
+    ),
+    (
+        "doctor-cache-unreadable",
+        "couldn't read {path}: {error}",
+        "{path} を読めませんでした: {error}",
+    ),
+    (
+        "doctor-cache-unreadable-fix",
+        "remove {path} and run `nit -r`",
+        "{path} を削除して `nit -r` を実行してください",
+    ),
+    (
+        "doctor-cache-unwritable",
+        "couldn't write to {path}: {error}",
+        "{path} に書き込めませんでした: {error}",
+    ),
+    (
+        "doctor-cache-unwritable-fix",
+        "make {path} writable",
+        "{path} を書き込み可能にしてください",
+    ),
+    (
+        "doctor-cache-ok",
+        "{path} is readable and writable",
+        "{path} は読み書きできます",
+    ),
+    (
+        "doctor-cache-empty",
+        "{path} is writable, the cache is built on the next launch",
+        "{path} は書き込めます。キャッシュは次の起動で作られます",
+    ),
+    (
+        "doctor-no-terminal",
+        "stdin or stdout is not a terminal",
+        "標準入力か標準出力が端末ではありません",
+    ),
+    (
+        "doctor-no-terminal-fix",
+        "run nit in a terminal, or use --first with --query",
+        "端末で nit を実行するか、--first と --query を使ってください",
+    ),
+    ("doctor-term", "TERM is `{term}`", "TERM が `{term}` です"),
+    (
+        "doctor-term-fix",
+        "set TERM, e.g. to xterm-256color",
+        "TERM を xterm-256color などに設定してください",
+    ),
+    (
+        "doctor-terminal-size",
+        "couldn't get the terminal size: {error}",
+        "端末のサイズを取得できませんでした: {error}",
+    ),
+    (
+        "doctor-terminal-size-fix",
+        "run nit in a terminal emulator",
+        "端末エミュレータで nit を実行してください",
+    ),
+    ("aborted", "aborted", "中止しました"),
+    ("plain-error", "error: {error}", "エラー: {error}"),
+    ("plain-caused-by", "caused by: {cause}", "原因: {cause}"),
+    ("plain-hint", "hint: {hint}", "ヒント: {hint}"),
+    (
+        "undo-unrecorded",
+        "the init of {uri} in {target} didn't record what it created",
+        "{target} への {uri} の初期化は作成したものを記録していません",
+    ),
+    (
+        "undo-edited",
+        "edited since the init:\n{files}",
+        "初期化の後に編集されたファイル:\n{files}",
+    ),
+    (
+        "undo-edited-no-terminal",
+        "{count} file(s) were edited since the init, run nit undo in a terminal to remove them anyway",
+        "{count} 個のファイルが初期化の後に編集されています。それでも削除するには端末で nit undo を実行してください",
+    ),
+    (
+        "undo-removed",
+        "removed {count} file(s) of {uri} from {target}",
+        "{target} から {uri} のファイルを {count} 個削除しました",
+    ),
+    (
+        "init-failed",
+        "failed to initialize {uri}, {status}",
+        "{uri} の初期化に失敗しました。{status}",
+    ),
+    (
+        "init-bundle",
+        "{uri} is a bundle, not a template",
+        "{uri} はテンプレートではなくバンドルです",
+    ),
+    (
+        "init-git-add-failed",
+        "failed to run git add, err: {stderr}",
+        "git add の実行に失敗しました。エラー: {stderr}",
+    ),
+    (
+        "init-wiring",
+        "{path} is a flake of its own. To use it from {flake}, add it as an input:\n\n    inputs.{name}.url = \"path:./{path}\";\n",
+        "{path} は独立した flake です。{flake} から使うには input として追加してください:\n\n    inputs.{name}.url = \"path:./{path}\";\n",
+    ),
+    (
+        "init-hook-failed",
+        "hook `{hook}` failed with {status}",
+        "フック `{hook}` が失敗しました: {status}",
+    ),
+    (
+        "init-inside-flake",
+        "initializing inside the flake {path}",
+        "flake {path} の中で初期化します",
+    ),
+    (
+        "prefetch-failed",
+        "{count} flake(s) couldn't be fetched",
+        "{count} 個の flake を取得できませんでした",
+    ),
+    ("prefetch-fetched", "fetched {uri}", "{uri} を取得しました"),
+    (
+        "prefetch-fetch-failed",
+        "failed to fetch {uri}: {error}",
+        "{uri} の取得に失敗しました: {error}",
+    ),
+    (
+        "eject-not-a-flake",
+        "only flake templates can be ejected, {uri} is a {type} template",
+        "取り出せるのは flake のテンプレートだけです。{uri} は {type} のテンプレートです",
+    ),
+    (
+        "eject-done",
+        "ejected {uri} into {dir}",
+        "{uri} を {dir} に取り出しました",
+    ),
+    (
+        "eject-exists",
+        "{path} already exists",
+        "{path} は既に存在します",
+    ),
+    (
+        "edit-exists",
+        "{uri} is already in {path}",
+        "{uri} は既に {path} にあります",
+    ),
+    (
+        "edit-no-templates",
+        "{uri} has no templates",
+        "{uri} にはテンプレートがありません",
+    ),
+    (
+        "edit-added",
+        "added {uri} as {name} with {count} template(s) to {path}",
+        "{uri} を {name} として {path} に追加しました (テンプレート {count} 個)",
+    ),
+    (
+        "edit-missing",
+        "{uri} isn't a [[template]] in {path}",
+        "{uri} は {path} の [[template]] にありません",
+    ),
+    (
+        "edit-remove-failed",
+        "couldn't remove {uri} from {path} without touching the rest, edit it by hand",
+        "他の部分に触れずに {path} から {uri} を削除できませんでした。手で編集してください",
+    ),
+    (
+        "edit-removed",
+        "removed {uri} from {path}",
+        "{path} から {uri} を削除しました",
+    ),
+    (
+        "update-nix-store",
+        "nit is installed with nix ({path}), update it through nix instead",
+        "nit は nix でインストールされています ({path})。nix で更新してください",
+    ),
+    (
+        "update-latest",
+        "nit {version} is the latest version",
+        "nit {version} は最新版です",
+    ),
+    (
+        "update-available",
+        "nit {version} is available (running {running})",
+        "nit {version} が利用できます (実行中: {running})",
+    ),
+    (
+        "update-no-asset",
+        "the release has no {name}",
+        "リリースに {name} がありません",
+    ),
+    (
+        "update-done",
+        "updated nit to {version}",
+        "nit を {version} に更新しました",
+    ),
+    (
+        "checksum-mismatch",
+        "checksum mismatch for {url}: expected {expected}, got {actual}",
+        "{url} のチェックサムが一致しません: 期待値 {expected}、実際 {actual}",
+    ),
+    (
+        "catalog-kept",
+        "keeping the last version of {url}: {error}",
+        "{url} は前回のものを使います: {error}",
+    ),
+    (
+        "catalog-broken",
+        "the catalog {url} is broken: {message}",
+        "カタログ {url} が壊れています: {message}",
+    ),
+    (
+        "preview-new-file",
+        "new file: {path}",
+        "新しいファイル: {path}",
+    ),
+    (
+        "preview-no-terminal",
+        "the template would change {count} existing file(s), run nit in a terminal to confirm or pass --yes",
+        "テンプレートが既存のファイルを {count} 個変更します。確認するには端末で nit を実行するか --yes を付けてください",
+    ),
+    (
+        "dev-no-flake",
+        "{dir} has no flake.nix",
+        "{dir} に flake.nix がありません",
+    ),
+    (
+        "dev-watching",
+        "watching {dir}, applying into {work}",
+        "{dir} を監視し、{work} に適用します",
+    ),
+    (
+        "dev-waiting",
+        "waiting for changes...",
+        "変更を待っています...",
+    ),
+    (
+        "dev-files",
+        "{name}: {count} file(s)",
+        "{name}: ファイル {count} 個",
+    ),
+    ("dev-unchanged", "{name}: unchanged", "{name}: 変更なし"),
+    (
+        "dev-changed",
+        "{name}: {new} new, {modified} modified, {removed} removed",
+        "{name}: 新規 {new}、変更 {modified}、削除 {removed}",
+    ),
+    (
+        "dev-removed-file",
+        "removed file: {path}",
+        "削除されたファイル: {path}",
+    ),
+    ("updated-now", "updated just now", "たった今更新"),
+    (
+        "updated-minutes",
+        "updated {count}m ago",
+        "{count} 分前に更新",
+    ),
+    (
+        "updated-hours",
+        "updated {count}h ago",
+        "{count} 時間前に更新",
+    ),
+    ("updated-days", "updated {count}d ago", "{count} 日前に更新"),
+    (
+        "updated-months",
+        "updated {count}mo ago",
+        "{count} か月前に更新",
+    ),
+    (
+        "updated-years",
+        "updated {count}y ago",
+        "{count} 年前に更新",
+    ),
+    (
+        "update-bad-version",
+        "`{version}` isn't a version",
+        "`{version}` はバージョンではありません",
+    ),
+    (
+        "update-empty-checksum",
+        "the checksum file is empty",
+        "チェックサムのファイルが空です",
+    ),
+    ("cancelled", "cancelled", "キャンセルしました"),
+    ("status-into", "{uri} into {target}", "{uri} を {target} に"),
+    ("status-normal", "NORMAL", "ノーマル"),
+    ("status-sort-recent", "recent", "最近"),
+    ("status-sort-frecency", "frecency", "頻度"),
+    ("status-sort-match", "match", "一致"),
+    (
+        "bundle-unknown",
+        "no [[bundle]] named `{name}` in the config",
+        "設定に `{name}` という [[bundle]] はありません",
+    ),
+    (
+        "wizard-popular-nixos",
+        "the official templates, for most languages",
+        "公式のテンプレート、ほとんどの言語向け",
+    ),
+    (
+        "wizard-popular-nix-community",
+        "community maintained, e.g. for flake-parts and NixOS modules",
+        "コミュニティによるもの、flake-parts や NixOS モジュール向けなど",
+    ),
+    (
+        "wizard-popular-the-nix-way",
+        "dev shells for many languages and tools",
+        "多くの言語とツールの開発シェル",
+    ),
+    (
+        "wizard-config-header",
+        "written by the first run of nit, see `nit --help` for everything it can hold",
+        "nit の初回起動で作成、書ける内容は `nit --help` を参照",
+    ),
+    (
+        "gui-no-picker",
+        "found none of fuzzel, wofi, rofi, bemenu and dmenu, set `[ui] gui`",
+        "fuzzel、wofi、rofi、bemenu、dmenu のどれも見つかりません、`[ui] gui` を設定してください",
+    ),
+    ("gui-empty", "`[ui] gui` is empty", "`[ui] gui` が空です"),
+    (
+        "gui-failed",
+        "failed to run {program}",
+        "{program} を実行できませんでした",
+    ),
+    (
+        "gui-no-stdin",
+        "failed to open the stdin of the picker",
+        "ピッカーの標準入力を開けませんでした",
+    ),
+    ("status-sort", "sort", "並び順"),
+    ("status-profile", "profile", "プロファイル"),
+];
+
+/// The message `id` in the current language, with `args` filled in
+pub fn tr(id: &str, args: &[(&str, &dyn Display)]) -> String {
+    message(CURRENT.get().copied().unwrap_or_else(from_env), id, args)
+}
+
+/// `id` itself if it isn't in [`CATALOG`]
+fn message(language: Language, id: &str, args: &[(&str, &dyn Display)]) -> String {
+    let Some(&(_, en, ja)) = CATALOG.iter().find(|(i, _, _)| *i == id) else {
+        return id.to_string();
+    };
+
+    let mut message = match language {
+        Language::Ja => ja,
+        Language::En | Language::Auto => en,
+    }
+    .to_string();
+    for (name, value) in args {
+        message = message.replace(&format!("{{{name}}}"), &value.to_string());
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placeholders(message: &str) -> Vec<&str> {
+        let mut names: Vec<_> = message
+            .split('{')
+            .skip(1)
+            .filter_map(|rest| rest.split_once('}').map(|(name, _)| name))
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn catalog() {
+        for (id, en, ja) in CATALOG {
+            assert_eq!(placeholders(en), placeholders(ja), "{id}");
+            assert_eq!(
+                CATALOG.iter().filter(|(i, _, _)| i == id).count(),
+                1,
+                "{id} is there twice"
+            );
+        }
+    }
+
+    #[test]
+    fn fill() {
+        assert_eq!(
+            message(
+                Language::En,
+                "error-unknown-template",
+                &[("query", &"rust")]
+            ),
+            "no template named `rust`"
+        );
+        assert_eq!(
+            message(Language::Ja, "prompt-select", &[("count", &3)]),
+            "選択 [1-3] (空欄で終了): "
+        );
+        assert_eq!(message(Language::Ja, "missing", &[]), "missing");
+    }
+}
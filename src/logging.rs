@@ -0,0 +1,111 @@
+use std::fmt;
+
+use ltrait::Level;
+use ltrait::color_eyre::{Result, eyre::ContextCompat};
+use serde::Deserialize;
+use tracing::field::{Field, Visit};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::fmt::format::{FmtSpan, Writer};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogFormat {
+    /// What `ltrait::setup` writes
+    #[default]
+    Text,
+    /// One JSON object per line, for shipping the logs to e.g. journald or vector
+    Json,
+}
+
+/// Like `ltrait::setup`, writing to the same hourly rotated `<cache dir>/ltrait/log/core.log`.
+/// Unlike it, doesn't install color-eyre, which has to happen before the first error is created
+pub fn setup(level: Level, format: LogFormat) -> Result<WorkerGuard> {
+    let dir = crate::platform::cache_dir()
+        .wrap_err("failed to get log dir")?
+        .join("ltrait/log");
+    std::fs::create_dir_all(&dir)?;
+
+    let (writer, guard) =
+        tracing_appender::non_blocking(tracing_appender::rolling::hourly(dir, "core.log"));
+
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(writer)
+        .with_ansi(false)
+        .with_max_level(level)
+        .with_span_events(FmtSpan::ACTIVE);
+
+    match format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.event_format(Json).init(),
+    }
+
+    Ok(guard)
+}
+
+/// `{"timestamp": ..., "level": ..., "target": ..., "spans": [...], "fields": {...}}`
+struct Json;
+
+impl<S, N> FormatEvent<S, N> for Json
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &tracing::Event<'_>,
+    ) -> fmt::Result {
+        let mut fields = serde_json::Map::new();
+        event.record(&mut FieldVisitor(&mut fields));
+
+        let spans: Vec<_> = ctx
+            .event_scope()
+            .into_iter()
+            .flat_map(|scope| scope.from_root())
+            .map(|span| span.name())
+            .collect();
+
+        let metadata = event.metadata();
+        let line = serde_json::json!({
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "level": metadata.level().as_str(),
+            "target": metadata.target(),
+            "spans": spans,
+            "fields": fields,
+        });
+
+        writeln!(writer, "{line}")
+    }
+}
+
+struct FieldVisitor<'a>(&'a mut serde_json::Map<String, serde_json::Value>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().into(), format!("{value:?}").into());
+    }
+}
@@ -1,254 +1,1124 @@
+mod archive;
+mod bench;
+mod bundle;
+mod cache;
+mod cancel;
+mod catalog;
+mod color;
+mod complete;
+mod config;
+mod dev;
+mod doctor;
+mod download;
+mod edit;
+mod eject;
+mod error;
+mod events;
+mod export;
+mod filter;
+mod format;
+mod frecency;
+#[cfg(feature = "gui")]
+mod gui;
+mod history;
+mod init;
+mod license;
+mod list;
+mod locale;
+mod logging;
+mod man;
+mod matcher;
+mod nix;
+mod notify;
+mod output;
+mod platform;
+mod policy;
+mod prefetch;
+mod preview;
+mod prompt;
+mod readme;
+mod resume;
+mod self_update;
+mod spec;
+#[cfg(feature = "sqlite-cache")]
+mod sqlite_cache;
+mod staging;
+mod stats;
+mod stdio;
+mod tui;
+mod ui;
+mod undo;
+mod validate;
+mod wizard;
+
+use std::collections::HashMap;
+use std::io::IsTerminal as _;
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::process::Command;
 
-use clap::Parser;
+use clap::{CommandFactory as _, Parser, Subcommand, ValueHint};
 
 use serde::{Deserialize, Serialize};
 
-use ltrait::{
-    Launcher, Level,
-    color_eyre::{
-        Result,
-        eyre::{ContextCompat, ensure},
-    },
-};
+use ltrait::{Launcher, Level, color_eyre::Result};
 use ltrait_extra::scorer::ScorerExt as _;
 use ltrait_sorter_frecency::Frecency;
 
+use crate::config::SourceType;
+use crate::init::InitOptions;
+use crate::ui::Frontend;
+
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
-/// The file path of nit config file is ~/.config/nix-nit/config.toml
+/// The file path of nit config file is ~/.config/nix-nit/config.toml. A `.nit/config.toml` in the
+/// current directory adds its [[template]]s and [[bundle]]s and sets `into`, e.g. for the
+/// templates of a monorepo. Those get a cache of their own
 ///
 /// ```toml
+/// into = "packages" # optional. where to initialize without --into, relative to the current dir
+/// git_add = false # optional. `git add` the generated files inside a git repository
+/// hide_defaults = false # optional. hide the `default` template of every flake
+/// log_format = "text" # optional. "text" or "json"
+/// confirm = true # optional. show the uri, revision and directory and ask before init
+/// resume = false # optional. always start with the query the TUI was last closed with
+/// usage_counts = false # optional. append how many times a template was used, like `(×12)`
+/// accessible = false # optional. like --plain on every launch
+/// notifications = false # optional. notify-send when an init or re-cache of over 5s ends
+/// # optional. how nix is run. on Windows without a nix of its own, the one in WSL through
+/// # ["wsl.exe", "--exec", "sh", "-lc", "exec nix \"$@\"", "nix"]
+/// nix_command = ["nix"]
+/// # optional. set for every process nit runs: nix, hooks, git and the pager. ${VAR} is replaced
+/// # with the variable nit was started with
+/// env = { NIX_SSHOPTS = "-o ProxyJump=bastion", XDG_DATA_HOME = "${HOME}/.local/nit" }
+/// # optional. TOML files of [[template]]s, e.g. published by a platform team, added to the ones
+/// # below. fetched once and again on --re-cache. their entries may only set uri, name, tags,
+/// # templates, execludes, override, boost(s) and demote(s). a { url, sha256 } pins one, and a
+/// # list takes several
+/// catalog = "https://example.com/team-templates.toml"
+/// # optional. "en" or "ja" for the messages, prompts and hints. "auto" follows $LC_ALL,
+/// # $LC_MESSAGES and $LANG
+/// language = "auto"
+/// # optional. "nucleo" is fuzzy with the syntax of fzf, "substring" needs every word as is.
+/// # a [matcher] table with `kind` also sets the weights below
+/// matcher = "nucleo"
+/// # optional. "recent" orders by the last use alone, most recent first. Alt-s switches in the TUI
+/// sort = "frecency"
+/// # optional. "vim" starts in normal mode with j/k, gg/G and Ctrl-d/Ctrl-u, `/` types a query.
+/// # "emacs" adds C-n/C-p, C-v/M-v, M-</M-> and C-g
+/// keymap = "default"
+/// # optional. appended to the .gitignore of the project after init, unless already there.
+/// # can also be set per [[template]]
+/// gitignore = ["result", ".direnv/"]
+/// # optional. SPDX license written to LICENSE when the template ships none. can also be set
+/// # per [[template]]
+/// license = "MIT"
+/// author = "..." # optional. the copyright holder, `git config user.name` if omitted
+/// # optional. write a README.md from the description and welcomeText of the template when it
+/// # ships none. can also be set per [[template]]
+/// readme = false
+///
+/// # optional. shell commands with NIT_TEMPLATE_URI, NIT_TEMPLATE_NAME and NIT_TARGET_DIR set.
+/// # init is aborted when a pre_init hook fails. can also be set per [[template]]
+/// [hooks]
+/// pre_init = ["..."]
+/// post_init = ["..."]
+///
+/// # optional. bindings on top of keymap: `ctrl`, `alt` and `shift` joined with `-` to a character,
+/// # enter, esc, tab, up, pgdn, f5... bound to select, exit-without-select, up, down, page-up,
+/// # page-down, home, end, again, reload, toggle-two-line, toggle-sort, search, normal, input or
+/// # ignore. plain characters only apply in the normal mode of the vim keymap
+/// [keys]
+/// ctrl-n = "down"
+///
+/// # optional. how the TUI looks
+/// [ui]
+/// marker = ">" # in front of the highlighted entry
+/// filler = " " # in front of the other entries
+/// prompt = "" # in front of the query, e.g. "❯ "
+/// # optional. the dmenu-compatible picker of `--ui gui`. the first of fuzzel, wofi, rofi, bemenu
+/// # and dmenu in PATH if omitted
+/// gui = ["rofi", "-dmenu", "-i", "-p", "nit"]
+///
+/// # optional. instead of `matcher = "..."`, how much matches in each part of a template count
+/// [matcher]
+/// kind = "nucleo"
+/// name = 2.0
+/// uri = 1.0
+/// description = 0.5 # the description alone doesn't make a match
+///
+/// [cache]
+/// ttl = "7d" # optional. stale caches are refreshed in the background
+/// store = "json" # optional. "sqlite" loads large catalogs in pages, with the sqlite-cache feature
+///
+/// [performance]
+/// fetch_concurrency = 4 # optional. how many sources are fetched at once
+/// rate_limit_retries = 3 # optional. retries of a rate limited source, waiting 1s, 2s, 4s, ...
+///
+/// [policy] # optional. checked before nix runs against a source, `*` matches anything
+/// allow = ["github:myorg/*"] # optional. only these if set
+/// deny = ["git+http://*"] # optional. never these, even if allowed
+///
+/// # optional. a tarball of a template flake, downloaded once into the cache directory, e.g. where
+/// # GitHub is out of reach. collected as a path: flake
+/// [[sources.archive]]
+/// url = "https://artifacts.example.com/templates-1.2.tar.gz"
+/// sha256 = "..." # of the tarball
+/// name = "internal" # optional
+/// tags = ["internal"] # optional
+///
 /// [[template]]
 /// name = "test" # optional
 /// uri = "github:NixOS/templates"
+/// # optional. resolve uri through the flake registry, so that `uri = "templates"` is cached and
+/// # shown as github:NixOS/templates
+/// follow_registry = false
+/// # optional. read `deprecated` and `replacedBy` of the templates, one more nix eval per fetch
+/// deprecations = false
 /// templates = ["default"] # optional. if doesn't exit, import all of templates
 /// execludes = ["..."] # optional
+/// # optional. "flake", "devenv"(tags the templates with `devenv`) or
+/// # "cookiecutter", "cargo-generate"(uri is a git URL, templates are directories in it.
+/// # needs `cookiecutter` or `cargo generate`)
+/// type = "flake"
+/// tags = ["..."] # optional. attached to every template of the flake
+/// netrc = "~/.config/nix/netrc" # optional. for flakes behind HTTP authentication
+/// ssh = true # optional. true, or the path of the key to use for git over SSH
+/// env = { GIT_CONFIG_COUNT = "1" } # optional. set for the commands fetching the flake
+/// pure_eval = true # optional. false passes --impure to nix
+/// boost = 5.0 # optional. added to the frecency bonus of every use, which is 15
+/// # optional. per template, instead of boost. negative values sink it until a query is typed
+/// boosts = { rust = 10.0, default = -1.0 }
+/// # optional. sink the templates to the bottom until a query is typed
+/// demote = false
+/// demotes = ["default"] # optional. per template
+///
+/// # optional. per `uri#name` or template name, instead of what the flake says
+/// [template.override."github:NixOS/templates#rust"]
+/// description = "..." # optional
+/// tags = ["..."] # optional. replaces every tag
+/// icon = "🦀" # optional. in front of the entry
+/// display_name = "Rust" # optional. shown instead of uri#name
+/// # optional. marked and sunk to the bottom until a query is typed. a flake can say the same with
+/// # `deprecated = true;` and `replacedBy = "...";` next to `path` of the template, read with
+/// # `deprecations = true`
+/// deprecated = false
+/// replaced_by = "github:me/templates#rust" # optional. deprecates it and is offered instead
+///
+/// # optional. after init, replace `from` in `file` with the project name
+/// [[template.rename]]
+/// file = "Cargo.toml"
+/// from = "my-project"
+///
+/// # optional. after init, replace `from` with `to` in every generated file.
+/// # `to` can contain {dir_name}, {user} and {date}
+/// [[template.replace]]
+/// from = "TEMPLATE_NAME"
+/// to = "{dir_name}"
+///
+/// # optional. one entry, bundle#rust-ci, that initializes the templates in order into the same
+/// # directory and reports the files a later one replaced
+/// [[bundle]]
+/// name = "rust-ci"
+/// description = "..." # optional. the templates joined with + if omitted
+/// templates = ["github:NixOS/templates#rust", "github:me/templates#github-actions"]
+/// hooks = { post_init = ["..."] } # optional. once around all of them
+///
+/// # optional. a separate set of templates with its own cache, used with `--profile work`
+/// [[profile.work.template]]
+/// uri = "git+ssh://git@example.com/templates"
 /// ```
 struct Args {
-    /// Clear and re-collect the cache(if you changed config, you have to run with re-cache)
+    /// Clear and re-collect the cache. Done automatically when the config changed, and by F5 or
+    /// Ctrl-r in the TUI
     #[arg(short, long)]
     re_cache: bool,
 
-    /// Display on full screen on the terminal when TUI
+    /// Re-collect the cache every time the config is saved while the TUI is open, fetching only
+    /// the changed sources. New templates are listed from the next input on
+    #[arg(long)]
+    watch_config: bool,
+
+    /// Display on full screen on the terminal when TUI. Alt-d switches to two rows per entry there,
+    /// with the description on the second, and Alt-s between ordering by frecency and by the last
+    /// use
     #[arg(short, long, conflicts_with = "inline")]
     fullscreen: bool,
 
     /// How many lines to display when not in Fullscreen
     #[arg(short, long, default_value_t = 12)]
     inline: u16,
+
+    /// Start with the query already typed in
+    #[arg(short, long)]
+    query: Option<String>,
+
+    /// Start with the query the TUI was last closed with, unless --query is given
+    #[arg(long)]
+    resume: bool,
+
+    /// Hide the `default` template of every flake
+    #[arg(long)]
+    hide_defaults: bool,
+
+    /// Show only the `default` template of every flake, one entry per repository
+    #[arg(long, conflicts_with = "hide_defaults")]
+    only_defaults: bool,
+
+    /// Where to pick the template. `gui` opens fuzzel, wofi, rofi, bemenu or dmenu (or `[ui] gui`)
+    /// instead of the TUI, to bind nit to a desktop hotkey. Needs the gui feature
+    #[arg(long, value_enum, default_value_t = ui::UiKind::Tui)]
+    ui: ui::UiKind,
+
+    /// Select the best-ranked template for the query without showing the TUI
+    #[arg(long, visible_alias = "auto-select")]
+    first: bool,
+
+    /// Answer JSON requests on stdin instead of showing the TUI, one per line, for editor plugins:
+    /// `{"id": 1, "method": "query", "query": "rust"}` gives the ranked templates and
+    /// `{"id": 2, "method": "select", "template": "uri#name", "cwd": "..."}` initializes one
+    #[arg(long, conflicts_with_all = ["first", "fullscreen", "query", "resume"])]
+    stdio: bool,
+
+    /// Print the selected template instead of running nix flake init
+    #[arg(short, long, global = true)]
+    print: bool,
+
+    /// The project name to patch into the generated files of templates with rename rules.
+    /// Asked for after init in a terminal when omitted
+    #[arg(short, long, global = true)]
+    name: Option<String>,
+
+    /// Initialize into this directory, created if needed, e.g. a subdirectory of a monorepo
+    #[arg(long, global = true, value_name = "DIR", value_hint = ValueHint::DirPath)]
+    into: Option<PathBuf>,
+
+    /// Initialize without asking first, same as `confirm = false` in the config, and replace the
+    /// existing files the template changes
+    #[arg(short, long, global = true)]
+    yes: bool,
+
+    /// Fail instead of warning when the files of the template changed since the cache was built
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Evaluate the flakes with --impure, overriding `pure_eval` in the config
+    #[arg(long, global = true)]
+    impure: bool,
+
+    /// Use the templates of `[profile.<name>]` in the config instead of the top-level ones
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// Only show the templates matching EXPR, e.g.
+    /// `flake == "github:NixOS/templates" && (tag == "rust" || name ~ "rust")`
+    #[arg(long, global = true, value_name = "EXPR")]
+    filter: Option<String>,
+    /// Machine-readable output: errors as `{"error": {"code": ...}}` on stdout, the output of
+    /// `stats` and `list`, and `{"uri": ..., "target": ..., "warnings": [...]}` after init
+    #[arg(long, global = true)]
+    json: bool,
+    /// Output of `list`, `search` and `stats`. `json` is the same as `--json`
+    #[arg(long, global = true, value_name = "FORMAT")]
+    output: Option<output::Output>,
+
+    /// Format of the log file in ~/.cache/ltrait/log
+    #[arg(long, value_enum, global = true)]
+    log_format: Option<logging::LogFormat>,
+
+    /// Emit what nit is doing, like fetch-started, cache-written or init-finished, for GUI
+    /// wrappers and editor plugins. On stderr unless --events-to is given
+    #[arg(long, value_enum, global = true, value_name = "FORMAT")]
+    events: Option<events::EventFormat>,
+    /// Where --events go, e.g. /dev/fd/3 for a descriptor the caller opened
+    #[arg(long, global = true, value_name = "FILE", value_hint = ValueHint::FilePath, requires = "events")]
+    events_to: Option<PathBuf>,
+
+    /// No colors, borders or glyphs, and errors as plain `error:` and `hint:` lines, for screen
+    /// readers and braille terminals. Same as `accessible = true` in the config. Colors alone
+    /// follow NO_COLOR, CLICOLOR and CLICOLOR_FORCE
+    #[arg(long, global = true)]
+    plain: bool,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Initialize a template by its `uri#name`, or its name if it is unique, without the TUI
+    Init { template: String },
+    /// Copy the files of a template into DIR as they are in the nix store, without nix flake init
+    Eject {
+        template: String,
+        #[arg(value_hint = ValueHint::DirPath)]
+        dir: PathBuf,
+    },
+    /// Re-run the most recently used template in the current directory (Alt-a in the TUI)
+    Again,
+    /// Check nix, the config, every configured source, the cache and the terminal
+    Doctor,
+    /// Check the templates of the flake URI for empty descriptions, missing welcomeText, files
+    /// nix flake init can't copy and names that collide. Fails on anything but a missing
+    /// welcomeText, for the CI of template repositories
+    ValidateTemplate { uri: String },
+    /// Watch the template flake in DIR and apply its templates into a temporary directory on
+    /// every change, reporting how the result differs from the last run. Stop with Ctrl-C
+    Dev {
+        #[arg(default_value = ".", value_hint = ValueHint::DirPath)]
+        dir: PathBuf,
+    },
+    /// Replace this binary with the latest GitHub release
+    SelfUpdate {
+        /// Only report whether an update is available
+        #[arg(long)]
+        check: bool,
+    },
+    /// Summarize the history: most used templates, inits per week and per flake
+    Stats,
+    /// Add or remove a source in config.toml, keeping the rest of it as it is
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Print a config.toml snippet to start a config from, or to trim one with
+    ExportConfig {
+        /// A [[template]] for every flake initialized from, with only the templates used, and the
+        /// configured sources that never were. The only source for now
+        #[arg(long, required = true)]
+        from_history: bool,
+    },
+    /// Print every template as `uri#name<TAB>description`, what `nit | ...` does too
+    List {
+        #[command(flatten)]
+        options: list::Options,
+    },
+    /// `list` with only the templates matching QUERY, best match first
+    Search {
+        query: String,
+        #[command(flatten)]
+        options: list::Options,
+    },
+    /// Copy every cached flake and its inputs into the nix store, for inits without network access
+    Prefetch,
+    /// Remove the files the most recent init created, asking first if any was edited since
+    Undo,
+    /// Print the man page, or write one for every subcommand into DIR for packaging
+    Man {
+        #[arg(long, value_name = "DIR", value_hint = ValueHint::DirPath)]
+        out: Option<PathBuf>,
+    },
+    /// Print the completion for SHELL, e.g. to save as `_nit` in a directory of zsh's `fpath`.
+    /// With --dynamic, templates and profiles are completed too, by asking nit on every TAB
+    Completions {
+        shell: complete::Shell,
+        #[arg(long)]
+        dynamic: bool,
+    },
+    /// Time flattening `nix flake show`, reading the cache, building match strings and scoring
+    /// over synthetic catalogs, then the startup with the real cache
+    #[command(hide = true)]
+    Bench {
+        /// Templates in each synthetic catalog
+        #[arg(long, value_delimiter = ',', default_value = "1000,10000,100000")]
+        sizes: Vec<usize>,
+        /// Runs of each measurement, of which the median is shown
+        #[arg(long, default_value_t = 5)]
+        iterations: u32,
+    },
+    /// Candidates for shell completion, one per line. `templates` reads them from the cache,
+    /// `profiles` from the config
+    #[command(name = "__complete", hide = true)]
+    Complete {
+        kind: complete::Kind,
+        #[arg(default_value = "")]
+        prefix: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// Append a [[template]] for the flake URI, once nix flake show finds templates in it
+    Add {
+        uri: String,
+        /// Shown before its templates. Suggested from URI and asked about if omitted
+        #[arg(long)]
+        name: Option<String>,
+        /// Can be given more than once
+        #[arg(long)]
+        tag: Vec<String>,
+    },
+    /// Delete the [[template]] of URI, with its tables and the comment right above it
+    Remove { uri: String },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    // color-eyre colors its reports regardless of NO_COLOR and the terminal
+    ltrait::color_eyre::config::HookBuilder::new()
+        .theme(match color::support() {
+            color::Support::None => ltrait::color_eyre::config::Theme::new(),
+            _ => ltrait::color_eyre::config::Theme::dark(),
+        })
+        .install()?;
     let args = Args::parse();
+    let json = args.json || args.output == Some(output::Output::Json);
 
-    let _guard = ltrait::setup(Level::INFO)?;
-    let template = load_cache(args.re_cache).await?;
+    // a broken config is reported once the logs are set up
+    let config = config::Config::load_optional().ok().flatten();
+    let log_format = args
+        .log_format
+        .unwrap_or_else(|| config.as_ref().map(|c| c.log_format).unwrap_or_default());
+    locale::init(config.as_ref().map(|c| c.language).unwrap_or_default());
+    nix::set_command(config.as_ref().and_then(|c| c.nix_command.clone()));
+    if let Some(config) = &config {
+        platform::set_env(&config.env);
+    }
+    let plain = args.plain || config.is_some_and(|c| c.accessible);
+    let guard = logging::setup(Level::INFO, log_format)?;
+    cancel::install();
+    if let Some(format) = args.events {
+        events::init(format, args.events_to.as_deref())?;
+    }
 
+    match run(args).await {
+        Ok(()) => Ok(()),
+        Err(e) if json => {
+            error::print_json(&e);
+            drop(guard);
+            std::process::exit(1);
+        }
+        Err(e) if plain => {
+            error::print_plain(&e);
+            drop(guard);
+            std::process::exit(1);
+        }
+        Err(e) => Err(error::with_hint(e)),
+    }
+}
+
+async fn run(mut args: Args) -> Result<()> {
     let frecency_config = ltrait_sorter_frecency::FrecencyConfig {
         // Duration::from_secs(days * MINS_PER_HOUR * SECS_PER_MINUTE * HOURS_PER_DAY)
         half_life: Duration::from_secs(30 * 60 * 60 * 24),
-        type_ident: "nix-nit".into(),
+        // without a profile, the namespace from before profiles existed
+        type_ident: match &args.profile {
+            Some(profile) => format!("nix-nit:{profile}"),
+            None => "nix-nit".into(),
+        },
     };
 
-    let launcher = Launcher::default()
-        .batch_size(1000)
-        .add_raw_source(ltrait::source::from_iter(template))
-        .add_sorter(Frecency::new(frecency_config.clone())?, |c| {
-            ltrait_sorter_frecency::Context {
-                ident: format!("{}-{}", c.flake_info.uri, c.name),
-                bonus: 15.,
-            }
-        })
-        .add_sorter(
-            ltrait_scorer_nucleo::NucleoMatcher::new(
-                false,
-                ltrait_scorer_nucleo::CaseMatching::Smart,
-                ltrait_scorer_nucleo::Normalization::Smart,
-            )
-            .into_sorter(),
-            |c| ltrait_scorer_nucleo::Context {
-                match_string: format!(
-                    "{}{}#{}",
-                    if let Some(fname) = &c.flake_info.name {
-                        format!("{fname} ")
-                    } else {
-                        String::new()
-                    },
-                    c.flake_info.uri,
-                    c.name
-                ),
-            },
+    let init_options = InitOptions {
+        name: args.name.clone(),
+        impure: args.impure,
+        profile: args.profile.clone(),
+        into: args.into.clone(),
+        yes: args.yes,
+        json: args.json || args.output == Some(output::Output::Json),
+        bundled: false,
+        strict: args.strict,
+        overwrite: vec![],
+        created_dirs: vec![],
+        stdio: false,
+    };
+
+    let defaults = if args.only_defaults {
+        cache::Defaults::Only
+    } else if args.hide_defaults {
+        cache::Defaults::Hide
+    } else {
+        cache::Defaults::Show
+    };
+
+    let filter = args
+        .filter
+        .as_deref()
+        .map(str::parse::<filter::Filter>)
+        .transpose()?
+        .map(Arc::new);
+
+    let output = args.output.or(args.json.then_some(output::Output::Json));
+
+    if args.stdio && args.command.is_none() {
+        let templates = cache::load_cache(
+            args.re_cache,
+            args.impure,
+            args.profile.as_deref(),
+            defaults,
+            filter,
+            false,
         )
-        .add_action(Frecency::new(frecency_config)?, |c| {
-            ltrait_sorter_frecency::Context {
-                ident: format!("{}-{}", c.flake_info.uri, c.name),
-                bonus: 15.,
+        .await?
+        .templates
+        .collect();
+        let matcher = config::Config::load_optional()?
+            .map(|c| c.matcher)
+            .unwrap_or_default();
+
+        return stdio::run(
+            &nix::Subprocess,
+            templates,
+            matcher,
+            &frecency_config,
+            &init_options,
+        );
+    }
+
+    let command = match args.command.take() {
+        // like ls, a pipe gets a plain list instead of the TUI
+        None if !std::io::stdout().is_terminal() && !args.print && !args.first => {
+            Some(Commands::List {
+                options: list::Options::default(),
+            })
+        }
+        command => command,
+    };
+
+    let template = match command {
+        Some(Commands::Config {
+            action: ConfigAction::Add { uri, name, tag },
+        }) => return edit::add(&nix::Subprocess, &uri, name, &tag).await,
+        Some(Commands::Config {
+            action: ConfigAction::Remove { uri },
+        }) => return edit::remove(&uri),
+        Some(Commands::Init { template }) => {
+            let template = init::find(
+                cache::load_cache(
+                    args.re_cache,
+                    args.impure,
+                    args.profile.as_deref(),
+                    defaults,
+                    None,
+                    false,
+                )
+                .await?
+                .templates,
+                &template,
+            )?;
+
+            if args.print {
+                template
+            } else {
+                init::follow_replacement(template, &init_options).await?
             }
-        })
-        .add_raw_action(ltrait::action::ClosureAction::new(|t: &Template| {
-            let template_uri = format!("{}#{}", t.flake_info.uri, t.name);
-            let flake = std::process::Command::new("nix")
-                .args(["flake", "init", "-t"])
-                .arg(&template_uri)
-                .output()?;
-
-            ensure!(
-                flake.status.success(),
-                "failed to run nix flake init -t {template_uri}, err: {}",
-                String::from_utf8(flake.stderr)?,
+        }
+        Some(Commands::Again) => history::last_used()?,
+        Some(Commands::Eject { template, dir }) => {
+            let template = init::find(
+                cache::load_cache(
+                    args.re_cache,
+                    args.impure,
+                    args.profile.as_deref(),
+                    defaults,
+                    None,
+                    false,
+                )
+                .await?
+                .templates,
+                &template,
+            )?;
+            let mut config = config::Config::load_optional()?;
+            if let Some(config) = &mut config {
+                if let Some(profile) = &args.profile {
+                    config.use_profile(profile)?;
+                }
+                if args.impure {
+                    config.force_impure();
+                }
+            }
+
+            return eject::run(&template, &dir, config.as_ref());
+        }
+        Some(Commands::Doctor) => return doctor::run(args.profile.as_deref()),
+        Some(Commands::ValidateTemplate { uri }) => {
+            return validate::run(&nix::Subprocess, &uri).await;
+        }
+        Some(Commands::Dev { dir }) => return dev::run(&nix::Subprocess, &dir).await,
+        Some(Commands::SelfUpdate { check }) => return self_update::run(check),
+        Some(Commands::Stats) => {
+            return stats::Stats::new(&history::load()?).print(output);
+        }
+        Some(Commands::ExportConfig { from_history: _ }) => {
+            let mut config = config::Config::load_optional()?;
+            if let (Some(config), Some(profile)) = (&mut config, &args.profile) {
+                config.use_profile(profile)?;
+            }
+
+            print!(
+                "{}",
+                export::from_history(&history::load()?, config.as_ref())?
             );
+            return Ok(());
+        }
+        Some(Commands::Prefetch) => {
+            let cache = cache::load_cache(
+                args.re_cache,
+                args.impure,
+                args.profile.as_deref(),
+                cache::Defaults::Show,
+                None,
+                false,
+            )
+            .await?;
 
-            Ok(())
-        }))
-        .set_ui(
-            ltrait_ui_tui::Tui::new(ltrait_ui_tui::TuiConfig::new(
-                if !args.fullscreen {
-                    ltrait_ui_tui::Viewport::Inline(args.inline)
-                } else {
-                    ltrait_ui_tui::Viewport::Fullscreen
-                },
-                true,
-                '>',
-                ' ',
-                ltrait_ui_tui::sample_keyconfig,
-            )),
-            |c| ltrait_ui_tui::TuiEntry {
-                text: (
-                    format!(
-                        "{}{}#{}",
-                        if let Some(fname) = &c.flake_info.name {
-                            format!("{fname} - ")
-                        } else {
-                            String::new()
-                        },
-                        c.flake_info.uri,
-                        c.name
-                    ),
-                    ltrait_ui_tui::style::Style::new(),
+            return prefetch::run(cache.templates, args.profile.as_deref(), args.impure).await;
+        }
+        Some(Commands::List { ref options }) | Some(Commands::Search { ref options, .. }) => {
+            let cache = cache::load_cache(
+                args.re_cache,
+                args.impure,
+                args.profile.as_deref(),
+                defaults,
+                filter,
+                false,
+            )
+            .await?;
+
+            let mut templates: Vec<_> = match &command {
+                Some(Commands::Search { query, .. }) => list::search(
+                    cache.templates,
+                    query,
+                    config::Config::load_optional()?
+                        .map(|c| c.matcher)
+                        .unwrap_or_default(),
                 ),
-            },
-        );
+                _ => cache.templates.collect(),
+            };
+            options.apply(&mut templates, &frecency_config)?;
 
-    launcher.run().await?;
+            return list::run(templates, options.format.as_ref(), output);
+        }
+        Some(Commands::Undo) => return undo::run(),
+        Some(Commands::Man { out }) => return man::run(Args::command(), out.as_deref()),
+        Some(Commands::Completions { shell, dynamic }) => {
+            complete::script(Args::command(), shell, dynamic);
+            return Ok(());
+        }
+        Some(Commands::Bench { sizes, iterations }) => {
+            return bench::run(&sizes, iterations, args.profile.as_deref()).await;
+        }
+        Some(Commands::Complete { kind, prefix }) => {
+            return complete::run(kind, &prefix, args.profile.as_deref()).await;
+        }
+        None => return launch(args, defaults, filter, frecency_config, init_options).await,
+    };
+
+    if args.print {
+        println!("{}", template.uri());
+    } else {
+        ltrait::Action::act(
+            &Frecency::new(frecency_config)?,
+            &template.frecency_context(),
+        )?;
+        init::init_template(&nix::Subprocess, &template, &init_options)?;
+    }
 
     Ok(())
 }
 
-#[derive(Debug, Deserialize)]
-struct Config {
-    template: Vec<TemplateConfig>,
-}
+/// Pick a template in the TUI, or with `--first` without it. Starts over with a fresh cache when
+/// the TUI asks to reload
+async fn launch(
+    mut args: Args,
+    defaults: cache::Defaults,
+    filter: Option<Arc<filter::Filter>>,
+    frecency_config: ltrait_sorter_frecency::FrecencyConfig,
+    init_options: InitOptions,
+) -> Result<()> {
+    // a cold start asks what to start with instead of failing without a config
+    if !args.json && wizard::needed(args.profile.as_deref())? && !wizard::run().await? {
+        return Ok(());
+    }
 
-#[derive(Debug, Deserialize)]
-struct TemplateConfig {
-    name: Option<String>,
-    uri: String,
-    templates: Option<Vec<String>>,
-    execludes: Option<Vec<String>>,
-}
+    if args.query.is_none()
+        && (args.resume
+            || config::Config::load_optional()
+                .ok()
+                .flatten()
+                .is_some_and(|c| c.resume))
+    {
+        args.query = resume::load(args.profile.as_deref());
+    }
+
+    loop {
+        let reload = ui::ReloadRequest::default();
+        launch_once(
+            &args,
+            defaults,
+            filter.clone(),
+            frecency_config.clone(),
+            init_options.clone(),
+            reload.clone(),
+        )
+        .await?;
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Cache {
-    data: Vec<Template>,
+        let Some(query) = reload.lock().unwrap().take() else {
+            return Ok(());
+        };
+        args.query = Some(query);
+        args.re_cache = true;
+    }
 }
 
-async fn load_cache(re_cache: bool) -> Result<Vec<Template>> {
-    let cache_path = dirs::cache_dir()
-        .wrap_err("Cache directory does'nt exit.")?
-        .join("nix-nit/cache.json");
+async fn launch_once(
+    args: &Args,
+    defaults: cache::Defaults,
+    filter: Option<Arc<filter::Filter>>,
+    frecency_config: ltrait_sorter_frecency::FrecencyConfig,
+    init_options: InitOptions,
+    reload: ui::ReloadRequest,
+) -> Result<()> {
+    let loaded = cache::load_cache(
+        args.re_cache,
+        args.impure,
+        args.profile.as_deref(),
+        defaults,
+        filter,
+        args.watch_config,
+    )
+    .await?;
 
-    if re_cache || !cache_path.exists() {
-        let config_path = dirs::config_dir()
-            .wrap_err("Config directory  doesn't exit.")?
-            .join("nix-nit/config.toml");
+    // a broken config already failed loading the cache
+    let config = config::Config::load_optional()
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    let plain = args.plain || config.accessible;
+    let ui_config = if plain {
+        // the glyphs of the theme could be anything
+        config::UiConfig {
+            gui: config.ui.gui.clone(),
+            ..Default::default()
+        }
+    } else {
+        config.ui.clone()
+    };
+    let sort_mode = frecency::SharedSortMode::new(std::sync::Mutex::new(config.sort));
+    let query = args.query.clone().unwrap_or_default();
+    let gui = args.ui == ui::UiKind::Gui;
+    let numbered = !args.first && !gui && !tui::drawable();
 
-        ensure!(config_path.exists(), "Couldn't find a config");
+    let launcher = Launcher::default()
+        .batch_size(1000)
+        .add_raw_source(ltrait::source::from_iter(loaded.templates))
+        .add_raw_sorter(
+            frecency::BoostedFrecency::new(&frecency_config)?.sort_mode(sort_mode.clone())?,
+        )
+        .add_raw_sorter(matcher::Matcher::new(config.matcher).into_sorter());
 
-        let config = toml::from_str::<Config>(&tokio::fs::read_to_string(&config_path).await?)?;
-        let mut res = vec![];
-        for flake in config.template {
-            let mut data = load_flake(&flake.uri).await?;
-            if let Some(fil) = flake.templates {
-                data.retain(|value| fil.contains(&value.name));
-            }
-            if let Some(fil) = flake.execludes {
-                data.retain(|value| !fil.contains(&value.name));
-            }
-            if let Some(name) = flake.name {
-                for i in data.iter_mut() {
-                    i.flake_info.name = Some(name.clone());
+    let launcher = if let Some(refreshed) = loaded.refreshed {
+        // shows what the background refresh found from the next input on
+        launcher.add_raw_generator(ltrait::generator::ClosureGenerator::new(move |_: &str| {
+            refreshed.lock().unwrap().clone()
+        }))
+    } else {
+        launcher
+    };
+
+    let matched = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let launcher = if args.first || numbered || gui {
+        // without the UI nobody can tell a match from a non-match, so drop the latter
+        let matcher = matcher::Matcher::new(config.matcher);
+        launcher.add_raw_filter(ltrait::filter::ClosureFilter::new(
+            move |c: &Template, input: &str| {
+                input.is_empty() || matcher.score(c.match_string(), input) > 0
+            },
+        ))
+    } else {
+        // the sorters only reorder, so the TUI keeps every entry and shows how many match. Each
+        // is scored once as it comes in, instead of the whole list on every merge
+        let matcher = matcher::Matcher::new(config.matcher);
+        let matched = matched.clone();
+        launcher.add_raw_filter(ltrait::filter::ClosureFilter::new(
+            move |c: &Template, input: &str| {
+                if input.is_empty() || matcher.score(c.match_string(), input) > 0 {
+                    matched.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
                 }
-            }
-            res.extend(data);
+                true
+            },
+        ))
+    };
+
+    let launcher = if args.print {
+        launcher.add_raw_action(ltrait::action::ClosureAction::new(|t: &Template| {
+            println!("{}", t.uri());
+            Ok(())
+        }))
+    } else {
+        launcher
+            .add_action(Frecency::new(frecency_config)?, Template::frecency_context)
+            .add_raw_action(ltrait::action::ClosureAction::new(move |t: &Template| {
+                // the action isn't async, but the cache is read only for a deprecated template
+                let t = tokio::task::block_in_place(|| {
+                    tokio::runtime::Handle::current()
+                        .block_on(init::follow_replacement(t.clone(), &init_options))
+                })?;
+                init::init_template(&nix::Subprocess, &t, &init_options)
+            }))
+    };
+
+    let frontend = if args.first {
+        Frontend::First { query }
+    } else if gui {
+        Frontend::gui(query, ui_config.gui)?
+    } else if numbered {
+        Frontend::Numbered {
+            query,
+            limit: args.inline.into(),
+        }
+    } else {
+        Frontend::Tui {
+            tui: Box::new(tui::Tui::new(
+                tui::TuiConfig::new(
+                    if !args.fullscreen {
+                        tui::Viewport::Inline(args.inline)
+                    } else {
+                        tui::Viewport::Fullscreen
+                    },
+                    true,
+                    ui_config.marker,
+                    ui_config.filler,
+                    config.keymap.keybinder(config.keys),
+                )
+                .prompt(ui_config.prompt)
+                .plain(plain)
+                .mode(config.keymap.mode())
+                .matched(matched.clone())
+                .sort(sort_mode)
+                .query(query)
+                .profile(args.profile.clone())
+                .target(if args.print {
+                    None
+                } else {
+                    args.into.clone().or_else(|| std::env::current_dir().ok())
+                }),
+            )),
+            reload,
+            profile: args.profile.clone(),
         }
+    };
 
-        let cache = Cache { data: res.clone() };
+    let use_counts = if config.usage_counts {
+        history::use_counts()?
+    } else {
+        HashMap::new()
+    };
+    let launcher = launcher.set_ui(frontend, move |c| {
+        let mut text = match use_counts.get(c.uri()) {
+            Some(n) if plain => format!("{} ({n} uses)", c.display_text()),
+            Some(n) => format!("{} (×{n})", c.display_text()),
+            None => c.display_text().to_string(),
+        };
+        if c.deprecated {
+            text = format!("{text} {}", locale::tr("deprecated-marker", &[]));
+        }
 
-        {
-            if let Some(parent) = cache_path.parent()
-                && !parent.as_os_str().is_empty()
-            {
-                tokio::fs::create_dir_all(parent).await?;
-            }
+        tui::TuiEntry {
+            text: (
+                match &c.icon {
+                    Some(icon) if !plain => format!("{icon} {text}"),
+                    _ => text,
+                },
+                if c.deprecated && !plain {
+                    tui::style::Style::new().add_modifier(tui::style::Modifier::DIM)
+                } else {
+                    tui::style::Style::new()
+                },
+            ),
+            uri: c.uri().to_string(),
+            description: c.description.clone(),
+            name: c.display_name.clone().unwrap_or_else(|| c.name.clone()),
         }
+    });
 
-        tokio::fs::write(&cache_path, serde_json::to_string(&cache)?).await?;
+    launcher.run().await?;
 
-        Ok(res)
-    } else {
-        let data: Cache = serde_json::from_str(&tokio::fs::read_to_string(&cache_path).await?)?;
-        Ok(data.data)
-    }
+    Ok(())
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
-struct Template {
+pub struct Template {
     pub name: String,
     pub flake_info: FlakeInfo,
     pub description: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Added to the frecency bonus of every use, see [`Template::frecency_context`]. A negative one
+    /// sinks the template like `demoted`
+    #[serde(default)]
+    pub boost: f64,
+    /// Sorted below everything else while the query is empty
+    #[serde(default)]
+    pub demoted: bool,
+    /// From `[template.override]`, like `display_name`
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// By `[template.override]` or `deprecated` and `replacedBy` of the template in the flake
+    #[serde(default)]
+    pub deprecated: bool,
+    /// `uri#name`
+    #[serde(default)]
+    pub replaced_by: Option<String>,
+    /// Of the files of the template when the cache was built, see [`download::nar_hash`]
+    #[serde(default)]
+    pub nar_hash: Option<String>,
+    /// Built on first use, once the template is collected and won't change anymore
+    #[serde(skip)]
+    keys: Keys,
+}
+
+/// The strings the sorters and the UI ask a [`Template`] for on every pass, built once instead
+#[derive(Debug, Default, Clone)]
+struct Keys {
+    uri: std::sync::OnceLock<String>,
+    ident: std::sync::OnceLock<String>,
+    match_string: std::sync::OnceLock<String>,
+    display_text: std::sync::OnceLock<String>,
+}
+
+impl Template {
+    /// `uri#name`, the reference passed to `nix flake init -t`
+    fn uri(&self) -> &str {
+        self.keys
+            .uri
+            .get_or_init(|| format!("{}#{}", self.flake_info.uri, self.name))
+    }
+
+    /// The template a flake exports as `default`, usually the same as one of its named ones
+    fn is_default(&self) -> bool {
+        self.name == "default" && self.flake_info.source_type.is_flake()
+    }
+
+    /// What the frecency database knows the template by
+    fn ident(&self) -> &str {
+        self.keys
+            .ident
+            .get_or_init(|| format!("{}-{}", self.flake_info.uri, self.name))
+    }
+
+    /// The first use records a score of 0, each later one adds `bonus` to the decayed score. A
+    /// boosted template gains more with each use than the others, but not more than frecency
+    /// gives to the ones used more often
+    fn frecency_context(&self) -> ltrait_sorter_frecency::Context {
+        ltrait_sorter_frecency::Context {
+            ident: self.ident().to_string(),
+            bonus: 15. + self.boost.max(0.),
+        }
+    }
+
+    fn match_string(&self) -> &str {
+        self.keys.match_string.get_or_init(|| {
+            let mut s = String::new();
+            if let Some(fname) = &self.flake_info.name {
+                s.push_str(fname);
+                s.push(' ');
+            }
+            s.push_str(self.uri());
+            for t in self.tags.iter().chain(&self.display_name) {
+                s.push(' ');
+                s.push_str(t);
+            }
+            s
+        })
+    }
+
+    fn display_text(&self) -> &str {
+        self.keys.display_text.get_or_init(|| {
+            let mut s = String::new();
+            if let Some(fname) = &self.flake_info.name {
+                s.push_str(fname);
+                s.push_str(" - ");
+            }
+            s.push_str(self.display_name.as_deref().unwrap_or(self.uri()));
+            if !self.tags.is_empty() {
+                s.push_str(&format!(" [{}]", self.tags.join(", ")));
+            }
+            if let Some(summary) = self
+                .flake_info
+                .metadata
+                .as_ref()
+                .and_then(FlakeMetadata::summary)
+            {
+                s.push_str(&format!(" ({summary})"));
+            }
+            s
+        })
+    }
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
-struct FlakeInfo {
+pub struct FlakeInfo {
     name: Option<String>,
     uri: String,
+    #[serde(default)]
+    source_type: SourceType,
+    /// What the flake was locked to when the cache was built
+    #[serde(default)]
+    metadata: Option<FlakeMetadata>,
+    /// The registry alias `uri` was resolved from with `follow_registry`
+    #[serde(default)]
+    alias: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct FlakeTemplates {
-    #[serde(rename = "defaultTemplate")]
-    pub default_template: Template,
-
-    pub templates: std::collections::HashMap<String, Template>,
+impl FlakeInfo {
+    /// The `uri` of the config entry the flake came from
+    fn config_uri(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.uri)
+    }
 }
 
-async fn load_flake(flake_uri: &str) -> Result<Vec<Template>> {
-    let flake = Command::new("nix")
-        .args(["flake", "show"])
-        .arg(flake_uri)
-        .args(["--json", "--no-pretty"])
-        .output()
-        .await?;
+/// The parts of `nix flake metadata --json` nit keeps
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct FlakeMetadata {
+    /// Unix time
+    pub last_modified: Option<i64>,
+    pub revision: Option<String>,
+    /// The uri without indirections like registry aliases
+    pub resolved_url: Option<String>,
+}
 
-    ensure!(
-        flake.status.success(),
-        "failed to run nix flake show {flake_uri}, err: {}",
-        String::from_utf8(flake.stderr)?,
-    );
+impl FlakeMetadata {
+    fn from_json(json: &[u8]) -> serde_json::Result<Self> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Raw {
+            last_modified: Option<i64>,
+            revision: Option<String>,
+            resolved_url: Option<String>,
+        }
 
-    let mut flake = serde_json::from_slice::<FlakeTemplates>(&flake.stdout)?;
-    let mut res = vec![];
-    flake.default_template.name = "default".into();
-    flake.default_template.flake_info.uri = flake_uri.to_string();
-    res.push(flake.default_template);
-    for (name, mut template) in flake.templates.into_iter() {
-        template.name = name.clone();
-        template.flake_info.uri = flake_uri.to_string();
-        res.push(template);
+        let raw: Raw = serde_json::from_slice(json)?;
+        Ok(Self {
+            last_modified: raw.last_modified,
+            revision: raw.revision,
+            resolved_url: raw.resolved_url,
+        })
     }
 
-    Ok(res)
+    /// e.g. `updated 3d ago @ abc1234`
+    fn summary(&self) -> Option<String> {
+        let updated = self.last_modified.map(|t| {
+            let secs = (chrono::Utc::now().timestamp() - t).max(0);
+            match secs {
+                0..60 => locale::tr("updated-now", &[]),
+                60..3600 => locale::tr("updated-minutes", &[("count", &(secs / 60))]),
+                3600..86400 => locale::tr("updated-hours", &[("count", &(secs / 3600))]),
+                86400..2592000 => locale::tr("updated-days", &[("count", &(secs / 86400))]),
+                2592000..31536000 => locale::tr("updated-months", &[("count", &(secs / 2592000))]),
+                _ => locale::tr("updated-years", &[("count", &(secs / 31536000))]),
+            }
+        });
+        let rev = self
+            .revision
+            .as_ref()
+            .map(|r| r.chars().take(7).collect::<String>());
+
+        match (updated, rev) {
+            (Some(updated), Some(rev)) => Some(format!("{updated} @ {rev}")),
+            (Some(s), None) | (None, Some(s)) => Some(s),
+            (None, None) => None,
+        }
+    }
 }
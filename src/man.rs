@@ -0,0 +1,175 @@
+use std::path::Path;
+
+use clap::{Arg, Command};
+use ltrait::color_eyre::Result;
+
+/// Shown in NAME of `nit(1)`, the doc comment of the CLI starts with the config path instead
+const SUMMARY: &str = "launcher for nix flake templates";
+
+/// Write `nit.1` and a `nit-<subcommand>.1` for every subcommand into `out`, or print `nit.1`
+pub fn run(mut cmd: Command, out: Option<&Path>) -> Result<()> {
+    // propagates the global options and adds --help
+    cmd.build();
+
+    let Some(out) = out else {
+        print!("{}", page(&cmd, "nit", SUMMARY));
+        return Ok(());
+    };
+
+    std::fs::create_dir_all(out)?;
+    std::fs::write(out.join("nit.1"), page(&cmd, "nit", SUMMARY))?;
+    eprintln!("wrote {}", out.join("nit.1").display());
+    for sub in visible_subcommands(&cmd) {
+        let name = format!("nit-{}", sub.get_name());
+        let summary = sub.get_about().map(|a| a.to_string()).unwrap_or_default();
+        std::fs::write(out.join(format!("{name}.1")), page(sub, &name, &summary))?;
+        eprintln!("wrote {}", out.join(format!("{name}.1")).display());
+    }
+
+    Ok(())
+}
+
+pub fn visible_subcommands(cmd: &Command) -> impl Iterator<Item = &Command> {
+    cmd.get_subcommands()
+        .filter(|s| !s.is_hide_set() && s.get_name() != "help")
+}
+
+/// One man page in roff
+fn page(cmd: &Command, name: &str, summary: &str) -> String {
+    let version = cmd.get_version().unwrap_or(env!("CARGO_PKG_VERSION"));
+    let mut roff = format!(
+        ".TH {} 1 \"\" \"nit {version}\"\n.SH NAME\n{} \\- {}\n",
+        name.to_uppercase(),
+        escape(name),
+        escape(summary)
+    );
+
+    let usage = cmd.clone().render_usage().to_string();
+    roff.push_str(".SH SYNOPSIS\n");
+    roff.push_str(&escape(usage.trim_start_matches("Usage:").trim()));
+    roff.push('\n');
+
+    if let Some(about) = cmd.get_long_about().or(cmd.get_about())
+        && about.to_string() != summary
+    {
+        roff.push_str(".SH DESCRIPTION\n");
+        roff.push_str(&paragraphs(&about.to_string()));
+    }
+
+    let positionals: Vec<_> = cmd.get_positionals().filter(|a| !a.is_hide_set()).collect();
+    if !positionals.is_empty() {
+        roff.push_str(".SH ARGUMENTS\n");
+        for arg in positionals {
+            let value = arg
+                .get_value_names()
+                .and_then(|v| v.first())
+                .map(|v| v.to_string())
+                .unwrap_or_else(|| arg.get_id().to_string().to_uppercase());
+            roff.push_str(&format!(".TP\n\\fI{}\\fR\n", escape(&value)));
+            roff.push_str(&help(arg));
+        }
+    }
+
+    let options: Vec<_> = cmd
+        .get_arguments()
+        .filter(|a| !a.is_positional() && !a.is_hide_set())
+        .collect();
+    if !options.is_empty() {
+        roff.push_str(".SH OPTIONS\n");
+        for arg in options {
+            roff.push_str(&format!(".TP\n{}\n", flags(arg)));
+            roff.push_str(&help(arg));
+        }
+    }
+
+    let subcommands: Vec<_> = visible_subcommands(cmd).collect();
+    if !subcommands.is_empty() {
+        roff.push_str(".SH COMMANDS\n");
+        for sub in subcommands {
+            roff.push_str(&format!(
+                ".TP\n\\fB{}\\fR\n",
+                escape(&format!("{name}-{}(1)", sub.get_name()))
+            ));
+            if let Some(about) = sub.get_about() {
+                roff.push_str(&escape(&about.to_string()));
+                roff.push('\n');
+            }
+        }
+    }
+
+    roff
+}
+
+/// `\fB-s\fR, \fB--long\fR \fIVALUE\fR`
+fn flags(arg: &Arg) -> String {
+    let mut names = vec![];
+    if let Some(short) = arg.get_short() {
+        names.push(format!("\\fB\\-{short}\\fR"));
+    }
+    if let Some(long) = arg.get_long() {
+        names.push(format!("\\fB{}\\fR", escape(&format!("--{long}"))));
+    }
+
+    let mut flags = names.join(", ");
+    if arg.get_action().takes_values()
+        && let Some(values) = arg.get_value_names()
+    {
+        for value in values {
+            flags.push_str(&format!(" \\fI{}\\fR", escape(value)));
+        }
+    }
+
+    flags
+}
+
+fn help(arg: &Arg) -> String {
+    let mut help = arg
+        .get_long_help()
+        .or(arg.get_help())
+        .map(|h| paragraphs(&h.to_string()))
+        .unwrap_or_default();
+
+    let defaults: Vec<_> = arg
+        .get_default_values()
+        .iter()
+        .map(|v| v.to_string_lossy())
+        .collect();
+    if !defaults.is_empty() && arg.get_action().takes_values() && !arg.is_positional() {
+        help.push_str(&format!("[default: {}]\n", escape(&defaults.join(", "))));
+    }
+
+    help
+}
+
+/// Blank lines start a new paragraph, and fenced code blocks are kept as they are
+fn paragraphs(text: &str) -> String {
+    let mut roff = String::new();
+    let mut in_code = false;
+
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            roff.push_str(if in_code { ".fi\n" } else { ".PP\n.nf\n" });
+            in_code = !in_code;
+        } else if line.trim().is_empty() && !in_code {
+            roff.push_str(".PP\n");
+        } else {
+            roff.push_str(&escape(line));
+            roff.push('\n');
+        }
+    }
+    if in_code {
+        roff.push_str(".fi\n");
+    }
+
+    roff
+}
+
+/// Keep roff from reading text as requests or escapes
+fn escape(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+    if escaped.starts_with('.') || escaped.starts_with('\'') {
+        format!("\\&{escaped}")
+    } else {
+        escaped
+    }
+}
@@ -0,0 +1,194 @@
+use std::sync::Mutex;
+
+use ltrait_extra::scorer::Scorer;
+use ltrait_scorer_nucleo::{CaseMatching, Normalization, Pattern};
+use nucleo_matcher::{Config, Utf32Str, pattern::AtomKind};
+use serde::Deserialize;
+
+use crate::Template;
+
+/// `matcher` in the config: how the query is matched against the entries
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatcherKind {
+    /// Fuzzy, with the extended syntax of fzf (`'exact`, `^prefix`, `suffix$`, `!not`)
+    #[default]
+    Nucleo,
+    /// Every word of the query has to appear as is, smart case
+    Substring,
+}
+
+/// `matcher` in the config, either just the [`MatcherKind`] or a `[matcher]` table with the
+/// weights
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(from = "RawMatcherConfig")]
+pub struct MatcherConfig {
+    pub kind: MatcherKind,
+    /// How much a match in the name of the template adds to the score
+    pub name: f64,
+    /// How much a match in the uri of the flake adds
+    pub uri: f64,
+    /// How much a match in the description adds. The description alone doesn't make a match
+    pub description: f64,
+}
+
+impl Default for MatcherConfig {
+    fn default() -> Self {
+        Self {
+            kind: MatcherKind::default(),
+            name: 2.0,
+            uri: 1.0,
+            description: 0.5,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawMatcherConfig {
+    Kind(MatcherKind),
+    Table {
+        #[serde(default)]
+        kind: MatcherKind,
+        name: Option<f64>,
+        uri: Option<f64>,
+        description: Option<f64>,
+    },
+}
+
+impl From<RawMatcherConfig> for MatcherConfig {
+    fn from(raw: RawMatcherConfig) -> Self {
+        let default = Self::default();
+        match raw {
+            RawMatcherConfig::Kind(kind) => Self { kind, ..default },
+            RawMatcherConfig::Table {
+                kind,
+                name,
+                uri,
+                description,
+            } => Self {
+                kind,
+                name: name.unwrap_or(default.name),
+                uri: uri.unwrap_or(default.uri),
+                description: description.unwrap_or(default.description),
+            },
+        }
+    }
+}
+
+/// Scores entries according to a [`MatcherConfig`]. Smart case in all of the kinds
+pub struct Matcher {
+    config: MatcherConfig,
+    matcher: Mutex<nucleo_matcher::Matcher>,
+}
+
+impl Matcher {
+    pub fn new(config: MatcherConfig) -> Self {
+        Self {
+            config,
+            matcher: Mutex::new(nucleo_matcher::Matcher::new(Config::DEFAULT)),
+        }
+    }
+
+    /// The score of `haystack` alone, 0 if it doesn't match
+    pub fn score(&self, haystack: &str, input: &str) -> u32 {
+        let pattern = match self.config.kind {
+            MatcherKind::Nucleo => Pattern::parse(input, CaseMatching::Smart, Normalization::Smart),
+            MatcherKind::Substring => Pattern::new(
+                input,
+                CaseMatching::Smart,
+                Normalization::Smart,
+                AtomKind::Substring,
+            ),
+        };
+
+        pattern
+            .score(
+                Utf32Str::new(haystack, &mut Vec::new()),
+                &mut self.matcher.lock().unwrap(),
+            )
+            .unwrap_or(0)
+    }
+}
+
+/// Whether a template matches is up to its match string, the name, uri and description only rank
+/// the matches
+impl Scorer for Matcher {
+    type Context = Template;
+
+    fn predicate_score(&self, t: &Self::Context, input: &str) -> u32 {
+        let score = self.score(t.match_string(), input);
+        if score == 0 {
+            return 0;
+        }
+
+        let weighted = self.config.name * self.score(&t.name, input) as f64
+            + self.config.uri * self.score(&t.flake_info.uri, input) as f64
+            + self.config.description * self.score(&t.description, input) as f64;
+        score.saturating_add(weighted.max(0.0) as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(kind: MatcherKind, match_string: &str, input: &str) -> u32 {
+        Matcher::new(MatcherConfig {
+            kind,
+            ..Default::default()
+        })
+        .score(match_string, input)
+    }
+
+    #[test]
+    fn kinds() {
+        assert!(score(MatcherKind::Nucleo, "github:NixOS/templates#rust", "rst") > 0);
+        assert_eq!(
+            score(MatcherKind::Substring, "github:NixOS/templates#rust", "rst"),
+            0
+        );
+        assert!(
+            score(
+                MatcherKind::Substring,
+                "github:NixOS/templates#rust",
+                "rust nixos"
+            ) > 0
+        );
+        assert!(score(MatcherKind::Nucleo, "café", "cafe") > 0);
+    }
+
+    #[test]
+    fn names_rank_above_uris() {
+        let matcher = Matcher::new(MatcherConfig::default());
+        let context = |name: &str, uri: &str| -> Template {
+            serde_json::from_value(serde_json::json!({
+                "name": name,
+                "flake_info": { "name": null, "uri": uri },
+                "description": "",
+            }))
+            .unwrap()
+        };
+
+        let named = matcher.predicate_score(&context("rust", "github:NixOS/templates"), "rust");
+        let in_uri = matcher.predicate_score(&context("default", "github:a/rust"), "rust");
+        assert!(named > in_uri, "{named} <= {in_uri}");
+    }
+
+    #[test]
+    fn config() {
+        #[derive(Deserialize)]
+        struct Config {
+            matcher: MatcherConfig,
+        }
+
+        let kind: Config = toml::from_str(r#"matcher = "substring""#).unwrap();
+        assert_eq!(kind.matcher.kind, MatcherKind::Substring);
+        assert_eq!(kind.matcher.name, 2.0);
+
+        let table: Config = toml::from_str("[matcher]\nname = 5.0").unwrap();
+        assert_eq!(table.matcher.kind, MatcherKind::Nucleo);
+        assert_eq!(table.matcher.name, 5.0);
+        assert_eq!(table.matcher.uri, 1.0);
+    }
+}
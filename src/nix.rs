@@ -0,0 +1,562 @@
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use ltrait::color_eyre::Result;
+
+use crate::error::NitError;
+use crate::{Template, cancel};
+
+/// Which nix is in `PATH`, from `nix --version`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backend {
+    pub implementation: Implementation,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Implementation {
+    /// `nix (Nix) 2.24.10`
+    Nix,
+    /// `nix (Lix, like Nix) 2.91.1`
+    Lix,
+    /// `nix (Determinate Nix 3.0.0) 2.26.3`
+    Determinate,
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.implementation {
+            Implementation::Nix => "Nix",
+            Implementation::Lix => "Lix",
+            Implementation::Determinate => "Determinate Nix",
+        };
+        write!(f, "{name} {}", self.version)
+    }
+}
+
+impl Backend {
+    /// Parse the output of `nix --version`. Unknown implementations are treated as Nix
+    pub fn parse(version: &str) -> Option<Self> {
+        let version = version.lines().next()?.trim();
+        let (implementation, version) = match version.split_once('(') {
+            Some((_, rest)) => {
+                let (name, rest) = rest.split_once(')')?;
+                if name.starts_with("Lix") {
+                    (Implementation::Lix, rest)
+                } else if name.starts_with("Determinate") {
+                    // its own version rather than the one of the Nix it's based on
+                    (Implementation::Determinate, name)
+                } else {
+                    (Implementation::Nix, rest)
+                }
+            }
+            None => (Implementation::Nix, version),
+        };
+
+        Some(Self {
+            implementation,
+            version: version.split_whitespace().last()?.to_string(),
+        })
+    }
+
+    /// Arguments every nix command needs with this backend
+    pub fn args(&self) -> Vec<OsString> {
+        match self.implementation {
+            // flakes are stable there
+            Implementation::Determinate => vec![],
+            // experimental on both, and Lix doesn't enable them even through the installer
+            Implementation::Nix | Implementation::Lix => vec![
+                "--extra-experimental-features".into(),
+                "nix-command flakes".into(),
+            ],
+        }
+    }
+}
+
+/// `nix_command` of the config
+static COMMAND: OnceLock<Vec<String>> = OnceLock::new();
+
+/// nix through a login shell in WSL, which puts it in PATH
+const WSL: &[&str] = &["wsl.exe", "--exec", "sh", "-lc", "exec nix \"$@\"", "nix"];
+
+/// Run nix as `command` from now on, if the config says so
+pub fn set_command(command: Option<Vec<String>>) {
+    if let Some(command) = command.filter(|c| !c.is_empty()) {
+        let _ = COMMAND.set(command);
+    }
+}
+
+/// `nix`, or how `nix_command` says to run it. On Windows without a `nix` of its own, the one
+/// in WSL
+pub fn command() -> std::process::Command {
+    let command = COMMAND.get_or_init(|| {
+        let native = !cfg!(windows)
+            || crate::platform::command("nix")
+                .arg("--version")
+                .output()
+                .is_ok_and(|out| out.status.success());
+
+        if native {
+            vec!["nix".into()]
+        } else {
+            WSL.iter().map(|s| s.to_string()).collect()
+        }
+    });
+
+    let mut cmd = crate::platform::command(&command[0]);
+    cmd.args(&command[1..]);
+    cmd
+}
+
+/// The backend, probed on the first call. `None` if nix couldn't be run
+pub fn backend() -> Option<&'static Backend> {
+    static BACKEND: OnceLock<Option<Backend>> = OnceLock::new();
+
+    BACKEND
+        .get_or_init(|| {
+            let out = command().arg("--version").output().ok()?;
+            if !out.status.success() {
+                return None;
+            }
+
+            let backend = Backend::parse(&String::from_utf8_lossy(&out.stdout));
+            tracing::info!("detected nix backend: {backend:?}");
+            backend
+        })
+        .as_ref()
+}
+
+/// The nix commands nit runs, so that the cache and init can be tested against [`mock::Mock`]
+/// instead of a nix daemon. Every command gets the `args` and `envs` of the config entry of its
+/// flake, and fails with [`NitError::NixFailed`]
+pub trait NixBackend: Sync {
+    /// The output of `nix flake show --json`
+    fn flake_show(
+        &self,
+        uri: &str,
+        args: &[OsString],
+        envs: &[(String, String)],
+    ) -> impl Future<Output = Result<Vec<u8>>> + Send;
+
+    /// The output of `nix flake metadata --json`
+    fn flake_metadata(
+        &self,
+        uri: &str,
+        args: &[OsString],
+        envs: &[(String, String)],
+    ) -> impl Future<Output = Result<Vec<u8>>> + Send;
+
+    /// [`NixBackend::flake_metadata`] for callers outside of the runtime
+    fn flake_metadata_blocking(
+        &self,
+        uri: &str,
+        args: &[OsString],
+        envs: &[(String, String)],
+    ) -> Result<Vec<u8>>;
+
+    /// `nix flake init -t template_uri` in `dir`. Returns the warnings nix printed
+    fn flake_init(
+        &self,
+        template_uri: &str,
+        dir: &Path,
+        args: &[OsString],
+        envs: &[(String, String)],
+    ) -> Result<Vec<String>>;
+
+    /// The output of `nix eval --raw attr`
+    fn eval_raw(&self, attr: &str, args: &[OsString], envs: &[(String, String)]) -> Result<String>;
+
+    /// The output of `nix eval --json attr --apply apply`
+    fn eval_json(
+        &self,
+        attr: &str,
+        apply: &str,
+        args: &[OsString],
+        envs: &[(String, String)],
+    ) -> impl Future<Output = Result<Vec<u8>>> + Send;
+}
+
+/// Runs the `nix` in `PATH`
+pub struct Subprocess;
+
+impl Subprocess {
+    fn command(
+        args: &[&str],
+        arg: &str,
+        nix_args: &[OsString],
+        envs: &[(String, String)],
+    ) -> std::process::Command {
+        let mut cmd = command();
+        cmd.args(args)
+            .arg(arg)
+            .args(nix_args)
+            .envs(envs.iter().cloned());
+        // the environment only reaches nix in WSL through WSLENV
+        let keys: Vec<_> = crate::platform::env()
+            .iter()
+            .chain(envs)
+            .map(|(k, _)| k.clone())
+            .collect();
+        if cfg!(windows) && !keys.is_empty() {
+            let shared = std::env::var("WSLENV").into_iter();
+            cmd.env("WSLENV", shared.chain(keys).collect::<Vec<_>>().join(":"));
+        }
+        cmd
+    }
+
+    fn stdout(cmd: String, out: std::process::Output) -> Result<Vec<u8>> {
+        if !out.status.success() {
+            return Err(NitError::NixFailed {
+                cmd,
+                stderr: String::from_utf8(out.stderr)?,
+            }
+            .into());
+        }
+
+        Ok(out.stdout)
+    }
+}
+
+impl NixBackend for Subprocess {
+    async fn flake_show(
+        &self,
+        uri: &str,
+        args: &[OsString],
+        envs: &[(String, String)],
+    ) -> Result<Vec<u8>> {
+        let mut cmd = Self::command(&["flake", "show"], uri, args, envs);
+        cmd.args(["--json", "--no-pretty"]);
+        let out = cancel::output_async(&mut tokio::process::Command::from(cmd)).await?;
+
+        Self::stdout(format!("nix flake show {uri}"), out)
+    }
+
+    async fn flake_metadata(
+        &self,
+        uri: &str,
+        args: &[OsString],
+        envs: &[(String, String)],
+    ) -> Result<Vec<u8>> {
+        let cmd = Self::command(&["flake", "metadata", "--json"], uri, args, envs);
+        let out = cancel::output_async(&mut tokio::process::Command::from(cmd)).await?;
+
+        Self::stdout(format!("nix flake metadata {uri}"), out)
+    }
+
+    fn flake_metadata_blocking(
+        &self,
+        uri: &str,
+        args: &[OsString],
+        envs: &[(String, String)],
+    ) -> Result<Vec<u8>> {
+        let out = cancel::output(&mut Self::command(
+            &["flake", "metadata", "--json"],
+            uri,
+            args,
+            envs,
+        ))?;
+
+        Self::stdout(format!("nix flake metadata {uri}"), out)
+    }
+
+    fn flake_init(
+        &self,
+        template_uri: &str,
+        dir: &Path,
+        args: &[OsString],
+        envs: &[(String, String)],
+    ) -> Result<Vec<String>> {
+        let out = cancel::output(
+            Self::command(&["flake", "init", "-t"], template_uri, args, envs).current_dir(dir),
+        )?;
+        let warnings = warnings(&String::from_utf8_lossy(&out.stderr));
+
+        Self::stdout(format!("nix flake init -t {template_uri}"), out)?;
+        Ok(warnings)
+    }
+
+    fn eval_raw(&self, attr: &str, args: &[OsString], envs: &[(String, String)]) -> Result<String> {
+        let out = cancel::output(&mut Self::command(&["eval", "--raw"], attr, args, envs))?;
+
+        Ok(String::from_utf8(Self::stdout(
+            format!("nix eval --raw {attr}"),
+            out,
+        )?)?)
+    }
+
+    async fn eval_json(
+        &self,
+        attr: &str,
+        apply: &str,
+        args: &[OsString],
+        envs: &[(String, String)],
+    ) -> Result<Vec<u8>> {
+        let mut cmd = Self::command(&["eval", "--json"], attr, args, envs);
+        cmd.args(["--apply", apply]);
+        let out = cancel::output_async(&mut tokio::process::Command::from(cmd)).await?;
+
+        Self::stdout(format!("nix eval --json {attr}"), out)
+    }
+}
+
+/// The `warning:`s in the stderr of nix, each with its indented lines, e.g. about files
+/// `nix flake init` refused to overwrite
+fn warnings(stderr: &str) -> Vec<String> {
+    let mut warnings: Vec<String> = vec![];
+    let mut in_warning = false;
+
+    for line in stderr.lines() {
+        if let Some(warning) = line.strip_prefix("warning: ") {
+            warnings.push(warning.to_string());
+            in_warning = true;
+        } else if in_warning && line.starts_with(char::is_whitespace) && !line.trim().is_empty() {
+            if let Some(last) = warnings.last_mut() {
+                last.push('\n');
+                last.push_str(line.trim());
+            }
+        } else {
+            in_warning = false;
+        }
+    }
+
+    warnings
+}
+
+/// Whether nix failed as the server rate limited it, e.g. GitHub answering 403 once the quota of
+/// unauthenticated API requests is used up
+pub fn rate_limited(e: &ltrait::color_eyre::Report) -> bool {
+    match e.downcast_ref::<NitError>() {
+        Some(NitError::NixFailed { stderr, .. }) => {
+            ["HTTP error 429", "HTTP error 403", "rate limit"]
+                .iter()
+                .any(|p| stderr.contains(p))
+        }
+        _ => false,
+    }
+}
+
+/// The store path of the files of `t`, fetching the flake if needed
+pub fn template_path(
+    nix: &impl NixBackend,
+    t: &Template,
+    nix_args: &[OsString],
+    envs: &[(String, String)],
+) -> Result<PathBuf> {
+    let attr = format!("{}#templates.\"{}\".path", t.flake_info.uri, t.name);
+
+    Ok(PathBuf::from(nix.eval_raw(&attr, nix_args, envs)?.trim()))
+}
+
+/// [`Backend::args`] of the detected backend
+pub fn args() -> Vec<OsString> {
+    backend().map(Backend::args).unwrap_or_default()
+}
+
+#[cfg(test)]
+pub mod mock {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// Answers from canned outputs, failing like nix for anything it doesn't know
+    #[derive(Debug, Default)]
+    pub struct Mock {
+        /// `nix flake show --json` by flake uri
+        pub show: HashMap<String, String>,
+        /// `nix flake metadata --json` by flake uri
+        pub metadata: HashMap<String, String>,
+        /// The files `nix flake init` writes, by template uri
+        pub files: HashMap<String, Vec<(PathBuf, String)>>,
+        /// `nix eval --raw` by attribute
+        pub eval: HashMap<String, String>,
+        /// `nix eval --json` by `<attribute> --apply <function>`
+        pub json: HashMap<String, String>,
+        /// Flake uris whose `nix flake show` fails with HTTP error 429
+        pub rate_limited: Vec<String>,
+    }
+
+    fn answer<'a>(
+        answers: &'a HashMap<String, String>,
+        key: &str,
+        cmd: &str,
+    ) -> Result<&'a String> {
+        answers.get(key).ok_or_else(|| {
+            NitError::NixFailed {
+                cmd: format!("{cmd} {key}"),
+                stderr: format!("error: no answer for {key}"),
+            }
+            .into()
+        })
+    }
+
+    impl NixBackend for Mock {
+        async fn flake_show(
+            &self,
+            uri: &str,
+            _: &[OsString],
+            _: &[(String, String)],
+        ) -> Result<Vec<u8>> {
+            if self.rate_limited.iter().any(|u| u == uri) {
+                return Err(NitError::NixFailed {
+                    cmd: format!("nix flake show {uri}"),
+                    stderr: format!("error: unable to download '{uri}': HTTP error 429"),
+                }
+                .into());
+            }
+            Ok(answer(&self.show, uri, "nix flake show")?.clone().into())
+        }
+
+        async fn flake_metadata(
+            &self,
+            uri: &str,
+            args: &[OsString],
+            envs: &[(String, String)],
+        ) -> Result<Vec<u8>> {
+            self.flake_metadata_blocking(uri, args, envs)
+        }
+
+        fn flake_metadata_blocking(
+            &self,
+            uri: &str,
+            _: &[OsString],
+            _: &[(String, String)],
+        ) -> Result<Vec<u8>> {
+            Ok(answer(&self.metadata, uri, "nix flake metadata")?
+                .clone()
+                .into())
+        }
+
+        fn flake_init(
+            &self,
+            template_uri: &str,
+            dir: &Path,
+            _: &[OsString],
+            _: &[(String, String)],
+        ) -> Result<Vec<String>> {
+            let files = self
+                .files
+                .get(template_uri)
+                .ok_or_else(|| NitError::NixFailed {
+                    cmd: format!("nix flake init -t {template_uri}"),
+                    stderr: format!("error: no template {template_uri}"),
+                })?;
+            for (path, content) in files {
+                let path = dir.join(path);
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(path, content)?;
+            }
+
+            Ok(vec![])
+        }
+
+        fn eval_raw(&self, attr: &str, _: &[OsString], _: &[(String, String)]) -> Result<String> {
+            answer(&self.eval, attr, "nix eval --raw").cloned()
+        }
+
+        async fn eval_json(
+            &self,
+            attr: &str,
+            apply: &str,
+            _: &[OsString],
+            _: &[(String, String)],
+        ) -> Result<Vec<u8>> {
+            let key = format!("{attr} --apply {apply}");
+            Ok(answer(&self.json, &key, "nix eval --json")?.clone().into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::Mock;
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let parse = |v| {
+            let backend = Backend::parse(v).unwrap();
+            (backend.implementation, backend.version)
+        };
+
+        assert_eq!(
+            parse("nix (Nix) 2.24.10\n"),
+            (Implementation::Nix, "2.24.10".into())
+        );
+        assert_eq!(
+            parse("nix (Lix, like Nix) 2.91.1"),
+            (Implementation::Lix, "2.91.1".into())
+        );
+        assert_eq!(
+            parse("nix (Determinate Nix 3.0.0) 2.26.3"),
+            (Implementation::Determinate, "3.0.0".into())
+        );
+        assert!(Backend::parse("").is_none());
+    }
+
+    #[test]
+    fn init_warnings() {
+        let stderr = "\
+warning: Git tree '/home/a/p' is dirty
+wrote: /home/a/p/flake.nix
+warning: refusing to overwrite existing file '/home/a/p/.envrc'
+ please merge it manually with '/nix/store/abc-source/rust/.envrc'
+error: something else
+ with more";
+
+        assert_eq!(
+            warnings(stderr),
+            [
+                "Git tree '/home/a/p' is dirty",
+                "refusing to overwrite existing file '/home/a/p/.envrc'\n\
+                 please merge it manually with '/nix/store/abc-source/rust/.envrc'",
+            ]
+        );
+        assert!(warnings("").is_empty());
+    }
+
+    #[test]
+    fn rate_limits() {
+        let failed = |stderr: &str| -> ltrait::color_eyre::Report {
+            NitError::NixFailed {
+                cmd: "nix flake show github:x/y".into(),
+                stderr: stderr.into(),
+            }
+            .into()
+        };
+
+        assert!(rate_limited(&failed(
+            "error: unable to download 'https://api.github.com/repos/x/y/commits/HEAD': HTTP error 403"
+        )));
+        assert!(rate_limited(&failed("error: HTTP error 429")));
+        assert!(!rate_limited(&failed("error: HTTP error 404")));
+        assert!(!rate_limited(&ltrait::color_eyre::eyre::eyre!(
+            "HTTP error 429"
+        )));
+    }
+
+    #[test]
+    fn template_path_through_eval() {
+        let t: Template = serde_json::from_value(serde_json::json!({
+            "name": "rust",
+            "flake_info": { "name": null, "uri": "github:x/y" },
+            "description": "",
+            "tags": [],
+        }))
+        .unwrap();
+        let mut nix = Mock::default();
+        nix.eval.insert(
+            r#"github:x/y#templates."rust".path"#.into(),
+            "/nix/store/abc-source/rust\n".into(),
+        );
+
+        assert_eq!(
+            template_path(&nix, &t, &[], &[]).unwrap(),
+            PathBuf::from("/nix/store/abc-source/rust")
+        );
+        assert!(template_path(&Mock::default(), &t, &[], &[]).is_err());
+    }
+}
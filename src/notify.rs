@@ -0,0 +1,61 @@
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+/// Shorter operations are still in view when they end
+const AFTER: Duration = Duration::from_secs(5);
+
+/// Sends [`finished`] once dropped, with `done` only if [`Long::succeeded`] was called first, so
+/// every early return reports `failed`
+pub struct Long {
+    started: Instant,
+    /// `notifications` in the config
+    enabled: bool,
+    done: String,
+    failed: String,
+    success: bool,
+}
+
+impl Long {
+    pub fn start(enabled: bool, done: String, failed: String) -> Self {
+        Self {
+            started: Instant::now(),
+            enabled,
+            done,
+            failed,
+            success: false,
+        }
+    }
+
+    pub fn succeeded(mut self) {
+        self.success = true;
+    }
+}
+
+impl Drop for Long {
+    fn drop(&mut self) {
+        let summary = if self.success {
+            &self.done
+        } else {
+            &self.failed
+        };
+        finished(self.started, self.enabled, summary);
+    }
+}
+
+/// Notify the desktop with `summary` if `enabled` and more than [`AFTER`] passed since `started`,
+/// through `notify-send`. Nothing happens where there is none
+pub fn finished(started: Instant, enabled: bool, summary: &str) {
+    if !enabled || started.elapsed() < AFTER {
+        return;
+    }
+
+    let sent = crate::platform::command("notify-send")
+        .args(["--app-name", "nit", summary])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+
+    if let Err(e) = sent {
+        tracing::warn!("failed to run notify-send: {e}");
+    }
+}
@@ -0,0 +1,125 @@
+use std::io::Write as _;
+
+use ltrait::color_eyre::Result;
+
+/// `--output` of the non-interactive subcommands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Output {
+    /// Aligned columns with a header
+    Table,
+    /// RFC 4180, with a header
+    Csv,
+    /// Tab separated with a header. Tabs, newlines and backslashes in values are escaped as `\t`,
+    /// `\n` and `\\`
+    Tsv,
+    /// Like `--json`
+    Json,
+}
+
+/// Print `rows` under `header`. JSON is an object per line, keyed by the header
+pub fn print_rows(output: Output, header: &[&str], rows: &[Vec<String>]) -> Result<()> {
+    let header: Vec<String> = header.iter().map(|h| h.to_string()).collect();
+    let lines: Vec<String> = match output {
+        Output::Table => {
+            let widths: Vec<usize> = (0..header.len())
+                .map(|i| {
+                    std::iter::once(&header)
+                        .chain(rows)
+                        .map(|row| row.get(i).map_or(0, |v| v.chars().count()))
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect();
+
+            std::iter::once(&header)
+                .chain(rows)
+                .map(|row| {
+                    let cells: Vec<String> = row
+                        .iter()
+                        .zip(&widths)
+                        .map(|(value, width)| format!("{value:width$}"))
+                        .collect();
+                    cells.join("  ").trim_end().to_string()
+                })
+                .collect()
+        }
+        Output::Csv => std::iter::once(&header)
+            .chain(rows)
+            .map(|row| {
+                row.iter()
+                    .map(|v| csv_field(v))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .collect(),
+        Output::Tsv => std::iter::once(&header)
+            .chain(rows)
+            .map(|row| {
+                row.iter()
+                    .map(|v| tsv_field(v))
+                    .collect::<Vec<_>>()
+                    .join("\t")
+            })
+            .collect(),
+        // one object per row, for callers without a richer shape
+        Output::Json => rows
+            .iter()
+            .map(|row| {
+                let object: serde_json::Map<String, serde_json::Value> = header
+                    .iter()
+                    .cloned()
+                    .zip(row.iter().map(|v| v.clone().into()))
+                    .collect();
+                serde_json::Value::Object(object).to_string()
+            })
+            .collect(),
+    };
+
+    print_lines(lines)
+}
+
+/// One line each, stopping quietly when the reader closed the pipe like `nit list | head`
+pub fn print_lines(lines: impl IntoIterator<Item = String>) -> Result<()> {
+    let mut out = std::io::stdout().lock();
+
+    for line in lines {
+        match writeln!(out, "{line}") {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn tsv_field(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaping() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a, b"), "\"a, b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+
+        assert_eq!(tsv_field("a\tb\nc\\d"), "a\\tb\\nc\\\\d");
+    }
+}
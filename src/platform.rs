@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// The terminal the TUI draws on even when stdout is piped: `/dev/tty`, or the console buffer on
+/// Windows
+pub const TTY: &str = if cfg!(windows) { "CONOUT$" } else { "/dev/tty" };
+
+/// Where the config lives: `$XDG_CONFIG_HOME`, which is also honored on Windows for setups shared
+/// with WSL, or the platform's own, e.g. `%APPDATA%`
+pub fn config_dir() -> Option<PathBuf> {
+    xdg("XDG_CONFIG_HOME").or_else(dirs::config_dir)
+}
+
+/// `$XDG_CACHE_HOME`, or e.g. `%LOCALAPPDATA%`
+pub fn cache_dir() -> Option<PathBuf> {
+    xdg("XDG_CACHE_HOME").or_else(dirs::cache_dir)
+}
+
+/// `$XDG_STATE_HOME`, or the data directory on platforms without a state directory
+pub fn state_dir() -> Option<PathBuf> {
+    xdg("XDG_STATE_HOME")
+        .or_else(dirs::state_dir)
+        .or_else(dirs::data_dir)
+}
+
+/// `dirs` already reads these where XDG is the convention
+fn xdg(var: &str) -> Option<PathBuf> {
+    if !cfg!(windows) {
+        return None;
+    }
+
+    std::env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+}
+
+/// `env` of the config, expanded
+static ENV: OnceLock<Vec<(String, String)>> = OnceLock::new();
+
+/// Set `env` for every process run from now on, `${VAR}` expanded against nit's own environment
+pub fn set_env(env: &BTreeMap<String, String>) {
+    let _ = ENV.set(
+        env.iter()
+            .map(|(k, v)| {
+                let value = crate::config::expand_vars(v, |var| std::env::var(var).ok());
+                (k.clone(), value)
+            })
+            .collect(),
+    );
+}
+
+/// `env` of the config, which [`command`] sets
+pub fn env() -> &'static [(String, String)] {
+    ENV.get().map(Vec::as_slice).unwrap_or_default()
+}
+
+/// `program` with `env` of the config set. Every process nit runs starts from here
+pub fn command(program: impl AsRef<std::ffi::OsStr>) -> Command {
+    let mut cmd = Command::new(program);
+    cmd.envs(env().iter().map(|(k, v)| (k, v)));
+    cmd
+}
+
+/// `command` run by `sh -c`, or `cmd /C` on Windows
+pub fn shell(command: &str) -> Command {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+
+    let mut cmd = self::command(shell);
+    cmd.arg(flag).arg(command);
+    cmd
+}
+
+/// Start `cmd` in a process group of its own, for [`kill_group`]
+pub fn new_group(cmd: &mut Command) -> &mut Command {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt as _;
+        cmd.process_group(0)
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt as _;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        cmd.creation_flags(CREATE_NEW_PROCESS_GROUP)
+    }
+}
+
+/// Terminate the process started by [`new_group`] with `id`, and everything it spawned
+pub fn kill_group(id: u32) {
+    #[cfg(unix)]
+    // SAFETY: killpg has no memory safety preconditions
+    unsafe {
+        libc::killpg(id as libc::pid_t, libc::SIGTERM);
+    }
+    #[cfg(windows)]
+    {
+        let _ = Command::new("taskkill")
+            .args(["/T", "/F", "/PID", &id.to_string()])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status();
+    }
+}
+
+/// A symlink at `link` to `target`. Windows tells links to directories from links to files, and
+/// `target` is resolved against the directory of `link` like the OS does
+pub fn symlink(target: &Path, link: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(target, link)
+    }
+    #[cfg(windows)]
+    {
+        let resolved = link.parent().unwrap_or(Path::new(".")).join(target);
+        if resolved.is_dir() {
+            std::os::windows::fs::symlink_dir(target, link)
+        } else {
+            std::os::windows::fs::symlink_file(target, link)
+        }
+    }
+}
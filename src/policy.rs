@@ -0,0 +1,91 @@
+use serde::Deserialize;
+
+use crate::error::NitError;
+
+/// `[policy]` in the config: which flake uris nit may run nix against. `*` in a pattern matches
+/// any run of characters, including `/`
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct Policy {
+    /// Only these are allowed when not empty
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// Never allowed, even when also in `allow`
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+impl Policy {
+    /// Refuse `uri` before anything is fetched from it
+    pub fn check(&self, uri: &str) -> Result<(), NitError> {
+        if let Some(rule) = self.deny.iter().find(|rule| matches(rule, uri)) {
+            return Err(NitError::Denied {
+                uri: uri.to_string(),
+                rule: Some(rule.clone()),
+            });
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|rule| matches(rule, uri)) {
+            return Err(NitError::Denied {
+                uri: uri.to_string(),
+                rule: None,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+fn matches(pattern: &str, uri: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // without a `*` the first part is the whole pattern
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = uri.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut parts: Vec<_> = parts.collect();
+    let Some(last) = parts.pop() else {
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns() {
+        assert!(matches("github:myorg/*", "github:myorg/templates"));
+        assert!(matches("github:myorg/*", "github:myorg/templates?dir=rust"));
+        assert!(!matches("github:myorg/*", "github:other/templates"));
+        assert!(matches("git+http://*", "git+http://example.com/repo"));
+        assert!(!matches("git+http://*", "git+https://example.com/repo"));
+        assert!(matches("*/templates", "github:NixOS/templates"));
+        assert!(matches(
+            "github:*/templates*",
+            "github:NixOS/templates/main"
+        ));
+        assert!(matches("github:a/b", "github:a/b"));
+        assert!(!matches("github:a/b", "github:a/bc"));
+        assert!(!matches("a*bc*c", "abc"));
+    }
+
+    #[test]
+    fn deny_wins() {
+        let policy = Policy {
+            allow: vec!["github:myorg/*".into()],
+            deny: vec!["github:myorg/secret".into()],
+        };
+
+        assert!(policy.check("github:myorg/templates").is_ok());
+        assert!(policy.check("github:myorg/secret").is_err());
+        assert!(policy.check("github:NixOS/templates").is_err());
+        assert!(Policy::default().check("github:NixOS/templates").is_ok());
+    }
+}
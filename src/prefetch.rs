@@ -0,0 +1,92 @@
+use std::collections::BTreeSet;
+
+use futures::StreamExt as _;
+use ltrait::color_eyre::{Result, eyre::bail};
+use tokio::process::Command;
+
+use crate::Template;
+use crate::cancel;
+use crate::config::Config;
+use crate::locale::tr;
+
+/// Copy the flakes of the cached templates and their inputs into the nix store with
+/// `nix flake archive`, so they can be initialized without network access later
+pub async fn run(
+    templates: impl IntoIterator<Item = Template>,
+    profile: Option<&str>,
+    impure: bool,
+) -> Result<()> {
+    let mut config = Config::load()?;
+    if let Some(profile) = profile {
+        config.use_profile(profile)?;
+    }
+    if impure {
+        config.force_impure();
+    }
+
+    let uris: BTreeSet<String> = templates
+        .into_iter()
+        .filter(|t| t.flake_info.source_type.is_flake())
+        .map(|t| t.flake_info.config_uri().to_string())
+        .collect();
+    for uri in &uris {
+        config.policy.check(uri)?;
+    }
+
+    let failed = futures::stream::iter(&uris)
+        .map(|uri| archive(&config, uri))
+        .buffer_unordered(config.performance.fetch_concurrency.max(1))
+        .filter(|fetched| std::future::ready(!fetched))
+        .count()
+        .await;
+
+    if failed > 0 {
+        bail!(tr("prefetch-failed", &[("count", &failed)]));
+    }
+
+    Ok(())
+}
+
+/// `false` if it failed, which is reported right away
+async fn archive(config: &Config, uri: &str) -> bool {
+    let (nix_args, envs) = config
+        .flake(uri)
+        .map(|c| (c.nix_args(), c.envs()))
+        .unwrap_or_else(|| (crate::nix::args(), vec![]));
+
+    let out = cancel::output_async(
+        Command::from(crate::nix::command())
+            .args(["flake", "archive"])
+            .arg(uri)
+            .args(nix_args)
+            .envs(envs),
+    )
+    .await;
+
+    match out {
+        Ok(out) if out.status.success() => {
+            eprintln!("{}", tr("prefetch-fetched", &[("uri", &uri)]));
+            true
+        }
+        Ok(out) => {
+            eprintln!(
+                "{}",
+                tr(
+                    "prefetch-fetch-failed",
+                    &[
+                        ("uri", &uri),
+                        ("error", &String::from_utf8_lossy(&out.stderr).trim())
+                    ]
+                )
+            );
+            false
+        }
+        Err(e) => {
+            eprintln!(
+                "{}",
+                tr("prefetch-fetch-failed", &[("uri", &uri), ("error", &e)])
+            );
+            false
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use ltrait::color_eyre::{Result, eyre::bail};
+
+use crate::locale::tr;
+use crate::prompt;
+
+/// What initializing the template files in `source` into `target` would do
+pub struct Changes {
+    /// Relative to both directories
+    pub new: Vec<PathBuf>,
+    /// Existing files with a different content in the template
+    pub modified: Vec<PathBuf>,
+}
+
+impl Changes {
+    pub fn new(source: &Path, target: &Path) -> Result<Self> {
+        let mut changes = Self {
+            new: vec![],
+            modified: vec![],
+        };
+
+        let mut dirs = vec![PathBuf::new()];
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(source.join(&dir))? {
+                let entry = entry?;
+                let relative = dir.join(entry.file_name());
+
+                if entry.file_type()?.is_dir() {
+                    dirs.push(relative);
+                    continue;
+                }
+
+                let existing = target.join(&relative);
+                if existing.symlink_metadata().is_err() {
+                    changes.new.push(relative);
+                } else if contents(&existing)? != contents(&entry.path())? {
+                    changes.modified.push(relative);
+                }
+            }
+        }
+
+        changes.new.sort();
+        changes.modified.sort();
+
+        Ok(changes)
+    }
+
+    /// The new files, and a unified diff of the modified ones
+    pub fn render(&self, source: &Path, target: &Path) -> Result<String> {
+        let mut out = String::new();
+
+        for file in &self.new {
+            out.push_str(&tr("preview-new-file", &[("path", &file.display())]));
+            out.push('\n');
+        }
+
+        for file in &self.modified {
+            let label = file.display().to_string();
+            let diff = crate::platform::command("diff")
+                .arg("-u")
+                .args(["--label", &format!("a/{label}")])
+                .args(["--label", &format!("b/{label}")])
+                .arg(target.join(file))
+                .arg(source.join(file))
+                .output()?;
+            out.push_str(&String::from_utf8_lossy(&diff.stdout));
+        }
+
+        Ok(out)
+    }
+}
+
+/// The bytes of a file, or where a symlink points to
+pub fn contents(path: &Path) -> Result<Vec<u8>> {
+    if path.symlink_metadata()?.is_symlink() {
+        Ok(std::fs::read_link(path)?
+            .into_os_string()
+            .into_encoded_bytes())
+    } else {
+        Ok(std::fs::read(path)?)
+    }
+}
+
+/// Show what the templates in `sources` would change in a non-empty `target`, and ask for
+/// confirmation once when existing files would change unless `yes` agreed already, failing if the
+/// user can't be asked. The files of `target` the user agreed to replace, `None` if declined
+pub fn confirm(
+    sources: &[PathBuf],
+    target: &Path,
+    can_ask: bool,
+    yes: bool,
+) -> Result<Option<Vec<PathBuf>>> {
+    let empty = std::fs::read_dir(target)?
+        .filter_map(|e| e.ok())
+        .all(|e| e.file_name() == ".git");
+    if empty {
+        return Ok(Some(vec![]));
+    }
+
+    let mut rendered = String::new();
+    let mut modified = vec![];
+    for source in sources {
+        let changes = Changes::new(source, target)?;
+        rendered.push_str(&changes.render(source, target)?);
+        modified.extend(changes.modified.iter().map(|f| target.join(f)));
+    }
+    modified.sort();
+    modified.dedup();
+
+    if modified.is_empty() {
+        eprint!("{rendered}");
+        return Ok(Some(vec![]));
+    }
+    if yes {
+        eprint!("{rendered}");
+        return Ok(Some(modified));
+    }
+
+    if !can_ask {
+        bail!(tr("preview-no-terminal", &[("count", &modified.len())]));
+    }
+
+    page(&rendered)?;
+    let answer = prompt::ask(&tr("prompt-changes", &[("count", &modified.len())]))?;
+    if !matches!(answer.to_lowercase().as_str(), "y" | "yes") {
+        return Ok(None);
+    }
+
+    Ok(Some(modified))
+}
+
+/// Show `text` in `$PAGER`, or `less`
+fn page(text: &str) -> Result<()> {
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| "less -R".into());
+
+    let child = crate::platform::shell(&pager).stdin(Stdio::piped()).spawn();
+
+    match child {
+        Ok(mut child) => {
+            if let Some(mut stdin) = child.stdin.take() {
+                // the pager may quit before reading everything
+                let _ = stdin.write_all(text.as_bytes());
+            }
+            child.wait()?;
+        }
+        Err(_) => eprint!("{text}"),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yes_replaces_without_asking() {
+        let dir = std::env::temp_dir().join(format!("nit-preview-test-{}", std::process::id()));
+        let (source, target) = (dir.join("source"), dir.join("target"));
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::create_dir_all(&target).unwrap();
+        std::fs::write(source.join("flake.nix"), "{ new }").unwrap();
+        std::fs::write(target.join("flake.nix"), "{ old }").unwrap();
+        let sources = [source];
+
+        assert!(confirm(&sources, &target, false, false).is_err());
+        assert_eq!(
+            confirm(&sources, &target, false, true).unwrap(),
+            Some(vec![target.join("flake.nix")])
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
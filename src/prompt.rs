@@ -0,0 +1,18 @@
+use std::io::Write as _;
+
+use ltrait::color_eyre::{Result, eyre::ensure};
+
+use crate::locale::tr;
+
+/// Print `message` and read one trimmed line from stdin. Fails once stdin is closed, as no answer
+/// is going to come anymore
+pub fn ask(message: &str) -> Result<String> {
+    eprint!("{message}");
+    std::io::stderr().flush()?;
+
+    let mut line = String::new();
+    let read = std::io::stdin().read_line(&mut line)?;
+    ensure!(read != 0, tr("error-stdin-closed", &[]));
+
+    Ok(line.trim().to_string())
+}
@@ -0,0 +1,80 @@
+use std::ffi::OsString;
+use std::path::Path;
+
+use ltrait::color_eyre::Result;
+
+use crate::Template;
+use crate::nix::NixBackend;
+
+/// Whether `dir` has a README of any format
+pub fn exists(dir: &Path) -> Result<bool> {
+    for entry in std::fs::read_dir(dir)? {
+        if entry?
+            .file_name()
+            .to_string_lossy()
+            .to_uppercase()
+            .starts_with("README")
+        {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Write `dir/README.md` with `project` as the title, the description of `t`, and its
+/// `welcomeText` below with the headings nested under the title
+pub fn write(dir: &Path, project: &str, t: &Template, welcome_text: Option<&str>) -> Result<()> {
+    let mut readme = format!("# {project}\n");
+
+    if !t.description.is_empty() {
+        readme.push_str(&format!("\n{}\n", t.description));
+    }
+
+    if let Some(welcome_text) = welcome_text {
+        readme.push('\n');
+        readme.push_str(&nest_headings(welcome_text));
+        if !readme.ends_with('\n') {
+            readme.push('\n');
+        }
+    }
+
+    std::fs::write(dir.join("README.md"), readme)?;
+
+    Ok(())
+}
+
+/// Add a level to every ATX heading outside of code blocks
+fn nest_headings(markdown: &str) -> String {
+    let mut in_code = false;
+
+    markdown
+        .lines()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+                in_code = !in_code;
+            }
+
+            if !in_code && trimmed.starts_with('#') {
+                format!("#{trimmed}")
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The `welcomeText` of the template, which `nix flake show` leaves out
+pub fn welcome_text(
+    nix: &impl NixBackend,
+    t: &Template,
+    nix_args: &[OsString],
+    envs: &[(String, String)],
+) -> Option<String> {
+    let attr = format!("{}#templates.\"{}\".welcomeText", t.flake_info.uri, t.name);
+    let text = nix.eval_raw(&attr, nix_args, envs).ok()?;
+
+    (!text.trim().is_empty()).then_some(text)
+}
@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+
+use ltrait::color_eyre::{Result, eyre::ContextCompat};
+
+/// `<state dir>/nix-nit/query`, or `query-<profile>` as every profile has its own
+fn query_path(profile: Option<&str>) -> Result<PathBuf> {
+    let file = match profile {
+        Some(profile) => format!("query-{profile}"),
+        None => "query".into(),
+    };
+
+    Ok(crate::platform::state_dir()
+        .wrap_err("State directory doesn't exist.")?
+        .join("nix-nit")
+        .join(file))
+}
+
+/// Remember what was typed when the TUI closed, for `--resume`
+pub fn save(profile: Option<&str>, query: &str) -> Result<()> {
+    let path = query_path(profile)?;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, query)?;
+
+    Ok(())
+}
+
+/// The query of the last TUI, `None` if there was none
+pub fn load(profile: Option<&str>) -> Option<String> {
+    std::fs::read_to_string(query_path(profile).ok()?).ok()
+}
@@ -0,0 +1,200 @@
+use std::cmp::Ordering;
+
+use ltrait::color_eyre::{
+    Result,
+    eyre::{ContextCompat, ensure, eyre},
+};
+use serde::Deserialize;
+
+use crate::download::{curl, sha256};
+use crate::locale::tr;
+
+const LATEST_RELEASE: &str = "https://api.github.com/repos/satler-git/nit/releases/latest";
+
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// Replace the running executable with the binary of the latest GitHub release.
+/// The release has to ship `nit-<arch>-<os>` and `nit-<arch>-<os>.sha256`, with `.exe` before
+/// `.sha256` on Windows
+pub fn run(check: bool) -> Result<()> {
+    let current = std::env::current_exe()?.canonicalize()?;
+    ensure!(
+        !current.starts_with("/nix/store"),
+        tr("update-nix-store", &[("path", &current.display())])
+    );
+
+    let release: Release = serde_json::from_slice(&curl(&[LATEST_RELEASE])?)?;
+    let latest = release.tag_name.trim_start_matches('v');
+    let running = env!("CARGO_PKG_VERSION");
+
+    let newer = Version::parse(latest)
+        .ok_or_else(|| eyre!(tr("update-bad-version", &[("version", &release.tag_name)])))?
+        > Version::parse(running)
+            .wrap_err_with(|| tr("update-bad-version", &[("version", &running)]))?;
+    if !newer {
+        println!("{}", tr("update-latest", &[("version", &running)]));
+        return Ok(());
+    }
+    println!(
+        "{}",
+        tr(
+            "update-available",
+            &[("version", &latest), ("running", &running)]
+        )
+    );
+    if check {
+        return Ok(());
+    }
+
+    let name = format!(
+        "nit-{}-{}{}",
+        std::env::consts::ARCH,
+        std::env::consts::OS,
+        std::env::consts::EXE_SUFFIX
+    );
+    let asset = |name: &str| {
+        release
+            .assets
+            .iter()
+            .find(|a| a.name == name)
+            .wrap_err_with(|| tr("update-no-asset", &[("name", &name)]))
+    };
+    let binary = asset(&name)?;
+    let checksum = asset(&format!("{name}.sha256"))?;
+
+    let expected = String::from_utf8(curl(&[&checksum.browser_download_url])?)?;
+    let expected = expected
+        .split_whitespace()
+        .next()
+        .wrap_err_with(|| tr("update-empty-checksum", &[]))?;
+
+    // next to the executable, so that the rename below doesn't cross file systems
+    let download = current.with_file_name(format!(".{name}.download"));
+    let downloaded = curl(&[
+        &binary.browser_download_url,
+        "-o",
+        &download.to_string_lossy(),
+    ]);
+    let verified = downloaded.and_then(|_| {
+        let actual = sha256(&download)?;
+        ensure!(
+            actual.eq_ignore_ascii_case(expected),
+            tr(
+                "checksum-mismatch",
+                &[
+                    ("url", &binary.browser_download_url),
+                    ("expected", &expected),
+                    ("actual", &actual)
+                ]
+            )
+        );
+        Ok(())
+    });
+    if let Err(e) = verified {
+        let _ = std::fs::remove_file(&download);
+        return Err(e);
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt as _;
+        std::fs::set_permissions(&download, std::fs::Permissions::from_mode(0o755))?;
+    }
+    // a running executable can't be replaced on Windows, only renamed
+    #[cfg(windows)]
+    {
+        let old = current.with_file_name(format!(".{name}.old"));
+        let _ = std::fs::remove_file(&old);
+        std::fs::rename(&current, &old)?;
+    }
+    std::fs::rename(&download, &current)?;
+
+    println!("{}", tr("update-done", &[("version", &latest)]));
+
+    Ok(())
+}
+
+/// A semantic version, `major.minor.patch` with an optional `-pre-release`. Build metadata after
+/// `+` doesn't take part in comparisons
+#[derive(Debug, PartialEq, Eq)]
+struct Version<'a> {
+    core: [u64; 3],
+    pre: Option<&'a str>,
+}
+
+impl<'a> Version<'a> {
+    fn parse(version: &'a str) -> Option<Self> {
+        let version = version.split_once('+').map_or(version, |(v, _)| v);
+        let (core, pre) = match version.split_once('-') {
+            Some((core, pre)) => (core, Some(pre)),
+            None => (version, None),
+        };
+
+        let mut parts = core.split('.').map(|p| p.parse().ok());
+        let core = [parts.next()??, parts.next()??, parts.next()??];
+        if parts.next().is_some() || pre.is_some_and(str::is_empty) {
+            return None;
+        }
+
+        Some(Version { core, pre })
+    }
+}
+
+impl Ord for Version<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.core
+            .cmp(&other.core)
+            .then_with(|| match (self.pre, other.pre) {
+                (None, None) => Ordering::Equal,
+                // a pre-release comes before the release
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => {
+                    let identifier = |i: &str| i.parse::<u64>().map_err(|_| i.to_string());
+                    // numeric identifiers are lower than alphanumeric ones, as `Ok < Err`
+                    a.split('.')
+                        .map(identifier)
+                        .cmp(b.split('.').map(identifier))
+                }
+            })
+    }
+}
+
+impl PartialOrd for Version<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions() {
+        let v = |s| Version::parse(s).unwrap();
+
+        assert!(v("0.10.0") > v("0.9.1"));
+        assert!(v("1.0.0") > v("1.0.0-rc.1"));
+        assert!(v("1.0.0-rc.2") > v("1.0.0-rc.1"));
+        assert!(v("1.0.0-rc.10") > v("1.0.0-rc.9"));
+        assert!(v("1.0.0-beta") > v("1.0.0-alpha.1"));
+        assert!(v("1.0.0-alpha.beta") > v("1.0.0-alpha.1"));
+        assert_eq!(v("1.2.3+build.5"), v("1.2.3"));
+        assert!(v("0.1.0") < v("0.1.1"));
+        assert!(Version::parse("1.2").is_none());
+        assert!(Version::parse("1.2.3.4").is_none());
+        assert!(Version::parse("1.2.3-").is_none());
+        assert!(Version::parse("latest").is_none());
+    }
+}
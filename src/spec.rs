@@ -0,0 +1,168 @@
+//! `.nit.toml`, the prompt spec a template can ship to get parameterized after init
+//!
+//! ```toml
+//! [[prompt]]
+//! name = "project_name"
+//! message = "Project name" # optional. defaults to name
+//! type = "string" # optional. "string", "bool" or "choice"
+//! default = "my-project" # optional
+//! choices = ["..."] # for "choice"
+//! replace = "my-project" # optional. the string replaced with the answer. defaults to {{name}}
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use ltrait::color_eyre::{
+    Result,
+    eyre::{bail, ensure},
+};
+use serde::Deserialize;
+
+use crate::locale::tr;
+use crate::prompt;
+
+pub const FILE_NAME: &str = ".nit.toml";
+
+#[derive(Debug, Deserialize)]
+struct Spec {
+    #[serde(default)]
+    prompt: Vec<Prompt>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Prompt {
+    name: String,
+    message: Option<String>,
+    #[serde(rename = "type", default)]
+    prompt_type: PromptType,
+    default: Option<toml::Value>,
+    #[serde(default)]
+    choices: Vec<String>,
+    replace: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+enum PromptType {
+    #[default]
+    String,
+    Bool,
+    Choice,
+}
+
+impl Prompt {
+    fn default_value(&self) -> Option<String> {
+        self.default.as_ref().map(|v| match v {
+            toml::Value::String(s) => s.clone(),
+            v => v.to_string(),
+        })
+    }
+
+    fn ask(&self) -> Result<String> {
+        let message = self.message.as_deref().unwrap_or(&self.name);
+        let default = self.default_value();
+
+        loop {
+            let answer = match self.prompt_type {
+                PromptType::String => prompt::ask(&match &default {
+                    Some(d) => format!("{message} [{d}]: "),
+                    None => format!("{message}: "),
+                })?,
+                PromptType::Bool => {
+                    let answer = prompt::ask(&format!(
+                        "{message} [{}]: ",
+                        if default.as_deref() == Some("true") {
+                            "Y/n"
+                        } else {
+                            "y/N"
+                        }
+                    ))?;
+                    match answer.to_lowercase().as_str() {
+                        "y" | "yes" => "true".into(),
+                        "n" | "no" => "false".into(),
+                        _ => answer,
+                    }
+                }
+                PromptType::Choice => {
+                    for (i, choice) in self.choices.iter().enumerate() {
+                        eprintln!("{}) {choice}", i + 1);
+                    }
+                    let answer = prompt::ask(&match &default {
+                        Some(d) => format!("{message} [{d}]: "),
+                        None => format!("{message}: "),
+                    })?;
+                    match answer.parse::<usize>() {
+                        Ok(i) if (1..=self.choices.len()).contains(&i) => {
+                            self.choices[i - 1].clone()
+                        }
+                        _ => answer,
+                    }
+                }
+            };
+
+            let answer = match (answer.is_empty(), &default) {
+                (true, Some(d)) => d.clone(),
+                _ => answer,
+            };
+
+            let valid = match self.prompt_type {
+                PromptType::String => !answer.is_empty(),
+                PromptType::Bool => answer == "true" || answer == "false",
+                PromptType::Choice => self.choices.contains(&answer),
+            };
+
+            if valid {
+                return Ok(answer);
+            }
+            eprintln!("Invalid answer `{answer}`");
+        }
+    }
+}
+
+/// If `target` has a spec, ask its questions, substitute the answers in `files` and delete it.
+/// Returns the path of the deleted spec. Fails without asking unless `can_ask`, e.g. for `--stdio`
+pub fn apply(target: &Path, files: &[PathBuf], can_ask: bool) -> Result<Option<PathBuf>> {
+    let path = target.join(FILE_NAME);
+    if !files.contains(&path) {
+        // only a spec that came with the template counts
+        return Ok(None);
+    }
+
+    let spec: Spec = toml::from_str(&std::fs::read_to_string(&path)?)?;
+
+    ensure!(
+        spec.prompt.is_empty() || can_ask,
+        tr("error-spec-no-terminal", &[("file", &FILE_NAME)])
+    );
+
+    let mut replacements = Vec::with_capacity(spec.prompt.len());
+    for p in &spec.prompt {
+        if matches!(p.prompt_type, PromptType::Choice) && p.choices.is_empty() {
+            bail!("prompt `{}` in {FILE_NAME} has no choices", p.name);
+        }
+
+        let from = p
+            .replace
+            .clone()
+            .unwrap_or_else(|| format!("{{{{{}}}}}", p.name));
+        replacements.push((from, p.ask()?));
+    }
+
+    std::fs::remove_file(&path)?;
+
+    for file in files.iter().filter(|f| **f != path) {
+        let Ok(content) = std::fs::read_to_string(file) else {
+            continue;
+        };
+
+        let replaced = replacements
+            .iter()
+            .fold(content.clone(), |acc, (from, to)| acc.replace(from, to));
+
+        if replaced != content {
+            std::fs::write(file, replaced)?;
+        }
+    }
+
+    Ok(Some(path))
+}
@@ -0,0 +1,247 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use ltrait::color_eyre::Result;
+use rusqlite::{Connection, OptionalExtension as _};
+
+use crate::Template;
+use crate::cache::{self, CacheStore, Header};
+
+/// How many templates [`SqliteTemplates`] reads at once
+const PAGE: i64 = 512;
+
+/// `store = "sqlite"`: the templates in a database indexed by uri and name, so that catalogs of
+/// thousands of templates are read a page at a time and completion doesn't scan all of them
+pub struct SqliteCache {
+    path: PathBuf,
+}
+
+impl SqliteCache {
+    pub fn new(profile: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            path: cache::file(profile, "sqlite")?,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+fn open(path: &Path) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS header (config_hash INTEGER NOT NULL);
+         CREATE TABLE IF NOT EXISTS templates (
+             id INTEGER PRIMARY KEY,
+             uri TEXT NOT NULL,
+             name TEXT NOT NULL,
+             json TEXT NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS templates_uri ON templates (uri);
+         CREATE INDEX IF NOT EXISTS templates_name ON templates (name);",
+    )?;
+
+    Ok(conn)
+}
+
+impl CacheStore for SqliteCache {
+    type Templates = SqliteTemplates;
+
+    fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    fn is_stale(&self, ttl: Duration) -> bool {
+        std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > ttl)
+    }
+
+    /// The header is `None` until the first write
+    async fn read(&self) -> Result<(Option<Header>, SqliteTemplates)> {
+        let path = self.path.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let conn = open(&path)?;
+            let header = conn
+                .query_row("SELECT config_hash FROM header", [], |row| {
+                    row.get::<_, i64>(0)
+                })
+                .optional()?
+                .map(|hash| Header {
+                    config_hash: hash as u64,
+                });
+
+            Ok((
+                header,
+                SqliteTemplates {
+                    conn,
+                    last_id: 0,
+                    page: VecDeque::new(),
+                },
+            ))
+        })
+        .await?
+    }
+
+    async fn write(&self, data: &[Template], config_hash: u64) -> Result<()> {
+        let path = self.path.clone();
+        let rows = data
+            .iter()
+            .map(|t| {
+                Ok((
+                    t.uri().to_string(),
+                    t.name.clone(),
+                    serde_json::to_string(t)?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        tokio::task::spawn_blocking(move || {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            // one transaction, so a refresh killed halfway leaves the old templates
+            let mut conn = open(&path)?;
+            let tx = conn.transaction()?;
+            tx.execute("DELETE FROM header", [])?;
+            tx.execute("DELETE FROM templates", [])?;
+            tx.execute(
+                "INSERT INTO header (config_hash) VALUES (?1)",
+                [config_hash as i64],
+            )?;
+            {
+                let mut insert =
+                    tx.prepare("INSERT INTO templates (uri, name, json) VALUES (?1, ?2, ?3)")?;
+                for row in rows {
+                    insert.execute(row)?;
+                }
+            }
+            tx.commit()?;
+
+            Ok(())
+        })
+        .await?
+    }
+
+    async fn with_prefix(&self, prefix: &str) -> Result<Vec<Template>> {
+        let path = self.path.clone();
+        // every string starting with `prefix` sorts below this, so the indexes answer the range
+        let end = format!("{prefix}\u{10FFFF}");
+        let prefix = prefix.to_string();
+
+        tokio::task::spawn_blocking(move || {
+            let conn = open(&path)?;
+            let mut stmt = conn.prepare(
+                "SELECT json FROM templates
+                 WHERE (uri >= ?1 AND uri < ?2) OR (name >= ?1 AND name < ?2)
+                 ORDER BY id",
+            )?;
+            let rows = stmt
+                .query_map([&prefix, &end], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            Ok(rows
+                .iter()
+                .filter_map(|json| serde_json::from_str(json).ok())
+                .collect())
+        })
+        .await?
+    }
+}
+
+/// Reads the next [`PAGE`] templates every time the previous ones ran out
+pub struct SqliteTemplates {
+    conn: Connection,
+    last_id: i64,
+    page: VecDeque<Template>,
+}
+
+impl SqliteTemplates {
+    /// How many rows there were, broken ones included
+    fn fetch(&mut self) -> rusqlite::Result<usize> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT id, json FROM templates WHERE id > ?1 ORDER BY id LIMIT ?2")?;
+        let rows = stmt
+            .query_map([self.last_id, PAGE], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        for (id, json) in &rows {
+            self.last_id = *id;
+            match serde_json::from_str(json) {
+                Ok(template) => self.page.push_back(template),
+                Err(e) => tracing::warn!("skipping a broken row of the cache: {e}"),
+            }
+        }
+
+        Ok(rows.len())
+    }
+}
+
+impl Iterator for SqliteTemplates {
+    type Item = Template;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.page.is_empty() {
+            match self.fetch() {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::warn!("failed to read the cache: {e}");
+                    return None;
+                }
+            }
+        }
+
+        self.page.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn round_trip() {
+        let dir = std::env::temp_dir().join(format!("nit-sqlite-test-{}", std::process::id()));
+        let cache = SqliteCache {
+            path: dir.join("cache.sqlite"),
+        };
+
+        let templates: Vec<Template> = (0..PAGE + 3)
+            .map(|i| {
+                serde_json::from_value(serde_json::json!({
+                    "name": format!("t{i}"),
+                    "flake_info": { "name": null, "uri": "github:x/y" },
+                    "description": "",
+                    "tags": [],
+                }))
+                .unwrap()
+            })
+            .collect();
+        cache.write(&templates, u64::MAX).await.unwrap();
+
+        let (header, read) = cache.read().await.unwrap();
+        assert_eq!(header.unwrap().config_hash, u64::MAX);
+        assert_eq!(read.count(), templates.len());
+
+        let names: Vec<_> = cache
+            .with_prefix("t51")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        assert_eq!(names, ["t51", "t510", "t511", "t512", "t513", "t514"]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
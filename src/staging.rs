@@ -0,0 +1,207 @@
+use std::path::{Path, PathBuf};
+
+use ltrait::color_eyre::Result;
+
+use crate::cancel;
+use crate::preview;
+
+/// A directory inside the target that a template is initialized into, so that its files only
+/// reach the target all at once with [`Staging::commit`]
+pub struct Staging {
+    root: PathBuf,
+    target: PathBuf,
+    /// Files moved into the target
+    moved: Vec<PathBuf>,
+    /// Directories created in the target
+    created_dirs: Vec<PathBuf>,
+    /// Files of the target the template replaced, and where they were moved to
+    backups: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Staging {
+    pub fn new(target: &Path) -> Result<Self> {
+        let root = target.join(format!(".nit-staging-{}", std::process::id()));
+        cancel::writing(&root);
+        std::fs::create_dir_all(root.join("init"))?;
+        std::fs::create_dir_all(root.join("backup"))?;
+
+        Ok(Self {
+            root,
+            target: target.to_path_buf(),
+            moved: vec![],
+            created_dirs: vec![],
+            backups: vec![],
+        })
+    }
+
+    /// Where the template is initialized
+    pub fn dir(&self) -> PathBuf {
+        self.root.join("init")
+    }
+
+    /// Move every staged file into the target. Like `nix flake init`, an existing file with another
+    /// content is only replaced if it is in `overwrite`, the paths the user agreed to. Returns the
+    /// existing files that were kept instead
+    pub fn commit(&mut self, overwrite: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        let mut kept = vec![];
+        self.move_dir(&self.dir(), Path::new(""), overwrite, &mut kept)?;
+
+        Ok(kept)
+    }
+
+    fn move_dir(
+        &mut self,
+        from: &Path,
+        relative: &Path,
+        overwrite: &[PathBuf],
+        kept: &mut Vec<PathBuf>,
+    ) -> Result<()> {
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            let relative = relative.join(entry.file_name());
+            let target = self.target.join(&relative);
+
+            if entry.file_type()?.is_dir() && !target.is_symlink() {
+                if !target.exists() {
+                    std::fs::create_dir(&target)?;
+                    self.created_dirs.push(target);
+                }
+                self.move_dir(&entry.path(), &relative, overwrite, kept)?;
+                continue;
+            }
+
+            if target.symlink_metadata().is_ok() {
+                if preview::contents(&target)? == preview::contents(&entry.path())? {
+                    continue;
+                }
+                if !overwrite.contains(&target) {
+                    kept.push(target);
+                    continue;
+                }
+
+                let backup = self.root.join("backup").join(&relative);
+                if let Some(parent) = backup.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::rename(&target, &backup)?;
+                self.backups.push((target.clone(), backup));
+            }
+
+            std::fs::rename(entry.path(), &target)?;
+            self.moved.push(target);
+        }
+
+        Ok(())
+    }
+
+    /// Keep the moved files, and remove the staging directory with the backups
+    pub fn finish(mut self) -> Result<()> {
+        self.moved.clear();
+        self.backups.clear();
+        self.created_dirs.clear();
+        std::fs::remove_dir_all(&self.root)?;
+        cancel::done_writing(&self.root);
+
+        Ok(())
+    }
+}
+
+/// Unless finished, e.g. when a step after the init failed, the target is put back as it was
+/// before [`Staging::commit`]
+impl Drop for Staging {
+    fn drop(&mut self) {
+        for file in self.moved.drain(..) {
+            let _ = std::fs::remove_file(file);
+        }
+        for (original, backup) in self.backups.drain(..) {
+            let _ = std::fs::rename(backup, original);
+        }
+        // deepest first, and kept if something else was put in them
+        for dir in self.created_dirs.drain(..).rev() {
+            let _ = std::fs::remove_dir(dir);
+        }
+
+        if self.root.exists() {
+            let _ = std::fs::remove_dir_all(&self.root);
+            cancel::done_writing(&self.root);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("nit-staging-test-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("README.md"), "mine").unwrap();
+        std::fs::write(dir.join(".envrc"), "use flake").unwrap();
+        dir
+    }
+
+    fn stage(target: &Path) -> Staging {
+        let staging = Staging::new(target).unwrap();
+        std::fs::create_dir_all(staging.dir().join("src")).unwrap();
+        std::fs::write(staging.dir().join("src/main.rs"), "fn main() {}").unwrap();
+        std::fs::write(staging.dir().join("README.md"), "template").unwrap();
+        std::fs::write(staging.dir().join(".envrc"), "use flake").unwrap();
+        staging
+    }
+
+    #[test]
+    fn commit() {
+        let dir = target("commit");
+
+        let mut staging = stage(&dir);
+        assert_eq!(staging.commit(&[]).unwrap(), [dir.join("README.md")]);
+        staging.finish().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("README.md")).unwrap(),
+            "mine"
+        );
+        assert!(dir.join("src/main.rs").exists());
+
+        let mut staging = stage(&dir);
+        assert!(staging.commit(&[dir.join("README.md")]).unwrap().is_empty());
+        staging.finish().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("README.md")).unwrap(),
+            "template"
+        );
+
+        let mut entries: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        entries.sort();
+        assert_eq!(entries, [".envrc", "README.md", "src"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rollback_on_drop() {
+        let dir = target("rollback");
+
+        let mut staging = stage(&dir);
+        staging.commit(&[dir.join("README.md")]).unwrap();
+        assert!(dir.join("src/main.rs").exists());
+        drop(staging);
+
+        assert_eq!(
+            std::fs::read_to_string(dir.join("README.md")).unwrap(),
+            "mine"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join(".envrc")).unwrap(),
+            "use flake"
+        );
+        assert!(!dir.join("src").exists());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
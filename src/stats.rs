@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+
+use chrono::Datelike as _;
+use ltrait::color_eyre::Result;
+use serde::Serialize;
+
+use crate::history::Record;
+use crate::output::{self, Output};
+
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    /// Most used first
+    pub templates: Vec<Count>,
+    /// Oldest first
+    pub weeks: Vec<Count>,
+    /// Most used first
+    pub flakes: Vec<Count>,
+    pub failures: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Count {
+    pub key: String,
+    pub count: usize,
+}
+
+impl Stats {
+    /// Only successful inits are counted
+    pub fn new(records: &[Record]) -> Self {
+        let mut templates = BTreeMap::<String, usize>::new();
+        let mut weeks = BTreeMap::<String, usize>::new();
+        let mut flakes = BTreeMap::<String, usize>::new();
+
+        for r in records.iter().filter(|r| r.success) {
+            *templates.entry(r.template.uri().to_string()).or_default() += 1;
+            *flakes.entry(r.template.flake_info.uri.clone()).or_default() += 1;
+
+            let week = r.timestamp.iso_week();
+            *weeks
+                .entry(format!("{}-W{:02}", week.year(), week.week()))
+                .or_default() += 1;
+        }
+
+        Self {
+            templates: by_count(templates),
+            weeks: weeks
+                .into_iter()
+                .map(|(key, count)| Count { key, count })
+                .collect(),
+            flakes: by_count(flakes),
+            failures: records.iter().filter(|r| !r.success).count(),
+        }
+    }
+
+    pub fn print(&self, output: Option<Output>) -> Result<()> {
+        match output {
+            Some(Output::Json) => {
+                println!("{}", serde_json::to_string(self)?);
+                return Ok(());
+            }
+            Some(output @ (Output::Csv | Output::Tsv)) => {
+                let rows: Vec<_> = [
+                    ("templates", &self.templates),
+                    ("weeks", &self.weeks),
+                    ("flakes", &self.flakes),
+                ]
+                .into_iter()
+                .flat_map(|(section, counts)| {
+                    counts
+                        .iter()
+                        .map(move |c| vec![section.into(), c.key.clone(), c.count.to_string()])
+                })
+                .chain([vec![
+                    "failures".into(),
+                    String::new(),
+                    self.failures.to_string(),
+                ]])
+                .collect();
+
+                return output::print_rows(output, &["section", "key", "count"], &rows);
+            }
+            Some(Output::Table) | None => {}
+        }
+
+        for (title, counts) in [
+            ("Templates", &self.templates),
+            ("Weeks", &self.weeks),
+            ("Flakes", &self.flakes),
+        ] {
+            println!("{title}");
+            let width = counts.iter().map(|c| c.key.len()).max().unwrap_or(0);
+            for c in counts {
+                println!("  {:width$}  {}", c.key, c.count);
+            }
+            println!();
+        }
+        println!("Failed inits: {}", self.failures);
+
+        Ok(())
+    }
+}
+
+fn by_count(counts: BTreeMap<String, usize>) -> Vec<Count> {
+    let mut counts: Vec<_> = counts
+        .into_iter()
+        .map(|(key, count)| Count { key, count })
+        .collect();
+    // the sort is stable, so ties stay in key order
+    counts.sort_by_key(|c| std::cmp::Reverse(c.count));
+    counts
+}
@@ -0,0 +1,176 @@
+use std::io::BufRead as _;
+use std::path::{Path, PathBuf};
+
+use ltrait::color_eyre::Result;
+use ltrait_sorter_frecency::{Frecency, FrecencyConfig};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::init::{self, InitOptions};
+use crate::list::{self, Sort};
+use crate::matcher::MatcherConfig;
+use crate::nix::NixBackend;
+use crate::{Template, error};
+
+/// One line of `--stdio` input. `id` is echoed in the response, to tell them apart
+#[derive(Debug, Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    #[serde(flatten)]
+    method: Method,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "method", rename_all = "kebab-case")]
+enum Method {
+    /// The templates matching `query`, best match first. Without a query, by frecency like the TUI
+    Query {
+        #[serde(default)]
+        query: String,
+        limit: Option<usize>,
+    },
+    /// Initialize `template`, a `uri#name` or a unique name, in `cwd`
+    Select {
+        template: String,
+        /// The editor's working directory, nit's own if omitted
+        cwd: Option<PathBuf>,
+        into: Option<PathBuf>,
+        /// The project name for rename rules, never asked for
+        name: Option<String>,
+    },
+}
+
+/// `--stdio`: answer one JSON request per line of stdin with one JSON line on stdout, for editor
+/// plugins that show the templates in their own picker. A request that fails gets
+/// `{"id": ..., "error": ...}` like `--json`, and the next one is read
+pub fn run(
+    nix: &impl NixBackend,
+    templates: Vec<Template>,
+    matcher: MatcherConfig,
+    frecency: &FrecencyConfig,
+    opts: &InitOptions,
+) -> Result<()> {
+    let cwd = std::env::current_dir()?;
+
+    for line in std::io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (id, res) = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => (
+                request.id,
+                answer(
+                    nix,
+                    request.method,
+                    &templates,
+                    &cwd,
+                    matcher,
+                    frecency,
+                    opts,
+                ),
+            ),
+            Err(e) => (Value::Null, Err(e.into())),
+        };
+        let response = match res {
+            Ok(result) => json!({ "id": id, "result": result }),
+            Err(e) => json!({ "id": id, "error": error::to_json(&e) }),
+        };
+        println!("{response}");
+    }
+
+    Ok(())
+}
+
+fn answer(
+    nix: &impl NixBackend,
+    method: Method,
+    templates: &[Template],
+    cwd: &Path,
+    matcher: MatcherConfig,
+    frecency: &FrecencyConfig,
+    opts: &InitOptions,
+) -> Result<Value> {
+    match method {
+        Method::Query { query, limit } => {
+            let mut matched = if query.is_empty() {
+                let mut all = templates.to_vec();
+                list::Options {
+                    sort: Some(Sort::Frecency),
+                    ..Default::default()
+                }
+                .apply(&mut all, frecency)?;
+                all
+            } else {
+                list::search(templates.iter().cloned(), &query, matcher)
+            };
+            if let Some(limit) = limit {
+                matched.truncate(limit);
+            }
+
+            // `uri` is what `select` takes
+            Ok(matched
+                .iter()
+                .map(|t| {
+                    let mut value = json!(t);
+                    value["uri"] = json!(t.uri());
+                    value
+                })
+                .collect())
+        }
+        Method::Select {
+            template,
+            cwd: dir,
+            into,
+            name,
+        } => {
+            let t = init::find(templates.iter().cloned(), &template)?;
+            // a previous select may have moved into its `into`
+            std::env::set_current_dir(dir.as_deref().unwrap_or(cwd))?;
+
+            ltrait::Action::act(&Frecency::new(frecency.clone())?, &t.frecency_context())?;
+            init::init_template(
+                nix,
+                &t,
+                &InitOptions {
+                    name: name.or(opts.name.clone()),
+                    into: into.or(opts.into.clone()),
+                    // the editor asked already, and stdin is ours
+                    yes: true,
+                    json: false,
+                    stdio: true,
+                    ..opts.clone()
+                },
+            )?;
+
+            Ok(json!({
+                "uri": t.uri(),
+                "target": std::env::current_dir()?,
+            }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests() {
+        let request: Request =
+            serde_json::from_str(r#"{"id": 1, "method": "query", "query": "rust"}"#).unwrap();
+        assert_eq!(request.id, json!(1));
+        assert!(matches!(request.method, Method::Query { query, limit: None } if query == "rust"));
+
+        let request: Request =
+            serde_json::from_str(r#"{"method": "select", "template": "github:x/y#rust"}"#).unwrap();
+        assert_eq!(request.id, Value::Null);
+        assert!(
+            matches!(request.method, Method::Select { template, .. } if template == "github:x/y#rust")
+        );
+
+        assert!(serde_json::from_str::<Request>(r#"{"method": "quit"}"#).is_err());
+    }
+}
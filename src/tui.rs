@@ -0,0 +1,1159 @@
+//! The picker TUI, adapted from `ltrait-ui-tui` 0.6.0
+//! (<https://github.com/ltrait/exts>, by satler, MIT licence).
+//!
+//! Changed from upstream: an initial query, prompt and status line, sort
+//! and matcher modes, the detail pane and two-line entries, vim/emacs
+//! keymaps with `[keys]` overrides, plain output, condensed layouts for
+//! narrow terminals, reload from the cache, and restoring the terminal on
+//! panics and signals.
+//!
+//! Copyright (c) satler
+//!
+//! Permission is hereby granted, free of charge, to any person obtaining a
+//! copy of this software and associated documentation files (the
+//! "Software"), to deal in the Software without restriction, including
+//! without limitation the rights to use, copy, modify, merge, publish,
+//! distribute, sublicense, and/or sell copies of the Software, and to
+//! permit persons to whom the Software is furnished to do so, subject to
+//! the following conditions:
+//!
+//! The above copyright notice and this permission notice shall be included
+//! in all copies or substantial portions of the Software.
+//!
+//! THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS
+//! OR IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF
+//! MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.
+//! IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+//! CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT,
+//! TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE
+//! SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+use ltrait::{
+    color_eyre::eyre::{OptionExt, Result, WrapErr, bail},
+    launcher::batcher::Batcher,
+    tokio_stream::StreamExt as _,
+    ui::{Buffer, Position},
+};
+
+use crossterm::{
+    event::{Event as CEvent, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode},
+};
+use ratatui::{
+    Frame, Terminal, TerminalOptions,
+    layout::{Constraint, Direction, Layout},
+    prelude::{Backend, CrosstermBackend},
+    style::Style,
+    widgets::{Block, Borders, Clear, List, Padding, Paragraph, Widget},
+};
+use tracing::{debug, info};
+use tui_input::{Input, backend::crossterm::EventHandler};
+
+pub use ratatui::{Viewport, style};
+
+use futures::{FutureExt as _, select};
+use tokio::sync::mpsc;
+
+use std::{
+    io::{IsTerminal as _, Write},
+    path::PathBuf,
+    sync::{
+        Arc, Once, RwLock,
+        atomic::{AtomicU8, AtomicUsize, Ordering},
+    },
+};
+
+use crate::frecency::{SharedSortMode, SortMode};
+use crate::locale::tr;
+
+pub struct Tui<F>
+where
+    F: Fn(&[KeyEvent], Mode) -> Action + Clone,
+{
+    config: TuiConfig<F>,
+    /// What was typed when the TUI closed
+    last_query: std::sync::Mutex<String>,
+}
+
+/// What the user chose in the TUI
+pub enum Selection<Cushion> {
+    Entry(Cushion),
+    /// Re-run the most recently used template instead of an entry
+    Again,
+    /// Re-collect the cache and start over with this query
+    Reload(String),
+}
+
+impl<F> Tui<F>
+where
+    F: Fn(&[KeyEvent], Mode) -> Action + Send + Sync + Clone,
+{
+    pub async fn run<Cushion: Send>(
+        &self,
+        mut batcher: Batcher<Cushion, TuiEntry>,
+    ) -> Result<Option<Selection<Cushion>>> {
+        let writer: Box<dyn Write + Send> = if self.config.use_tty {
+            let tty = std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(crate::platform::TTY)?;
+            Box::new(tty)
+        } else {
+            Box::new(std::io::stdout())
+        };
+
+        let backend = CrosstermBackend::new(writer);
+
+        let mut terminal = Terminal::with_options(
+            backend,
+            TerminalOptions {
+                viewport: self.config.viewport.clone(),
+            },
+        )?;
+
+        install_panic_hook();
+        self.enter(&mut terminal)?;
+        ACTIVE.store(
+            if matches!(self.config.viewport, Viewport::Fullscreen) {
+                FULLSCREEN
+            } else {
+                INLINE
+            },
+            Ordering::SeqCst,
+        );
+
+        let mut app = App::new(self.config.clone());
+        let i = app.run(&mut terminal, &mut batcher).await;
+
+        ACTIVE.store(INACTIVE, Ordering::SeqCst);
+        self.exit(&mut terminal)?;
+        *self.last_query.lock().unwrap() = app.input.value().to_string();
+
+        Ok(match i? {
+            Some(Selection::Entry(id)) => Some(Selection::Entry(batcher.compute_cushion(id)?)),
+            Some(Selection::Again) => Some(Selection::Again),
+            Some(Selection::Reload(query)) => Some(Selection::Reload(query)),
+            None => None,
+        })
+    }
+}
+
+const INACTIVE: u8 = 0;
+const INLINE: u8 = 1;
+const FULLSCREEN: u8 = 2;
+
+/// Which viewport the TUI currently has the terminal in, for [`restore_terminal`]
+static ACTIVE: AtomicU8 = AtomicU8::new(INACTIVE);
+
+/// Whether there is a terminal the TUI can draw on, e.g. not in an editor without one. The
+/// console of Windows has no TERM
+pub fn drawable() -> bool {
+    let term = std::env::var("TERM").unwrap_or_default();
+    (cfg!(windows) || !term.is_empty())
+        && term != "dumb"
+        && std::fs::OpenOptions::new()
+            .write(true)
+            .open(crate::platform::TTY)
+            .is_ok_and(|tty| tty.is_terminal())
+}
+
+/// Put the terminal back to how it was before the TUI took it over, if it did. For panics and
+/// signals, see [`crate::cancel::install`], which skip [`Tui::exit`]
+pub fn restore_terminal() {
+    let active = ACTIVE.swap(INACTIVE, Ordering::SeqCst);
+    if active == INACTIVE {
+        return;
+    }
+
+    let _ = disable_raw_mode();
+    // the TUI draws on the tty when `use_tty` is set, which is the same terminal as stdout otherwise
+    let mut out: Box<dyn Write> = match std::fs::OpenOptions::new()
+        .write(true)
+        .open(crate::platform::TTY)
+    {
+        Ok(tty) => Box::new(tty),
+        Err(_) => Box::new(std::io::stdout()),
+    };
+    if active == FULLSCREEN {
+        let _ = execute!(
+            out,
+            crossterm::terminal::LeaveAlternateScreen,
+            crossterm::event::DisableMouseCapture
+        );
+    }
+    // start below what was left of the inline viewport
+    let _ = execute!(
+        out,
+        crossterm::cursor::Show,
+        crossterm::style::Print("\r\n")
+    );
+}
+
+/// Restore the terminal before the panic message is printed, so that it is readable
+fn install_panic_hook() {
+    static INSTALLED: Once = Once::new();
+
+    INSTALLED.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            restore_terminal();
+            previous(info);
+        }));
+    });
+}
+
+impl<F> Tui<F>
+where
+    F: Fn(&[KeyEvent], Mode) -> Action + Clone,
+{
+    pub fn new(config: TuiConfig<F>) -> Self {
+        Self {
+            config,
+            last_query: Default::default(),
+        }
+    }
+
+    /// The query when [`Tui::run`] returned
+    pub fn last_query(&self) -> String {
+        self.last_query.lock().unwrap().clone()
+    }
+
+    fn enter<B: Backend + Write>(&self, terminal: &mut Terminal<B>) -> Result<()> {
+        use ratatui::Viewport;
+
+        match &self.config.viewport {
+            Viewport::Fullscreen => {
+                execute!(
+                    terminal.backend_mut(),
+                    crossterm::terminal::EnterAlternateScreen,
+                    crossterm::event::EnableMouseCapture
+                )?;
+                enable_raw_mode()?;
+                terminal.clear()?;
+            }
+            Viewport::Inline(_) | Viewport::Fixed(_) => {
+                enable_raw_mode()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn exit<B: Backend + Write>(&self, terminal: &mut Terminal<B>) -> Result<()> {
+        match &self.config.viewport {
+            Viewport::Fullscreen => {
+                execute!(
+                    terminal.backend_mut(),
+                    crossterm::terminal::LeaveAlternateScreen,
+                    crossterm::event::DisableMouseCapture
+                )?;
+                disable_raw_mode()?;
+                ratatui::restore();
+            }
+            Viewport::Inline(_) | Viewport::Fixed(_) => {
+                disable_raw_mode()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct TuiConfig<F>
+where
+    F: Fn(&[KeyEvent], Mode) -> Action + Clone,
+{
+    viewport: Viewport,
+    use_tty: bool,
+    selecting: String,
+    no_selecting: String,
+    keybinder: F,
+    prompt: String,
+    query: String,
+    profile: Option<String>,
+    target: Option<PathBuf>,
+    mode: Mode,
+    matched: Arc<AtomicUsize>,
+    sort: SharedSortMode,
+    plain: bool,
+}
+
+impl<F> TuiConfig<F>
+where
+    F: Fn(&[KeyEvent], Mode) -> Action + Clone,
+{
+    pub fn new(
+        viewport: Viewport,
+        use_tty: bool,
+        selecting: impl Into<String>,
+        no_selecting: impl Into<String>,
+        keybinder: F,
+    ) -> Self {
+        Self {
+            viewport,
+            use_tty,
+            selecting: selecting.into(),
+            no_selecting: no_selecting.into(),
+            keybinder,
+            prompt: String::new(),
+            query: String::new(),
+            profile: None,
+            target: None,
+            mode: Mode::Insert,
+            matched: Arc::default(),
+            sort: SharedSortMode::default(),
+            plain: false,
+        }
+    }
+
+    /// Drawn in front of the query
+    pub fn prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = prompt.into();
+        self
+    }
+
+    /// The text the input starts with
+    pub fn query(mut self, query: impl Into<String>) -> Self {
+        self.query = query.into();
+        self
+    }
+
+    /// `--profile`, shown in the status line
+    pub fn profile(mut self, profile: Option<String>) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// How many entries match the input, for the status line. Counted by a filter of the
+    /// launcher as the entries are prepared, and reset here on every input
+    pub fn matched(mut self, matched: Arc<AtomicUsize>) -> Self {
+        self.matched = matched;
+        self
+    }
+
+    /// No dimmed text and no line above the input, for screen readers and braille terminals
+    pub fn plain(mut self, plain: bool) -> Self {
+        self.plain = plain;
+        self
+    }
+
+    /// Shared with the sorter, which Alt-s switches
+    pub fn sort(mut self, sort: SharedSortMode) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// The mode the TUI starts in
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Where Enter initializes the highlighted entry, shown below it
+    pub fn target(mut self, target: Option<PathBuf>) -> Self {
+        self.target = target;
+        self
+    }
+}
+
+type StyledText = (String, Style);
+
+/// `<SelectingStatus> <icon> <title> <sub_string>`
+/// SelectingStatus in above is a char
+pub struct TuiEntry {
+    pub text: StyledText,
+    /// `uri#name`, shown in full for the highlighted entry
+    pub uri: String,
+    /// Shown below the text in the two-line mode
+    pub description: String,
+    /// Shown instead of the text on terminals narrower than [`CONDENSED_WIDTH`]
+    pub name: String,
+}
+
+/// Below this many columns, entries are only their name and there are no descriptions
+const CONDENSED_WIDTH: u16 = 40;
+/// Below this, nothing but [`too_small`] fits
+const MIN_WIDTH: u16 = 12;
+/// The list, the input and the status line, one row each
+const MIN_HEIGHT: u16 = 4;
+
+// なんのArc, Mutex, RwLockを使うか検討する必要がある。renderの中で使えないと意味ないし
+struct App<F>
+where
+    F: Fn(&[KeyEvent], Mode) -> Action + Clone,
+{
+    config: TuiConfig<F>,
+
+    exit: bool,
+    // 上が0
+    selecting_i: usize,
+    input: Input,
+    cursor_pos: RwLock<Option<(u16, u16)>>,
+    /// How many entries the last draw had room for
+    page: AtomicUsize,
+    buffer: Buffer<(TuiEntry, usize)>,
+    has_more: bool,
+    tx: Option<mpsc::Sender<Event>>,
+    selected: bool,
+    again: bool,
+    reload: bool,
+    /// Every entry takes two rows, the second being the description
+    two_line: bool,
+    mode: Mode,
+    /// The keys of a binding that isn't complete yet
+    pending: Vec<KeyEvent>,
+}
+
+impl<F> App<F>
+where
+    F: Fn(&[KeyEvent], Mode) -> Action + Clone,
+{
+    fn new(config: TuiConfig<F>) -> Self {
+        Self {
+            has_more: true,
+            exit: false,
+            selecting_i: 0,
+            input: Input::new(config.query.clone()),
+            buffer: Buffer::default(),
+            tx: None,
+            cursor_pos: None.into(),
+            page: AtomicUsize::new(1),
+            selected: false,
+            again: false,
+            reload: false,
+            two_line: false,
+            mode: config.mode,
+            pending: vec![],
+            config,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Event {
+    Key(KeyEvent),
+    Refresh,
+    Input,
+}
+
+/// What a key does. Everything but `pending` can be bound in `[keys]`, by its kebab-case name
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    Select,
+    ExitWithoutSelect,
+    Up,
+    Down,
+    Input,
+    /// Exit and re-run the most recently used template
+    Again,
+    /// Re-collect the cache and show the TUI again with the same query
+    Reload,
+    /// Switch between one and two rows per entry
+    ToggleTwoLine,
+    /// Switch between ordering by frecency and by the last use
+    ToggleSort,
+    /// Up by as many entries as fit in the viewport
+    PageUp,
+    PageDown,
+    /// The entry at the top of the list
+    Home,
+    /// The entry at the bottom of the list, next to the input
+    End,
+    /// Type into the query, from [`Mode::Normal`]
+    Search,
+    /// Stop typing into the query
+    Normal,
+    /// The keys so far start a longer binding, e.g. the first `g` of `gg`
+    #[serde(skip)]
+    Pending,
+    /// Do nothing
+    Ignore,
+}
+
+/// Where the keys go
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Unbound keys edit the query
+    Insert,
+    /// Keys only move and select, like in vim
+    Normal,
+}
+
+/// The built-in key bindings, `keymap` in the config
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Keymap {
+    /// Every unbound key edits the query
+    #[default]
+    Default,
+    /// Starts in normal mode with j/k, gg/G and Ctrl-d/Ctrl-u. `/` types a query, Esc leaves it
+    /// or quits
+    Vim,
+    /// C-n/C-p, C-v/M-v, M-</M-> and C-g to quit
+    Emacs,
+}
+
+impl Keymap {
+    /// The preset with `overrides` on top. Plain characters are only overridden in
+    /// [`Mode::Normal`], so they can still be typed into the query
+    pub fn keybinder(
+        self,
+        overrides: Vec<(KeyEvent, Action)>,
+    ) -> impl Fn(&[KeyEvent], Mode) -> Action + Clone + Send + Sync {
+        let preset: fn(&[KeyEvent], Mode) -> Action = match self {
+            Keymap::Default => sample_keyconfig,
+            Keymap::Vim => vim_keyconfig,
+            Keymap::Emacs => emacs_keyconfig,
+        };
+
+        move |keys: &[KeyEvent], mode: Mode| {
+            if let [key] = keys {
+                let typed = matches!(key.code, KeyCode::Char(_))
+                    && (key.modifiers - KeyModifiers::SHIFT).is_empty();
+                let overridden = overrides.iter().find(|(k, _)| {
+                    k.code == key.code
+                        && (k.modifiers - KeyModifiers::SHIFT)
+                            == (key.modifiers - KeyModifiers::SHIFT)
+                });
+                if let Some((_, action)) = overridden
+                    && (!typed || mode == Mode::Normal)
+                {
+                    return action.clone();
+                }
+            }
+
+            preset(keys, mode)
+        }
+    }
+
+    pub fn mode(self) -> Mode {
+        match self {
+            Keymap::Default | Keymap::Emacs => Mode::Insert,
+            Keymap::Vim => Mode::Normal,
+        }
+    }
+}
+
+impl Event {
+    async fn terminal_event_listener(tx: mpsc::Sender<Event>) {
+        let mut reader = crossterm::event::EventStream::new();
+
+        loop {
+            let crossterm_event = reader.next().fuse();
+            std::thread::sleep(std::time::Duration::from_millis(10));
+
+            if let Some(Ok(CEvent::Key(key))) = crossterm_event.await
+                && key.kind == KeyEventKind::Press
+            {
+                tx.send(Event::Key(key)).await.unwrap();
+            }
+        }
+    }
+}
+
+impl<F> App<F>
+where
+    F: Fn(&[KeyEvent], Mode) -> Action + Clone,
+{
+    async fn run<Cusion: Send, B: Backend>(
+        &mut self,
+        terminal: &mut Terminal<B>,
+        batcher: &mut Batcher<Cusion, TuiEntry>,
+    ) -> Result<Option<Selection<usize>>> {
+        let (tx, mut rx) = mpsc::channel(100);
+
+        let listener = tokio::spawn(Event::terminal_event_listener(tx.clone()));
+        self.tx = Some(tx.clone());
+
+        if !self.input.value().is_empty() {
+            batcher.input(&mut self.buffer, self.input.value());
+        }
+
+        while !self.exit {
+            let prepare = async {
+                if self.has_more {
+                    batcher.prepare().await
+                } else {
+                    // HACK: もうeventだけ気にしていればいいから
+                    info!("No more items. Sleeping");
+                    tokio::time::sleep(std::time::Duration::from_secs(100)).await;
+                    batcher.prepare().await
+                }
+            };
+
+            select! {
+                // TODO: 毎回futureを生成し直していると
+                // dropした場合にバグるかも。あと必ず、rx.recvが早い場合何も表示されなくなっちゃうかも
+                from = prepare.fuse( ) => {
+                    info!("Merging");
+                    let has_more  =
+                        batcher.merge(&mut self.buffer, from);
+
+                        let _ = tx.send(Event::Refresh).await;
+
+                    self.has_more = has_more?;
+                    info!("Merged");
+                }
+                event_like = rx.recv().fuse() => {
+                    info!("Caught event-like");
+                    debug!("{event_like:?}");
+
+                    match event_like {
+                        Some(event) => {
+                            self.handle_events(event, batcher)
+                                .await
+                                .wrap_err("handle events failed")?;
+
+                            terminal.draw(|frame| self.draw(frame))?;
+                        }
+                    _ => bail!("the communication channel for event was unexpectedly closed.")
+                    }
+                }
+            }
+        }
+        // the keys belong to whatever reads the terminal next, e.g. the TUI after a reload
+        listener.abort();
+
+        Ok(if self.again {
+            Some(Selection::Again)
+        } else if self.reload {
+            // the next TUI starts where this one is, instead of below it
+            terminal.clear()?;
+            Some(Selection::Reload(self.input.value().to_string()))
+        } else if self.selected {
+            let mut pos = Position(self.buffer.len() - 1 - self.selecting_i);
+            Some(Selection::Entry(self.buffer.next(&mut pos).unwrap().1))
+        } else {
+            None
+        })
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        frame.render_widget(self, frame.area());
+        // hidden while the terminal is too small
+        if let Some(pos) = *self.cursor_pos.read().unwrap() {
+            frame.set_cursor_position(ratatui::layout::Position::from(pos));
+        }
+    }
+
+    async fn handle_events<Cusion: Send>(
+        &mut self,
+        event: Event,
+        batcher: &mut Batcher<Cusion, TuiEntry>,
+    ) -> Result<()> {
+        match event {
+            // it's important to check that the event is a key press event as
+            // crossterm also emits key release and repeat events on Windows.
+            Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
+                info!("Handling KeyInput");
+                self.handle_key_event(key_event).await?
+            }
+            Event::Input => {
+                info!("Handling Input");
+                batcher.input(&mut self.buffer, self.input.value());
+                self.config.matched.store(0, Ordering::Relaxed);
+                // 一回一番上に戻す
+                self.selecting_i = 0;
+                self.has_more = true;
+            }
+            _ => {}
+        };
+        Ok(())
+    }
+
+    async fn handle_key_event(&mut self, key_event: KeyEvent) -> Result<()> {
+        self.pending.push(key_event);
+        let action = (self.config.keybinder)(&self.pending, self.mode);
+        if !matches!(action, Action::Pending) {
+            self.pending.clear();
+        }
+
+        match action {
+            Action::Select => {
+                self.selected = true;
+                self.exit();
+            }
+            Action::ExitWithoutSelect => self.exit(),
+            Action::Again => {
+                self.again = true;
+                self.exit();
+            }
+            Action::Reload => {
+                self.reload = true;
+                self.exit();
+            }
+            Action::ToggleTwoLine => self.two_line = !self.two_line,
+            Action::ToggleSort => {
+                {
+                    let mut sort = self.config.sort.lock().unwrap();
+                    *sort = sort.toggled();
+                }
+
+                // sorts the entries again
+                self.tx
+                    .as_mut()
+                    .unwrap()
+                    .send(Event::Input)
+                    .await
+                    .wrap_err("Failed to send Refresh")?;
+            }
+            Action::Up => {
+                self.selecting_i = (self.selecting_i + 1).min(self.buffer.len().saturating_sub(1));
+            }
+            Action::Down => {
+                self.selecting_i = self.selecting_i.saturating_sub(1);
+            }
+            Action::PageUp => {
+                self.selecting_i = (self.selecting_i + self.page.load(Ordering::Relaxed))
+                    .min(self.buffer.len().saturating_sub(1));
+            }
+            Action::PageDown => {
+                self.selecting_i = self
+                    .selecting_i
+                    .saturating_sub(self.page.load(Ordering::Relaxed));
+            }
+            Action::Home => self.selecting_i = self.buffer.len().saturating_sub(1),
+            Action::End => self.selecting_i = 0,
+            Action::Search => self.mode = Mode::Insert,
+            Action::Normal => self.mode = Mode::Normal,
+            Action::Pending | Action::Ignore => {}
+            Action::Input => {
+                if !(self.input.cursor() == 0
+                    && (key_event.code == KeyCode::Backspace || key_event.code == KeyCode::Left)
+                    || self.input.cursor() == self.input.value().len()
+                        && (key_event.code == KeyCode::Delete || key_event.code == KeyCode::Right))
+                {
+                    self.input
+                        .handle_event(&crossterm::event::Event::Key(key_event))
+                        .ok_or_eyre("Failed to handle input")?;
+
+                    self.tx
+                        .as_mut()
+                        .unwrap()
+                        .send(Event::Input)
+                        .await
+                        .wrap_err("Failed to send Refresh")?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn exit(&mut self) {
+        self.exit = true;
+    }
+
+    fn highlighted(&self) -> Option<&TuiEntry> {
+        let mut pos = Position(self.buffer.len().checked_sub(1 + self.selecting_i)?);
+        self.buffer.next(&mut pos).map(|(entry, _)| entry)
+    }
+
+    /// The full `uri#name` of the highlighted entry, and where it would be initialized unless
+    /// `condensed`
+    fn highlighted_text(&self, condensed: bool) -> String {
+        match (self.highlighted(), &self.config.target) {
+            (Some(entry), Some(target)) if !condensed => tr(
+                "status-into",
+                &[("uri", &entry.uri), ("target", &target.display())],
+            ),
+            (Some(entry), _) => entry.uri.clone(),
+            (None, _) => String::new(),
+        }
+    }
+
+    /// Of the status line and the descriptions
+    fn dim(&self) -> Style {
+        if self.config.plain {
+            Style::new()
+        } else {
+            Style::new().add_modifier(style::Modifier::DIM)
+        }
+    }
+
+    /// `12/348  sort: match  profile: work`, after `NORMAL` in normal mode. The sort is `recent`
+    /// while Alt-s switched to it
+    fn status(&self) -> String {
+        let mut status = format!(
+            "{}{}/{}  {}: {}",
+            if self.mode == Mode::Normal {
+                format!("{}  ", tr("status-normal", &[]))
+            } else {
+                String::new()
+            },
+            self.config.matched.load(Ordering::Relaxed),
+            self.buffer.len(),
+            tr("status-sort", &[]),
+            tr(
+                match *self.config.sort.lock().unwrap() {
+                    SortMode::Recent => "status-sort-recent",
+                    SortMode::Frecency if self.input.value().is_empty() => "status-sort-frecency",
+                    SortMode::Frecency => "status-sort-match",
+                },
+                &[]
+            )
+        );
+        if let Some(profile) = &self.config.profile {
+            status.push_str(&format!("  {}: {profile}", tr("status-profile", &[])));
+        }
+        status
+    }
+}
+
+pub fn sample_keyconfig(keys: &[KeyEvent], _: Mode) -> Action {
+    let Some(key) = keys.last() else {
+        return Action::Ignore;
+    };
+
+    match (key.code, key.modifiers) {
+        (KeyCode::Enter, _) => Action::Select,
+        (KeyCode::Char('a'), KeyModifiers::ALT) => Action::Again,
+        (KeyCode::F(5), _) | (KeyCode::Char('r'), KeyModifiers::CONTROL) => Action::Reload,
+        (KeyCode::Char('d'), KeyModifiers::ALT) => Action::ToggleTwoLine,
+        (KeyCode::Char('s'), KeyModifiers::ALT) => Action::ToggleSort,
+        (KeyCode::Char('c'), KeyModifiers::CONTROL)
+        | (KeyCode::Char('d'), KeyModifiers::CONTROL)
+        | (KeyCode::Esc, _) => Action::ExitWithoutSelect,
+        (KeyCode::Up, _) | (KeyCode::Char('k'), KeyModifiers::CONTROL) => Action::Up,
+        (KeyCode::Down, _) | (KeyCode::Char('j'), KeyModifiers::CONTROL) => Action::Down,
+        (KeyCode::PageUp, _) => Action::PageUp,
+        (KeyCode::PageDown, _) => Action::PageDown,
+        (KeyCode::Home, _) => Action::Home,
+        (KeyCode::End, _) => Action::End,
+        _ => Action::Input,
+    }
+}
+
+/// [`Keymap::Emacs`]. Whatever isn't bound like in emacs behaves like [`sample_keyconfig`]
+pub fn emacs_keyconfig(keys: &[KeyEvent], mode: Mode) -> Action {
+    let Some(key) = keys.last() else {
+        return Action::Ignore;
+    };
+
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let alt = key.modifiers.contains(KeyModifiers::ALT);
+    match key.code {
+        KeyCode::Char('g') if ctrl => Action::ExitWithoutSelect,
+        KeyCode::Char('p') if ctrl => Action::Up,
+        KeyCode::Char('n') if ctrl => Action::Down,
+        KeyCode::Char('v') if alt => Action::PageUp,
+        KeyCode::Char('v') if ctrl => Action::PageDown,
+        KeyCode::Char('<') if alt => Action::Home,
+        KeyCode::Char('>') if alt => Action::End,
+        _ => sample_keyconfig(keys, mode),
+    }
+}
+
+/// A key in `[keys]`: `-`-separated `ctrl`, `alt` and `shift` followed by a character or one of
+/// `enter`, `esc`, `tab`, `backspace`, `delete`, `space`, `up`, `down`, `left`, `right`,
+/// `pgup`, `pgdn`, `home`, `end` and `f1`-`f12`, e.g. `ctrl-n` or `alt-<`
+pub fn parse_key(key: &str) -> std::result::Result<KeyEvent, String> {
+    let (modifiers, name) = match key.rsplit_once('-') {
+        // `-` itself, or `ctrl--`
+        Some((modifiers, "")) => (modifiers.strip_suffix('-').unwrap_or(""), "-"),
+        Some((modifiers, name)) => (modifiers, name),
+        None => ("", key),
+    };
+
+    let mut mods = KeyModifiers::NONE;
+    for m in modifiers.split('-').filter(|m| !m.is_empty()) {
+        mods |= match m.to_lowercase().as_str() {
+            "ctrl" | "c" => KeyModifiers::CONTROL,
+            "alt" | "meta" | "m" => KeyModifiers::ALT,
+            "shift" | "s" => KeyModifiers::SHIFT,
+            _ => return Err(format!("unknown modifier `{m}` in `{key}`")),
+        };
+    }
+
+    let mut chars = name.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match name.to_lowercase().as_str() {
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "delete" => KeyCode::Delete,
+            "space" => KeyCode::Char(' '),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "pgup" => KeyCode::PageUp,
+            "pgdn" => KeyCode::PageDown,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            f => match f.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                Some(n @ 1..=12) => KeyCode::F(n),
+                _ => return Err(format!("unknown key `{name}` in `{key}`")),
+            },
+        },
+    };
+
+    Ok(KeyEvent::new(code, mods))
+}
+
+/// [`Keymap::Vim`]. In [`Mode::Insert`], [`sample_keyconfig`] except that Esc goes back to
+/// [`Mode::Normal`]
+pub fn vim_keyconfig(keys: &[KeyEvent], mode: Mode) -> Action {
+    let Some(key) = keys.last() else {
+        return Action::Ignore;
+    };
+
+    if mode == Mode::Insert {
+        return match key.code {
+            KeyCode::Esc => Action::Normal,
+            _ => sample_keyconfig(keys, mode),
+        };
+    }
+
+    let chars: Vec<char> = keys
+        .iter()
+        .map(|k| match k.code {
+            KeyCode::Char(c) if !k.modifiers.contains(KeyModifiers::CONTROL) => c,
+            _ => '\0',
+        })
+        .collect();
+    match chars.as_slice() {
+        ['g'] => return Action::Pending,
+        ['g', 'g'] => return Action::Home,
+        [_, _, ..] => return Action::Ignore,
+        _ => {}
+    }
+
+    match (key.code, key.modifiers) {
+        (KeyCode::Enter, _) => Action::Select,
+        (KeyCode::Char('a'), KeyModifiers::ALT) => Action::Again,
+        (KeyCode::F(5), _) | (KeyCode::Char('r'), KeyModifiers::CONTROL) => Action::Reload,
+        (KeyCode::Char('d'), KeyModifiers::ALT) => Action::ToggleTwoLine,
+        (KeyCode::Char('s'), KeyModifiers::ALT) => Action::ToggleSort,
+        (KeyCode::Esc, _)
+        | (KeyCode::Char('q'), _)
+        | (KeyCode::Char('c'), KeyModifiers::CONTROL) => Action::ExitWithoutSelect,
+        (KeyCode::Char('k'), KeyModifiers::NONE) | (KeyCode::Up, _) => Action::Up,
+        (KeyCode::Char('j'), KeyModifiers::NONE) | (KeyCode::Down, _) => Action::Down,
+        (KeyCode::Char('u'), KeyModifiers::CONTROL) | (KeyCode::PageUp, _) => Action::PageUp,
+        (KeyCode::Char('d'), KeyModifiers::CONTROL) | (KeyCode::PageDown, _) => Action::PageDown,
+        (KeyCode::Home, _) => Action::Home,
+        (KeyCode::Char('G'), _) | (KeyCode::End, _) => Action::End,
+        (KeyCode::Char('/'), _) => Action::Search,
+        _ => Action::Ignore,
+    }
+}
+
+impl<F> Widget for &App<F>
+where
+    F: Fn(&[KeyEvent], Mode) -> Action + Clone,
+{
+    fn render(self, area: ratatui::prelude::Rect, buffer: &mut ratatui::prelude::Buffer) {
+        if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+            too_small(area, buffer);
+            *self.cursor_pos.write().unwrap() = None;
+            return;
+        }
+        let condensed = area.width < CONDENSED_WIDTH;
+        let two_line = self.two_line && !condensed;
+
+        // broken into lines instead of cut, however narrow the viewport
+        let highlighted: Vec<ratatui::text::Line> = self
+            .highlighted_text(condensed)
+            .chars()
+            .collect::<Vec<_>>()
+            .chunks(area.width.max(1) as usize)
+            .map(|chunk| chunk.iter().collect::<String>().into())
+            .collect();
+        let highlighted_height = (highlighted.len() as u16).clamp(1, (area.height / 2).max(1));
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Min(0),
+                    Constraint::Length(2),
+                    Constraint::Length(1),
+                    Constraint::Length(highlighted_height),
+                ]
+                .as_ref(),
+            )
+            .split(area);
+
+        // エントリーの部分
+        if !self.buffer.is_empty() {
+            let list_area = chunks[0];
+
+            let items_count = self.buffer.len();
+            let mut items = Vec::with_capacity(items_count);
+
+            let mut pos = Position::default();
+            // the description lines up with the text of the entry
+            let indent = " ".repeat(
+                ratatui::text::Line::from(self.config.selecting.as_str())
+                    .width()
+                    .max(ratatui::text::Line::from(self.config.no_selecting.as_str()).width())
+                    + 1,
+            );
+
+            while let Some((entry, _)) = self.buffer.next(&mut pos) {
+                let is_selected = pos.0 - 1 == items_count - self.selecting_i - 1;
+
+                let selecting_status = if is_selected {
+                    &self.config.selecting
+                } else {
+                    &self.config.no_selecting
+                };
+
+                let entry_text = if condensed {
+                    format!("{} {}", selecting_status, entry.name)
+                } else {
+                    format!("{} {}", selecting_status, entry.text.0)
+                };
+                let style = entry.text.1;
+
+                // リストアイテムを追加
+                let item = if two_line {
+                    ratatui::text::Text::from(vec![
+                        entry_text.into(),
+                        ratatui::text::Line::styled(
+                            format!("{}{}", indent, entry.description),
+                            self.dim(),
+                        ),
+                    ])
+                } else {
+                    entry_text.into()
+                };
+                items.push(
+                    ratatui::widgets::ListItem::new(item).style(crate::color::degrade(style)),
+                );
+            }
+
+            let visible_height = list_area.height as usize / if two_line { 2 } else { 1 };
+            self.page.store(visible_height.max(1), Ordering::Relaxed);
+            let reversed_selecting_index = items_count - 1 - self.selecting_i;
+
+            // 選択されたアイテムが常に表示されるようにスクロール位置を計算
+            let margin_below = 2;
+            let scroll_offset = reversed_selecting_index
+                .saturating_sub(visible_height.saturating_sub(margin_below + 1));
+
+            let start_index = scroll_offset;
+            let end_index = (scroll_offset + visible_height).min(items_count);
+
+            let items: Vec<_> = items
+                .into_iter()
+                .skip(start_index)
+                .take(end_index - start_index)
+                .collect();
+
+            List::new(items)
+                .block(Block::default())
+                .render(list_area, buffer);
+        } else {
+            let list_area = chunks[0];
+
+            Clear.render(list_area, buffer);
+        }
+        // テキスト入力部分
+        {
+            let input_area = chunks[1];
+            let input_text = format!("{}{}", self.config.prompt, self.input);
+            let prompt_width = ratatui::text::Line::from(self.config.prompt.as_str()).width();
+
+            Paragraph::new(input_text)
+                .block(if self.config.plain {
+                    // the row of the line stays, so the input doesn't move
+                    Block::default().padding(Padding::top(1))
+                } else {
+                    Block::default().borders(Borders::TOP)
+                })
+                .render(input_area, buffer);
+
+            *self.cursor_pos.write().unwrap() = Some((
+                input_area.x + (prompt_width + self.input.visual_cursor()) as u16,
+                input_area.y + 1,
+            ));
+        }
+
+        Paragraph::new(self.status())
+            .style(self.dim())
+            .render(chunks[2], buffer);
+
+        Paragraph::new(highlighted).render(chunks[3], buffer);
+    }
+}
+
+/// Centered, instead of a layout that would only be garbled at `area`'s size
+fn too_small(area: ratatui::prelude::Rect, buffer: &mut ratatui::prelude::Buffer) {
+    Clear.render(area, buffer);
+    Paragraph::new(tr("tui-too-small", &[]))
+        .alignment(ratatui::layout::Alignment::Center)
+        .wrap(ratatui::widgets::Wrap { trim: true })
+        .render(area, buffer);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys() {
+        assert_eq!(
+            parse_key("ctrl-n"),
+            Ok(KeyEvent::new(KeyCode::Char('n'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(
+            parse_key("alt-<"),
+            Ok(KeyEvent::new(KeyCode::Char('<'), KeyModifiers::ALT))
+        );
+        assert_eq!(
+            parse_key("ctrl--"),
+            Ok(KeyEvent::new(KeyCode::Char('-'), KeyModifiers::CONTROL))
+        );
+        assert_eq!(parse_key("f5"), Ok(KeyEvent::from(KeyCode::F(5))));
+        assert!(parse_key("hyper-x").is_err());
+        assert!(parse_key("f13").is_err());
+    }
+
+    #[test]
+    fn too_small_terminals() {
+        let app = App::new(TuiConfig::new(
+            Viewport::Inline(10),
+            false,
+            ">",
+            " ",
+            Keymap::Default.keybinder(vec![]),
+        ));
+        let text = |width, height| {
+            let area = ratatui::prelude::Rect::new(0, 0, width, height);
+            let mut buffer = ratatui::prelude::Buffer::empty(area);
+            (&app).render(area, &mut buffer);
+            buffer
+                .content()
+                .iter()
+                .map(|cell| cell.symbol())
+                .collect::<String>()
+        };
+
+        assert!(text(10, 10).contains("small"));
+        assert!(app.cursor_pos.read().unwrap().is_none());
+        assert!(!text(30, 10).contains("small"));
+        assert!(app.cursor_pos.read().unwrap().is_some());
+    }
+
+    #[test]
+    fn overrides_keep_typing() {
+        let bind = Keymap::Emacs.keybinder(vec![
+            (parse_key("q").unwrap(), Action::ExitWithoutSelect),
+            (parse_key("ctrl-n").unwrap(), Action::PageDown),
+        ]);
+
+        let ctrl_n = [KeyEvent::new(KeyCode::Char('n'), KeyModifiers::CONTROL)];
+        assert_eq!(bind(&ctrl_n, Mode::Insert), Action::PageDown);
+        let ctrl_p = [KeyEvent::new(KeyCode::Char('p'), KeyModifiers::CONTROL)];
+        assert_eq!(bind(&ctrl_p, Mode::Insert), Action::Up);
+        let q = [KeyEvent::from(KeyCode::Char('q'))];
+        assert_eq!(bind(&q, Mode::Insert), Action::Input);
+        assert_eq!(bind(&q, Mode::Normal), Action::ExitWithoutSelect);
+    }
+}
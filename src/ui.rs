@@ -0,0 +1,240 @@
+use std::sync::{Arc, Mutex};
+
+use crossterm::event::KeyEvent;
+use ltrait::{
+    color_eyre::eyre::{ContextCompat, Result},
+    launcher::batcher::Batcher,
+    ui::{Buffer, Position, UI},
+};
+
+use crate::Template;
+use crate::cancel;
+use crate::error::NitError;
+use crate::history;
+use crate::locale::tr;
+use crate::prompt;
+use crate::resume;
+use crate::tui::{Action, Mode, Selection, Tui, TuiEntry};
+
+/// Filled with the query when the user asked the TUI to reload, for the caller to start over
+pub type ReloadRequest = Arc<Mutex<Option<String>>>;
+
+/// `--ui`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum UiKind {
+    /// In the terminal
+    Tui,
+    /// In a desktop picker
+    Gui,
+}
+
+/// The frontends nit can drive the launcher with
+pub enum Frontend<F>
+where
+    F: Fn(&[KeyEvent], Mode) -> Action + Clone,
+{
+    Tui {
+        tui: Box<Tui<F>>,
+        reload: ReloadRequest,
+        /// Where the last query is remembered for `--resume`
+        profile: Option<String>,
+    },
+    /// Pick the best-ranked entry for the query without drawing anything
+    First { query: String },
+    /// Print the best entries as a numbered list and read the choice from stdin, where the TUI
+    /// can't be drawn
+    Numbered { query: String, limit: usize },
+    /// Hand the entries to a dmenu-compatible picker, `command` or the first one found
+    #[cfg(feature = "gui")]
+    Gui {
+        query: String,
+        command: Option<Vec<String>>,
+    },
+}
+
+impl<F> Frontend<F>
+where
+    F: Fn(&[KeyEvent], Mode) -> Action + Clone,
+{
+    /// `--ui gui`
+    pub fn gui(query: String, command: Option<Vec<String>>) -> Result<Self> {
+        #[cfg(feature = "gui")]
+        return Ok(Frontend::Gui { query, command });
+
+        #[cfg(not(feature = "gui"))]
+        {
+            let _ = (query, command);
+            ltrait::color_eyre::eyre::bail!("`--ui gui` needs nit built with the gui feature")
+        }
+    }
+}
+
+impl<F> UI<Template> for Frontend<F>
+where
+    F: Fn(&[KeyEvent], Mode) -> Action + Send + Sync + Clone,
+{
+    type Context = TuiEntry;
+
+    async fn run(&self, batcher: Batcher<Template, Self::Context>) -> Result<Option<Template>> {
+        let selected = match self {
+            Frontend::Tui {
+                tui,
+                reload,
+                profile,
+            } => {
+                let selection = tui.run(batcher).await?;
+                if let Err(e) = resume::save(profile.as_deref(), &tui.last_query()) {
+                    tracing::warn!("failed to remember the query: {e}");
+                }
+
+                match selection {
+                    Some(Selection::Entry(template)) => Ok(Some(template)),
+                    Some(Selection::Again) => history::last_used().map(Some),
+                    Some(Selection::Reload(query)) => {
+                        *reload.lock().unwrap() = Some(query);
+                        Ok(None)
+                    }
+                    None => Ok(None),
+                }
+            }
+            Frontend::First { query } => select_first(batcher, query).await.map(Some),
+            Frontend::Numbered { query, limit } => select_numbered(batcher, query, *limit).await,
+            #[cfg(feature = "gui")]
+            Frontend::Gui { query, command } => {
+                select_gui(batcher, query, command.as_deref()).await
+            }
+        };
+
+        // what is still being fetched can't be picked anymore
+        cancel::background();
+
+        selected
+    }
+}
+
+/// Every entry for `query`, the best at the end
+async fn collect<Cushion, UIContext>(
+    batcher: &mut Batcher<Cushion, UIContext>,
+    query: &str,
+) -> Result<Buffer<(UIContext, usize)>>
+where
+    Cushion: Send,
+    UIContext: Send,
+{
+    let mut buffer = Buffer::default();
+    batcher.input(&mut buffer, query);
+
+    loop {
+        let prepared = batcher.prepare().await;
+        if !batcher.merge(&mut buffer, prepared)? {
+            break;
+        }
+    }
+
+    Ok(buffer)
+}
+
+async fn select_first<Cushion, UIContext>(
+    mut batcher: Batcher<Cushion, UIContext>,
+    query: &str,
+) -> Result<Cushion>
+where
+    Cushion: Send,
+    UIContext: Send,
+{
+    let buffer = collect(&mut batcher, query).await?;
+
+    // sorters put the best entry at the end of the buffer
+    let mut pos = Position(
+        buffer
+            .len()
+            .checked_sub(1)
+            .ok_or_else(|| NitError::NoSelection {
+                query: query.to_string(),
+            })?,
+    );
+    let (_, id) = buffer
+        .next(&mut pos)
+        .wrap_err("Failed to get the best entry")?;
+
+    batcher.compute_cushion(*id)
+}
+
+async fn select_numbered<Cushion>(
+    mut batcher: Batcher<Cushion, TuiEntry>,
+    query: &str,
+    limit: usize,
+) -> Result<Option<Cushion>>
+where
+    Cushion: Send,
+{
+    let buffer = collect(&mut batcher, query).await?;
+
+    let ids: Vec<usize> = (0..buffer.len().min(limit))
+        .filter_map(|i| {
+            // best first
+            let mut pos = Position(buffer.len() - 1 - i);
+            let (entry, id) = buffer.next(&mut pos)?;
+            eprintln!("{:>3}) {}", i + 1, entry.text.0);
+            Some(*id)
+        })
+        .collect();
+    if ids.is_empty() {
+        return Err(NitError::NoSelection {
+            query: query.to_string(),
+        }
+        .into());
+    }
+
+    loop {
+        let answer = prompt::ask(&tr("prompt-select", &[("count", &ids.len())]))?;
+        if answer.is_empty() {
+            return Ok(None);
+        }
+
+        match answer.parse::<usize>() {
+            Ok(n) if (1..=ids.len()).contains(&n) => {
+                return batcher.compute_cushion(ids[n - 1]).map(Some);
+            }
+            _ => eprintln!(
+                "{}",
+                tr(
+                    "prompt-not-a-choice",
+                    &[("answer", &answer), ("count", &ids.len())]
+                )
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+async fn select_gui<Cushion>(
+    mut batcher: Batcher<Cushion, TuiEntry>,
+    query: &str,
+    command: Option<&[String]>,
+) -> Result<Option<Cushion>>
+where
+    Cushion: Send,
+{
+    let buffer = collect(&mut batcher, query).await?;
+
+    let (lines, ids): (Vec<String>, Vec<usize>) = (0..buffer.len())
+        .filter_map(|i| {
+            // best first
+            let mut pos = Position(buffer.len() - 1 - i);
+            let (entry, id) = buffer.next(&mut pos)?;
+            Some((entry.text.0.clone(), *id))
+        })
+        .unzip();
+    if ids.is_empty() {
+        return Err(NitError::NoSelection {
+            query: query.to_string(),
+        }
+        .into());
+    }
+
+    match crate::gui::pick(command, &lines)? {
+        Some(i) => batcher.compute_cushion(ids[i]).map(Some),
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,93 @@
+use std::io::IsTerminal as _;
+
+use ltrait::color_eyre::{
+    Result,
+    eyre::{bail, ensure},
+};
+
+use crate::error::NitError;
+use crate::locale::tr;
+use crate::{history, prompt};
+
+/// Remove what the most recent init created, unless it was edited since or the user agrees
+pub fn run() -> Result<()> {
+    let mut records = history::load()?;
+    let Some(record) = records.iter_mut().rev().find(|r| r.success && !r.undone) else {
+        return Err(NitError::NoHistory.into());
+    };
+
+    ensure!(
+        !record.created.is_empty(),
+        tr(
+            "undo-unrecorded",
+            &[
+                ("uri", &record.template.uri()),
+                ("target", &record.target.display())
+            ]
+        )
+    );
+
+    let mut files = vec![];
+    let mut dirs = vec![];
+    let mut modified = vec![];
+    for created in &record.created {
+        if created.path.symlink_metadata().is_err() {
+            continue;
+        }
+
+        if created.hash.is_none() {
+            dirs.push(&created.path);
+        } else {
+            if !created.unmodified()? {
+                modified.push(&created.path);
+            }
+            files.push(&created.path);
+        }
+    }
+
+    if !modified.is_empty() {
+        let list: Vec<_> = modified
+            .iter()
+            .map(|p| format!("  {}", p.display()))
+            .collect();
+        eprintln!("{}", tr("undo-edited", &[("files", &list.join("\n"))]));
+
+        if !std::io::stdin().is_terminal() {
+            bail!(tr("undo-edited-no-terminal", &[("count", &modified.len())]));
+        }
+
+        let answer = prompt::ask(&tr("prompt-remove-anyway", &[]))?;
+        if !matches!(answer.to_lowercase().as_str(), "y" | "yes") {
+            eprintln!("{}", tr("aborted", &[]));
+            return Ok(());
+        }
+    }
+
+    for file in &files {
+        std::fs::remove_file(file)?;
+    }
+    // deepest first, and only if nothing else was put in them
+    dirs.sort_by_key(|d| std::cmp::Reverse(d.components().count()));
+    for dir in dirs {
+        if std::fs::read_dir(dir)?.next().is_none() {
+            std::fs::remove_dir(dir)?;
+        }
+    }
+
+    eprintln!(
+        "{}",
+        tr(
+            "undo-removed",
+            &[
+                ("count", &files.len()),
+                ("uri", &record.template.uri()),
+                ("target", &record.target.display()),
+            ]
+        )
+    );
+
+    record.undone = true;
+    history::save(&records)?;
+
+    Ok(())
+}
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Component, Path};
+
+use ltrait::color_eyre::{Result, eyre::bail};
+
+use crate::cache;
+use crate::config::Config;
+use crate::nix::{self, NixBackend};
+use crate::readme;
+
+/// What is wrong with a template. Only errors make `validate-template` fail
+enum Problem {
+    Error(String),
+    Warning(String),
+}
+
+/// Check every template of the flake `uri` and print a FAIL or WARN line for each problem, for
+/// the CI of template repositories
+pub async fn run(nix: &impl NixBackend, uri: &str) -> Result<()> {
+    // the policy still applies when there is a config, e.g. on a developer machine
+    if let Some(config) = Config::load_optional()? {
+        config.policy.check(uri)?;
+    }
+    let nix_args = nix::args();
+
+    let show = nix.flake_show(uri, &nix_args, &[]).await?;
+    let templates = cache::parse_flake_show(&show, uri)?;
+    if templates.is_empty() {
+        bail!("{uri} has no templates");
+    }
+
+    let mut problems: Vec<(String, Problem)> = collisions(templates.iter().map(|t| &t.name[..]))
+        .into_iter()
+        .map(|p| (uri.to_string(), p))
+        .collect();
+    for t in &templates {
+        for problem in check(nix, t, &nix_args) {
+            problems.push((t.uri().to_string(), problem));
+        }
+    }
+
+    let mut errors = 0;
+    for (name, problem) in &problems {
+        match problem {
+            Problem::Error(message) => {
+                errors += 1;
+                println!("FAIL {name}: {message}");
+            }
+            Problem::Warning(message) => println!("WARN {name}: {message}"),
+        }
+    }
+    if errors > 0 {
+        bail!("{errors} problem(s) in {} template(s)", templates.len());
+    }
+    println!("{} template(s) of {uri} are fine", templates.len());
+
+    Ok(())
+}
+
+fn check(nix: &impl NixBackend, t: &crate::Template, nix_args: &[OsString]) -> Vec<Problem> {
+    let mut problems = vec![];
+
+    if t.description.trim().is_empty() {
+        problems.push(Problem::Error(
+            "the description is empty, nit and `nix flake show` list it without one".into(),
+        ));
+    }
+    if readme::welcome_text(nix, t, nix_args, &[]).is_none() {
+        problems.push(Problem::Warning(
+            "there is no welcomeText telling what to do after init".into(),
+        ));
+    }
+
+    match nix::template_path(nix, t, nix_args, &[]) {
+        Ok(path) if path.is_dir() => problems.extend(files(&path)),
+        Ok(path) => problems.push(Problem::Error(format!(
+            "the path {} is not a directory",
+            path.display()
+        ))),
+        Err(e) => problems.push(Problem::Error(format!("the path can't be evaluated: {e}"))),
+    }
+
+    problems
+}
+
+/// Names that only differ in case end up as the same directory on macOS
+fn collisions<'a>(names: impl IntoIterator<Item = &'a str>) -> Vec<Problem> {
+    let mut seen: HashMap<String, &str> = HashMap::new();
+    let mut problems = vec![];
+
+    for name in names {
+        if let Some(other) = seen.insert(name.to_lowercase(), name) {
+            problems.push(Problem::Error(format!(
+                "the templates `{other}` and `{name}` only differ in case"
+            )));
+        }
+    }
+
+    problems
+}
+
+/// The files of the template at `dir` that `nix flake init` can't copy as they are
+fn files(dir: &Path) -> Vec<Problem> {
+    let mut problems = vec![];
+    let mut empty = true;
+    let mut queue = vec![dir.to_path_buf()];
+
+    while let Some(current) = queue.pop() {
+        let Ok(entries) = std::fs::read_dir(&current) else {
+            continue;
+        };
+        let mut names: HashMap<String, String> = HashMap::new();
+
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            let relative = path
+                .strip_prefix(dir)
+                .unwrap_or(&path)
+                .display()
+                .to_string();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if let Some(other) = names.insert(name.to_lowercase(), relative.clone()) {
+                problems.push(Problem::Error(format!(
+                    "{other} and {relative} only differ in case, one overwrites the other on \
+                     macOS"
+                )));
+            }
+
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                queue.push(path);
+            } else if file_type.is_symlink() {
+                empty = false;
+                if let Ok(link) = std::fs::read_link(&path)
+                    && escapes(&link, path.parent().unwrap_or(dir), dir)
+                {
+                    problems.push(Problem::Error(format!(
+                        "the symlink {relative} points to {}, outside of the template",
+                        link.display()
+                    )));
+                }
+            } else if file_type.is_file() {
+                empty = false;
+            } else {
+                problems.push(Problem::Error(format!(
+                    "{relative} is neither a file, a directory nor a symlink"
+                )));
+            }
+        }
+    }
+
+    if empty {
+        problems.push(Problem::Error("the template has no files".into()));
+    }
+
+    problems
+}
+
+/// Whether `link` in `from` leaves `root` once copied out of the nix store
+fn escapes(link: &Path, from: &Path, root: &Path) -> bool {
+    if link.is_absolute() {
+        return true;
+    }
+
+    let mut depth = from
+        .strip_prefix(root)
+        .map_or(0, |p| p.components().count());
+    for component in link.components() {
+        match component {
+            Component::ParentDir if depth == 0 => return true,
+            Component::ParentDir => depth -= 1,
+            Component::Normal(_) => depth += 1,
+            _ => {}
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(problems: &[Problem]) -> Vec<&str> {
+        problems
+            .iter()
+            .filter_map(|p| match p {
+                Problem::Error(message) => Some(message.as_str()),
+                Problem::Warning(_) => None,
+            })
+            .collect()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks() {
+        let root = Path::new("/t");
+        assert!(!escapes(Path::new("flake.nix"), root, root));
+        assert!(!escapes(
+            Path::new("../flake.nix"),
+            Path::new("/t/nix"),
+            root
+        ));
+        assert!(escapes(Path::new("../flake.nix"), root, root));
+        assert!(escapes(Path::new("/etc/passwd"), root, root));
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(errors(&collisions(["rust", "go", "Rust"])).len(), 1);
+        assert!(errors(&collisions(["rust", "go"])).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn template_files() {
+        let dir = std::env::temp_dir().join(format!("nit-validate-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(errors(&files(&dir)), ["the template has no files"]);
+
+        std::fs::write(dir.join("flake.nix"), "{ }").unwrap();
+        std::os::unix::fs::symlink("flake.nix", dir.join("default.nix")).unwrap();
+        assert!(errors(&files(&dir)).is_empty());
+
+        std::os::unix::fs::symlink("../outside", dir.join("escaping")).unwrap();
+        assert_eq!(errors(&files(&dir)).len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,159 @@
+use std::io::IsTerminal as _;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ltrait::color_eyre::Result;
+
+use crate::cache::{self, CacheStore as _};
+use crate::config::{Config, Entry, Snippet};
+use crate::events::{self, Event};
+use crate::locale::tr;
+use crate::prompt;
+
+/// Offered on the first run, as `(name, uri, catalog id of the description)`
+const POPULAR: &[(&str, &str, &str)] = &[
+    ("NixOS", "github:NixOS/templates", "wizard-popular-nixos"),
+    (
+        "nix-community",
+        "github:nix-community/templates",
+        "wizard-popular-nix-community",
+    ),
+    (
+        "the-nix-way",
+        "github:the-nix-way/dev-templates",
+        "wizard-popular-the-nix-way",
+    ),
+];
+
+/// Whether a launch is the first one: there is neither a config nor a cache, and somebody at a
+/// terminal to ask
+pub fn needed(profile: Option<&str>) -> Result<bool> {
+    Ok(profile.is_none()
+        && std::io::stdin().is_terminal()
+        && std::io::stderr().is_terminal()
+        && Config::load_optional()?.is_none()
+        && !cache::store(None, None)?.exists())
+}
+
+/// Ask which of [`POPULAR`] to start with, write them into a new config and fetch their
+/// templates. `false` if nothing was added, and there is still nothing to pick from
+pub async fn run() -> Result<bool> {
+    eprintln!("{}", tr("wizard-welcome", &[]));
+    for (i, (name, uri, description)) in POPULAR.iter().enumerate() {
+        eprintln!("  {}. {name} ({uri}): {}", i + 1, tr(description, &[]));
+    }
+
+    let chosen = loop {
+        if let Some(chosen) = choose(&prompt::ask(&tr("prompt-wizard-flakes", &[]))?) {
+            break chosen;
+        }
+    };
+    let path = Config::path()?;
+    if chosen.is_empty() {
+        eprintln!("{}", tr("wizard-skipped", &[("path", &path.display())]));
+        return Ok(false);
+    }
+
+    let snippet = Snippet {
+        template: chosen
+            .iter()
+            .map(|&i| Entry {
+                name: POPULAR[i].0,
+                uri: POPULAR[i].1,
+                tags: &[],
+            })
+            .collect(),
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(
+        &path,
+        format!(
+            "# {}\n\n{}",
+            tr("wizard-config-header", &[]),
+            toml::to_string(&snippet)?
+        ),
+    )?;
+
+    let count = chosen.len();
+    eprintln!(
+        "{}",
+        tr(
+            "wizard-written",
+            &[("path", &path.display()), ("count", &count)]
+        )
+    );
+    let done = AtomicUsize::new(0);
+    events::watch(move |event| {
+        if let Event::FetchFinished {
+            uri,
+            templates,
+            error,
+        } = event
+        {
+            let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+            let message = match error {
+                None => tr(
+                    "wizard-fetched",
+                    &[
+                        ("done", &done),
+                        ("count", &count),
+                        ("uri", uri),
+                        ("templates", templates),
+                    ],
+                ),
+                Some(error) => tr(
+                    "wizard-fetch-failed",
+                    &[
+                        ("done", &done),
+                        ("count", &count),
+                        ("uri", uri),
+                        ("error", error),
+                    ],
+                ),
+            };
+            eprintln!("{message}");
+        }
+    });
+
+    cache::load_cache(true, false, None, cache::Defaults::Show, None, false).await?;
+
+    Ok(true)
+}
+
+/// The indices into [`POPULAR`] `answer` picks, `None` if it isn't an answer
+fn choose(answer: &str) -> Option<Vec<usize>> {
+    match answer.to_lowercase().as_str() {
+        "" | "a" | "all" => return Some((0..POPULAR.len()).collect()),
+        "n" | "no" | "none" => return Some(vec![]),
+        _ => {}
+    }
+
+    let mut chosen = vec![];
+    for number in answer.split([' ', ',']).filter(|s| !s.is_empty()) {
+        let i = number.parse::<usize>().ok()?.checked_sub(1)?;
+        if i >= POPULAR.len() {
+            return None;
+        }
+        if !chosen.contains(&i) {
+            chosen.push(i);
+        }
+    }
+
+    Some(chosen)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers() {
+        assert_eq!(choose(""), Some(vec![0, 1, 2]));
+        assert_eq!(choose("N"), Some(vec![]));
+        assert_eq!(choose("3, 1 3"), Some(vec![2, 0]));
+        assert_eq!(choose("0"), None);
+        assert_eq!(choose("4"), None);
+        assert_eq!(choose("rust"), None);
+    }
+}